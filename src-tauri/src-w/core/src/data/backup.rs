@@ -0,0 +1,397 @@
+use crate::DIR_STAGING;
+use crate::data::library::LIB_TABLE;
+use crate::data::manifest::MANIFEST_TABLE;
+use crate::data::metadata::Metadata;
+use crate::data::schema::{MIGRATION_BACKUP_DIR, SCHEMA_VERSION, SchemaError, schema_version};
+use crate::util::config::config_get;
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use log::{info, warn};
+use redb::{Database, ReadableTable, ReadableTableMetadata};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use thiserror::Error;
+
+const BACKUP_DIR: &str = "backup";
+const SNAPSHOT_DIR: &str = "snapshot";
+const BACKUP_EXT: &str = "bin";
+
+/// Extension added to the name of a backup for its header, e.g. `library.bin.json`
+const HEADER_EXT: &str = "json";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub enum BackupKind {
+    /// Taken each time the library is opened, rotated by the configured retention
+    Auto,
+
+    /// Taken on demand with a name, kept until deleted
+    Snapshot,
+
+    /// Taken before a schema migration, kept until deleted
+    Migration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    /// Path relative to the backup directory, identifies the backup in commands
+    pub id: String,
+    pub kind: BackupKind,
+
+    /// Name given to a snapshot
+    pub name: Option<String>,
+    pub time: DateTime<Utc>,
+    pub size: u64,
+
+    /// Count of entries, unset if the backup has no header
+    pub entries: Option<usize>,
+
+    /// Why the backup cannot be restored, found from its header
+    pub error: Option<String>,
+}
+
+/// Summary kept next to a backup, so backups are listed without opening them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BackupHeader {
+    schema_version: u64,
+    entries: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupRestoreReport {
+    /// Count of entries restored
+    pub entries: usize,
+
+    /// Records in the backup which could not be decoded and were skipped
+    pub undecodable: usize,
+
+    /// Id of the snapshot taken of the library before the restore
+    pub snapshot: String,
+}
+
+/// Content of a backup which passed validation
+#[derive(Debug, Clone)]
+pub(crate) struct BackupContent {
+    pub entries: Vec<Metadata>,
+
+    /// Records which could not be decoded, left out of `entries`
+    pub undecodable: usize,
+}
+
+fn backup_dir() -> PathBuf {
+    config_get().get_root().join(BACKUP_DIR)
+}
+
+fn header_path(backup: &Path) -> PathBuf {
+    let mut name = backup.as_os_str().to_owned();
+    name.push(format!(".{HEADER_EXT}"));
+    PathBuf::from(name)
+}
+
+/// Header of a backup, `None` for backups taken by earlier versions or if it is unreadable
+fn header_read(backup: &Path) -> Option<BackupHeader> {
+    let raw = fs::read(header_path(backup)).ok()?;
+    serde_json::from_slice(&raw)
+        .inspect_err(|e| warn!("Invalid header of backup {}: {}", backup.display(), e))
+        .ok()
+}
+
+/// Record the header of `backup`, which has to be a copy of `db` as it is now
+pub(crate) fn backup_describe(db: &Database, backup: &Path) -> BackupResult<()> {
+    let read = db.begin_read()?;
+    let header = BackupHeader {
+        schema_version: schema_version(&read)?,
+        entries: read.open_table(LIB_TABLE)?.len()? as usize,
+    };
+    fs::write(header_path(backup), serde_json::to_vec(&header)?)?;
+    Ok(())
+}
+
+/// Copy a backup along with its header
+pub(crate) fn backup_copy(backup: &Path, target: &Path) -> std::io::Result<()> {
+    fs::copy(backup, target)?;
+    let header = header_path(backup);
+    if header.exists() {
+        fs::copy(header, header_path(target))?;
+    }
+    Ok(())
+}
+
+fn backup_remove(backup: &Path) -> std::io::Result<()> {
+    fs::remove_file(backup)?;
+    let header = header_path(backup);
+    if header.exists() {
+        fs::remove_file(header)?;
+    }
+    Ok(())
+}
+
+fn file_time(path: &Path) -> DateTime<Utc> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+        .into()
+}
+
+/// Backup files directly in `dir`, the oldest first
+fn backup_files(dir: &Path) -> BackupResult<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| path.is_file())
+        .filter(|path| path.extension().is_some_and(|ext| ext == BACKUP_EXT))
+        .collect::<Vec<_>>();
+    files.sort_by_key(|path| file_time(path));
+    Ok(files)
+}
+
+/// Copy the library file into the backup directory before it is opened, then drop
+/// automatic backups beyond the configured retention. Returns the backup taken, if any.
+pub(crate) fn backup_on_open(bin_path: &Path) -> BackupResult<Option<PathBuf>> {
+    let dir = backup_dir();
+    fs::create_dir_all(&dir)?;
+    if !bin_path.exists() {
+        return Ok(None);
+    }
+
+    let timestamp = Local::now().format("%Y%m%d%H%M%S");
+    let stem = bin_path.file_stem().unwrap_or_default().to_string_lossy();
+    let backup = dir.join(format!("{stem}.{timestamp}.{BACKUP_EXT}"));
+    fs::copy(bin_path, &backup)?;
+
+    let keep_count = config_get().get_backup_keep_count();
+    let keep_days = config_get().get_backup_keep_days();
+    let deadline = Utc::now() - TimeDelta::days(keep_days.into());
+    let files = backup_files(&dir)?;
+    let excess = match keep_count {
+        0 => 0,
+        count => files.len().saturating_sub(count),
+    };
+    for (index, path) in files.iter().enumerate() {
+        let expired = keep_days > 0 && file_time(path) < deadline && *path != backup;
+        if (index < excess || expired)
+            && let Err(e) = backup_remove(path)
+        {
+            warn!("Failed to remove old backup {}: {}", path.display(), e);
+        }
+    }
+    Ok(Some(backup))
+}
+
+/// Copy the open library into a named snapshot
+pub(crate) fn backup_snapshot(db: &Database, bin_path: &Path, name: &str) -> BackupResult<String> {
+    let name = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() || c == '-' || c == '_' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    if name.is_empty() {
+        return Err(BackupError::InvalidName);
+    }
+    let dir = backup_dir().join(SNAPSHOT_DIR);
+    fs::create_dir_all(&dir)?;
+    let timestamp = Local::now().format("%Y%m%d%H%M%S");
+    let file_name = format!("{name}.{timestamp}.{BACKUP_EXT}");
+
+    // An idle write transaction keeps other writers out while the file is copied
+    let write = db.begin_write()?;
+    let path = dir.join(&file_name);
+    fs::copy(bin_path, &path)?;
+    if let Err(e) = backup_describe(db, &path) {
+        warn!("Failed to describe snapshot {}: {}", file_name, e);
+    }
+    write.abort()?;
+
+    info!("Created library snapshot {}", file_name);
+    Ok(format!("{SNAPSHOT_DIR}/{file_name}"))
+}
+
+/// Resolve a backup id, which has to point at a backup file inside the backup directory
+fn backup_path(id: &str) -> BackupResult<PathBuf> {
+    let relative = Path::new(id);
+    let valid = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        && relative.extension().is_some_and(|ext| ext == BACKUP_EXT);
+    let path = backup_dir().join(relative);
+    if !valid || !path.is_file() {
+        return Err(BackupError::NotFound(id.to_string()));
+    }
+    Ok(path)
+}
+
+/// Open a copy of the backup and decode its entries. Fails if it is not a readable
+/// library or it was written by a newer schema.
+pub(crate) fn backup_load(id: &str) -> BackupResult<BackupContent> {
+    let path = backup_path(id)?;
+    // Opening a database may repair it in place, the backup itself stays untouched
+    let staging = config_get().get_root().join(DIR_STAGING);
+    fs::create_dir_all(&staging)?;
+    let copy = staging.join(format!("{}.{BACKUP_EXT}", uuid::Uuid::new_v4()));
+    fs::copy(&path, &copy)?;
+    let result = load_copy(&copy);
+    if let Err(e) = fs::remove_file(&copy) {
+        warn!("Failed to remove staged backup {}: {}", copy.display(), e);
+    }
+    result
+}
+
+fn load_copy(path: &Path) -> BackupResult<BackupContent> {
+    let db = Database::open(path)?;
+    let read = db.begin_read()?;
+    let version = schema_version(&read)?;
+    if version > SCHEMA_VERSION {
+        return Err(SchemaError::UnsupportedVersion(version).into());
+    }
+
+    let table = read.open_table(LIB_TABLE)?;
+    let mut content = BackupContent {
+        entries: Vec::with_capacity(table.len()? as usize),
+        undecodable: 0,
+    };
+    // Backups from before manifests were stored apart have them embedded in the entries
+    let manifests = match read.open_table(MANIFEST_TABLE) {
+        Ok(manifests) => Some(manifests),
+        Err(redb::TableError::TableDoesNotExist(_)) => None,
+        Err(e) => return Err(e.into()),
+    };
+    for entry in table.iter()? {
+        match bson::from_slice::<Metadata>(&entry?.1.value()) {
+            Ok(mut metadata) => {
+                metadata.upgrade_legacy_deploy();
+                if let Some(manifests) = &manifests {
+                    for deployment in &mut metadata.deployments {
+                        if let Some(raw) = manifests.get(deployment.id.as_str())? {
+                            deployment.manifest = bson::from_slice(&raw.value()).ok();
+                        }
+                    }
+                }
+                content.entries.push(metadata);
+            }
+            Err(_) => content.undecodable += 1,
+        }
+    }
+    Ok(content)
+}
+
+/// All backups with their entry counts as far as known, the latest first.
+/// Backups are only told from their files and headers, see [`backup_load`] for validation.
+pub(crate) fn backup_list() -> BackupResult<Vec<BackupInfo>> {
+    let dir = backup_dir();
+    let kinds = [
+        (BackupKind::Auto, None),
+        (BackupKind::Snapshot, Some(SNAPSHOT_DIR)),
+        (BackupKind::Migration, Some(MIGRATION_BACKUP_DIR)),
+    ];
+    let mut backups = Vec::new();
+    for (kind, sub) in kinds {
+        let kind_dir = sub.map_or_else(|| dir.clone(), |sub| dir.join(sub));
+        for path in backup_files(&kind_dir)? {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let id = match sub {
+                Some(sub) => format!("{sub}/{file_name}"),
+                None => file_name.to_string(),
+            };
+            let name = (kind == BackupKind::Snapshot)
+                .then(|| file_name.rsplitn(3, '.').nth(2).map(str::to_string))
+                .flatten();
+            let header = header_read(&path);
+            let error = header
+                .as_ref()
+                .filter(|header| header.schema_version > SCHEMA_VERSION)
+                .map(|header| SchemaError::UnsupportedVersion(header.schema_version).to_string());
+            backups.push(BackupInfo {
+                time: file_time(&path),
+                size: fs::metadata(&path).map(|m| m.len()).unwrap_or_default(),
+                id,
+                kind,
+                name,
+                entries: header.map(|header| header.entries),
+                error,
+            });
+        }
+    }
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.time));
+    Ok(backups)
+}
+
+pub(crate) fn backup_delete(id: &str) -> BackupResult<()> {
+    backup_remove(&backup_path(id)?)?;
+    info!("Deleted backup {}", id);
+    Ok(())
+}
+
+pub(crate) type BackupResult<T> = Result<T, BackupError>;
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("Backup {0} not found")]
+    NotFound(String),
+
+    #[error("Snapshot name must not be empty")]
+    InvalidName,
+
+    #[error("Backup schema error: {0}")]
+    SchemaError(#[from] SchemaError),
+
+    #[error("Backup database error: {0}")]
+    DatabaseError(#[from] redb::DatabaseError),
+
+    #[error("Backup transaction error: {0}")]
+    TransactionError(Box<redb::TransactionError>),
+
+    #[error("Backup table error: {0}")]
+    TableError(#[from] redb::TableError),
+
+    #[error("Backup storage error: {0}")]
+    StorageError(#[from] redb::StorageError),
+
+    #[error("Backup header error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("File system error: {0}")]
+    FileSystemError(#[from] std::io::Error),
+}
+
+impl From<redb::TransactionError> for BackupError {
+    fn from(e: redb::TransactionError) -> Self {
+        BackupError::TransactionError(Box::new(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_follows_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup = dir.path().join(format!("library.{BACKUP_EXT}"));
+        let db = Database::create(&backup).unwrap();
+        let write = db.begin_write().unwrap();
+        {
+            let mut table = write.open_table(LIB_TABLE).unwrap();
+            table.insert("a", vec![0u8]).unwrap();
+            table.insert("b", vec![0u8]).unwrap();
+        }
+        write.commit().unwrap();
+
+        backup_describe(&db, &backup).unwrap();
+        let header = header_read(&backup).unwrap();
+        assert_eq!(header.schema_version, 0);
+        assert_eq!(header.entries, 2);
+        assert_eq!(backup_files(dir.path()).unwrap(), vec![backup.clone()]);
+
+        let copy = dir.path().join(format!("copy.{BACKUP_EXT}"));
+        backup_copy(&backup, &copy).unwrap();
+        assert_eq!(header_read(&copy).unwrap().entries, 2);
+        backup_remove(&copy).unwrap();
+        assert!(!header_path(&copy).exists());
+    }
+}
@@ -0,0 +1,643 @@
+use crate::data::import::{ImportItem, ImportReport};
+use crate::data::metadata::{ArchiveInfo, Metadata};
+use crate::util::config::config_get;
+use crate::util::hash::{hash_file, hash_reader};
+use crate::util::path_ext::PathExt;
+use crate::util::progress::ProgressHandle;
+use crate::{DIR_ARCHIVE, DIR_COVER, DIR_STAGING};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tar::{Archive, Builder, Header};
+use thiserror::Error;
+
+/// Name of the manifest, always the first entry of a bundle
+const BUNDLE_MANIFEST: &str = "manifest.json";
+
+/// Version written to new bundles, bundles of newer versions are refused.
+/// Version 2 added covers.
+const BUNDLE_VERSION: u32 = 2;
+
+/// Directory within the archive and cover directories for files from outside of them
+const BUNDLE_EXTERNAL_DIR: &str = "Bundle";
+
+pub(crate) const BUNDLE_EXT: &str = "tar";
+const BUNDLE_PART_EXT: &str = "part";
+
+/// Content of the manifest of a bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub version: u32,
+    pub time_created: DateTime<Utc>,
+    pub entries: Vec<BundleEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleEntry {
+    /// The entry without its deployments, which only apply to the machine it was exported from
+    pub metadata: Metadata,
+
+    /// Archive of the entry, unset if it was exported without one
+    pub file: Option<BundleFile>,
+
+    /// Cover image of the entry, unset if it has none or it could not be found
+    #[serde(default)]
+    pub cover: Option<BundleFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct BundleFile {
+    /// Path within the bundle, which is also where the file is placed relative to the data root
+    pub path: String,
+    pub size: u64,
+
+    /// BLAKE3 of the file, see [`hash_file`]
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleExportReport {
+    pub path: String,
+
+    /// Count of entries exported
+    pub entries: usize,
+
+    /// Count of archives packed, entries sharing an archive are counted once
+    pub archives: usize,
+
+    /// Count of covers packed, counted like `archives`
+    #[serde(default)]
+    pub covers: usize,
+    pub bytes: u64,
+
+    /// Titles of entries exported without an archive, as they have none, it is not an archive
+    /// file or it could not be found
+    pub without_archive: Vec<String>,
+}
+
+/// What a bundle import did, or would do for a dry run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleImportReport {
+    #[serde(flatten)]
+    pub import: ImportReport,
+
+    /// Archives and covers placed under the data root, relative to it
+    pub extracted: Vec<String>,
+
+    /// Archives and covers already under the data root with the same content, which were used
+    /// as they are
+    pub reused: Vec<String>,
+
+    /// Entries left out as their archive or cover was missing from the bundle or damaged
+    pub damaged: Vec<ImportItem>,
+}
+
+/// Where an archive or cover of a bundle is placed under the data root
+#[derive(Debug, Clone)]
+pub(crate) struct BundleTarget {
+    /// Path relative to the data root, as stored in [`ArchiveInfo::ArchiveFile`] or
+    /// [`Metadata::cover`]
+    pub path: String,
+
+    /// Whether a file with the same content is there already
+    pub exists: bool,
+}
+
+/// Tar header for a file of `size` bytes, the manifest has no file to take it from
+fn file_header(size: u64) -> Header {
+    let mut header = Header::new_gnu();
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp().max(0) as u64);
+    header.set_entry_type(tar::EntryType::Regular);
+    header
+}
+
+/// Path of an archive or cover within a bundle, always under `dir` of the data root. Files
+/// already there keep their place, any other goes into a directory of its entry.
+fn bundle_file_path(root: &Path, dir: &str, metadata: &Metadata, source: &Path) -> String {
+    if let Ok(canonical) = source.canonicalize()
+        && canonical.starts_with(root.join(dir))
+    {
+        return canonical.relative_name(root);
+    }
+    let name = source.file_name().unwrap_or_default().to_string_lossy();
+    format!("{dir}/{BUNDLE_EXTERNAL_DIR}/{}/{name}", metadata.id)
+}
+
+/// Write the manifest and then the files in `files` as `(source, path within the bundle)`
+fn write_bundle(
+    target: &Path,
+    manifest: &BundleManifest,
+    files: &[(PathBuf, String)],
+    progress: &ProgressHandle,
+) -> BundleResult<()> {
+    let mut builder = Builder::new(BufWriter::new(File::create(target)?));
+    let data = serde_json::to_vec_pretty(manifest)?;
+    builder.append_data(
+        &mut file_header(data.len() as u64),
+        BUNDLE_MANIFEST,
+        data.as_slice(),
+    )?;
+    for (source, name) in files {
+        progress.start_file(name);
+        let file = File::open(source)?;
+        let mut header = Header::new_gnu();
+        header.set_metadata(&file.metadata()?);
+        builder.append_data(&mut header, name, progress.reader(file))?;
+        progress.finish_file();
+    }
+    builder.into_inner()?.flush()?;
+    Ok(())
+}
+
+/// Write `entries` with their archive files and covers into a bundle at `path`.
+///
+/// Files are hashed in a first pass so the manifest can come first, letting an import
+/// read it without going through the files.
+pub(crate) fn bundle_write(
+    path: &Path,
+    entries: Vec<Metadata>,
+    progress: &ProgressHandle,
+) -> BundleResult<BundleExportReport> {
+    let root = config_get().get_root().canonicalize()?;
+    // Entries sharing an archive or cover refer to the same file
+    let mut sources: HashMap<PathBuf, usize> = HashMap::new();
+    let mut files: Vec<(PathBuf, BundleFile)> = Vec::new();
+    let (mut archives, mut covers) = (0, 0);
+    let mut add_file = |dir: &str, metadata: &Metadata, source: PathBuf| -> BundleResult<usize> {
+        if let Some(index) = sources.get(&source) {
+            return Ok(*index);
+        }
+        let file = BundleFile {
+            path: bundle_file_path(&root, dir, metadata, &source),
+            size: source.metadata()?.len(),
+            hash: String::new(),
+        };
+        sources.insert(source.clone(), files.len());
+        files.push((source, file));
+        match dir {
+            DIR_ARCHIVE => archives += 1,
+            _ => covers += 1,
+        }
+        Ok(files.len() - 1)
+    };
+    let mut without_archive = Vec::new();
+    let mut planned = Vec::with_capacity(entries.len());
+    for mut metadata in entries {
+        metadata.deployments.clear();
+        let cover = metadata
+            .cover
+            .as_ref()
+            .map(|cover| config_get().resolve_to_root(Path::new(cover)))
+            .filter(|source| source.is_file());
+        let cover = match cover {
+            Some(source) => Some(add_file(DIR_COVER, &metadata, source)?),
+            None => None,
+        };
+        let source = match &metadata.archive_info {
+            ArchiveInfo::ArchiveFile { path, .. } => {
+                Some(config_get().resolve_to_root(Path::new(path)))
+            }
+            _ => None,
+        }
+        .filter(|source| source.is_file());
+        let Some(source) = source else {
+            without_archive.push(metadata.title.clone());
+            planned.push((metadata, None, cover));
+            continue;
+        };
+        let index = add_file(DIR_ARCHIVE, &metadata, source)?;
+        planned.push((metadata, Some(index), cover));
+    }
+
+    let bytes = files.iter().map(|(_, file)| file.size).sum::<u64>();
+    progress.set_total(bytes * 2, files.len() as u64 * 2);
+    for (source, file) in &mut files {
+        progress.check()?;
+        progress.start_file(&file.path);
+        file.hash = hash_reader(progress.reader(File::open(source)?))?;
+        progress.finish_file();
+    }
+
+    let manifest = BundleManifest {
+        version: BUNDLE_VERSION,
+        time_created: Utc::now(),
+        entries: planned
+            .into_iter()
+            .map(|(metadata, index, cover)| BundleEntry {
+                metadata,
+                file: index.map(|index| files[index].1.clone()),
+                cover: cover.map(|index| files[index].1.clone()),
+            })
+            .collect(),
+    };
+    let files = files
+        .into_iter()
+        .map(|(source, file)| (source, file.path))
+        .collect::<Vec<_>>();
+    // Written aside first, a cancelled export leaves no bundle behind
+    let part = PathBuf::from(format!("{}.{BUNDLE_PART_EXT}", path.display()));
+    if let Err(e) = write_bundle(&part, &manifest, &files, progress) {
+        let _ = fs::remove_file(&part);
+        return Err(e);
+    }
+    fs::rename(&part, path)?;
+    progress.flush();
+
+    info!(
+        "Bundle of {} entries, {} archives and {} covers written to {}",
+        manifest.entries.len(),
+        archives,
+        covers,
+        path.display()
+    );
+    Ok(BundleExportReport {
+        path: path.to_string_lossy().to_string(),
+        entries: manifest.entries.len(),
+        archives,
+        covers,
+        bytes,
+        without_archive,
+    })
+}
+
+/// Read the manifest of the bundle at `path`
+pub(crate) fn bundle_manifest(path: &Path) -> BundleResult<BundleManifest> {
+    let mut archive = Archive::new(File::open(path)?);
+    // Anything but a tar starting with the manifest is not a bundle
+    let Some(Ok(entry)) = archive.entries()?.next() else {
+        return Err(BundleError::ManifestMissing);
+    };
+    if entry.path()?.as_ref() != Path::new(BUNDLE_MANIFEST) {
+        return Err(BundleError::ManifestMissing);
+    }
+    let manifest: BundleManifest = serde_json::from_reader(entry)?;
+    if manifest.version > BUNDLE_VERSION {
+        return Err(BundleError::UnsupportedVersion(manifest.version));
+    }
+    Ok(manifest)
+}
+
+/// Whether the file at `path` has the content described by `file`
+fn same_content(path: &Path, file: &BundleFile) -> bool {
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.len() == file.size)
+        && hash_file(path).is_ok_and(|hash| hash == file.hash)
+}
+
+/// Decide where an archive or cover of a bundle is placed under `dir` of the data root.
+/// A different file already there is kept, the file is then placed next to it with a numbered
+/// name. `taken` holds the places given to other files of the same bundle.
+///
+/// Files are only placed under `dir`, paths outside it are moved into [`BUNDLE_EXTERNAL_DIR`]
+/// as earlier exports could carry them.
+pub(crate) fn bundle_target(
+    file: &BundleFile,
+    dir: &str,
+    taken: &mut HashSet<String>,
+) -> BundleResult<BundleTarget> {
+    let path = Path::new(&file.path);
+    let valid = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        && path.file_name().is_some();
+    if !valid {
+        return Err(BundleError::InvalidPath(file.path.clone()));
+    }
+    let relative = if path.starts_with(dir) && path != Path::new(dir) {
+        path.to_path_buf()
+    } else {
+        Path::new(dir).join(BUNDLE_EXTERNAL_DIR).join(path)
+    };
+
+    let root = config_get().get_root();
+    let name = relative.file_name().unwrap_or_default().to_string_lossy();
+    let (stem, ext) = match name.split_once('.') {
+        Some((stem, ext)) => (stem.to_string(), format!(".{ext}")),
+        None => (name.to_string(), String::new()),
+    };
+    let parent = relative.parent().unwrap_or(Path::new(dir));
+    let mut candidate = relative.relative_name(Path::new(""));
+    for n in 1.. {
+        if !taken.contains(&candidate) {
+            let target = root.join(&candidate);
+            if !target.exists() {
+                break;
+            }
+            if same_content(&target, file) {
+                taken.insert(candidate.clone());
+                return Ok(BundleTarget {
+                    path: candidate,
+                    exists: true,
+                });
+            }
+        }
+        candidate = parent
+            .join(format!("{stem} ({n}){ext}"))
+            .relative_name(Path::new(""));
+    }
+    taken.insert(candidate.clone());
+    Ok(BundleTarget {
+        path: candidate,
+        exists: false,
+    })
+}
+
+/// Copy `reader` to `target`, returns the count of bytes copied and their hash
+fn copy_hashed(reader: impl Read, target: &Path) -> BundleResult<(u64, String)> {
+    let mut reader = reader;
+    let mut writer = BufWriter::new(File::create(target)?);
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0; 1 << 16];
+    let mut size = 0;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        writer.write_all(&buffer[..read])?;
+        size += read as u64;
+    }
+    writer.flush()?;
+    Ok((size, hasher.finalize().to_hex().to_string()))
+}
+
+/// Copy an entry of the bundle to `target` through the staging directory,
+/// false if its content does not match `file`
+fn extract_file(
+    reader: impl Read,
+    target: &Path,
+    file: &BundleFile,
+    progress: &ProgressHandle,
+) -> BundleResult<bool> {
+    let staging = config_get().get_root().join(DIR_STAGING);
+    fs::create_dir_all(&staging)?;
+    let part = staging.join(format!("{}.{BUNDLE_PART_EXT}", uuid::Uuid::new_v4()));
+    let (size, hash) = match copy_hashed(progress.reader(reader), &part) {
+        Ok(copied) => copied,
+        Err(e) => {
+            let _ = fs::remove_file(&part);
+            return Err(e);
+        }
+    };
+    if size != file.size || hash != file.hash {
+        warn!("File {} in bundle is damaged", file.path);
+        fs::remove_file(&part)?;
+        return Ok(false);
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(&part, target)?;
+    Ok(true)
+}
+
+/// Go through the bundle placing the files in `files`, see [`bundle_extract`]
+fn extract_files(
+    path: &Path,
+    files: &HashMap<String, (BundleFile, BundleTarget)>,
+    pending: &mut HashSet<String>,
+    damaged: &mut HashSet<String>,
+    placed: &mut Vec<PathBuf>,
+    progress: &ProgressHandle,
+) -> BundleResult<()> {
+    let root = config_get().get_root();
+    let mut archive = Archive::new(File::open(path)?);
+    for entry in archive.entries()? {
+        if pending.is_empty() {
+            break;
+        }
+        progress.check()?;
+        let entry = entry?;
+        let name = entry.path()?.relative_name(Path::new(""));
+        let Some((file, target)) = pending.take(&name).and_then(|name| files.get(&name)) else {
+            continue;
+        };
+        progress.start_file(&name);
+        let target_path = root.join(&target.path);
+        if extract_file(entry, &target_path, file, progress)? {
+            placed.push(target_path);
+        } else {
+            damaged.insert(name);
+        }
+        progress.finish_file();
+    }
+    Ok(())
+}
+
+/// Place the archives and covers of the bundle at `path` listed in `files`, keyed by their path
+/// within the bundle, at their targets. Returns the files which were missing or damaged, the
+/// files already placed are removed again on error.
+pub(crate) fn bundle_extract(
+    path: &Path,
+    files: &HashMap<String, (BundleFile, BundleTarget)>,
+    progress: &ProgressHandle,
+) -> BundleResult<HashSet<String>> {
+    let mut pending: HashSet<String> = files.keys().cloned().collect();
+    let mut damaged = HashSet::new();
+    let mut placed = Vec::new();
+    progress.set_total(
+        files.values().map(|(file, _)| file.size).sum(),
+        files.len() as u64,
+    );
+    let extracted = extract_files(
+        path,
+        files,
+        &mut pending,
+        &mut damaged,
+        &mut placed,
+        progress,
+    );
+    if let Err(e) = extracted {
+        for target in placed {
+            let _ = fs::remove_file(target);
+        }
+        return Err(e);
+    }
+    progress.flush();
+    info!(
+        "Extracted {} files from bundle {}",
+        placed.len(),
+        path.display()
+    );
+    damaged.extend(pending);
+    Ok(damaged)
+}
+
+pub(crate) type BundleResult<T> = Result<T, BundleError>;
+
+#[derive(Debug, Error)]
+pub enum BundleError {
+    #[error("Not a bundle, the manifest is missing")]
+    ManifestMissing,
+
+    #[error("Bundle version {0} is not supported, update the application to import it")]
+    UnsupportedVersion(u32),
+
+    #[error("Invalid file path {0} in bundle")]
+    InvalidPath(String),
+
+    #[error("Bundle manifest error: {0}")]
+    ManifestError(#[from] serde_json::Error),
+
+    #[error("File system error: {0}")]
+    FileSystemError(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::metadata::MetadataBuilder;
+    use crate::util::config::config_init_test;
+
+    fn bundle_file(path: &str) -> BundleFile {
+        BundleFile {
+            path: path.to_string(),
+            size: 4,
+            hash: blake3::hash(b"data").to_hex().to_string(),
+        }
+    }
+
+    #[test]
+    fn target_rejects_paths_leaving_the_root() {
+        config_init_test();
+        for path in [
+            "",
+            "/etc/passwd",
+            "../game.7z",
+            "archive/../../game.7z",
+            "./game.7z",
+        ] {
+            let target = bundle_target(&bundle_file(path), DIR_ARCHIVE, &mut HashSet::new());
+            assert!(
+                matches!(target, Err(BundleError::InvalidPath(_))),
+                "{path} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn target_moves_paths_into_the_archive_directory() {
+        config_init_test();
+        let id = uuid::Uuid::new_v4().to_string();
+        let path = format!("{id}/game.7z");
+        let target = bundle_target(&bundle_file(&path), DIR_ARCHIVE, &mut HashSet::new()).unwrap();
+        assert_eq!(
+            target.path,
+            format!("archive/{BUNDLE_EXTERNAL_DIR}/{id}/game.7z")
+        );
+
+        let target =
+            bundle_target(&bundle_file("archive"), DIR_ARCHIVE, &mut HashSet::new()).unwrap();
+        assert_eq!(
+            target.path,
+            format!("archive/{BUNDLE_EXTERNAL_DIR}/archive")
+        );
+    }
+
+    #[test]
+    fn target_numbers_taken_and_different_files() {
+        let config = config_init_test();
+        let id = uuid::Uuid::new_v4().to_string();
+        let path = format!("archive/{id}/game.tar.gz");
+        let mut taken = HashSet::new();
+        let first = bundle_target(&bundle_file(&path), DIR_ARCHIVE, &mut taken).unwrap();
+        assert_eq!(first.path, path);
+        assert!(!first.exists);
+        let second = bundle_target(&bundle_file(&path), DIR_ARCHIVE, &mut taken).unwrap();
+        assert_eq!(second.path, format!("archive/{id}/game (1).tar.gz"));
+
+        let dir = config.get_root().join("archive").join(&id);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("game.tar.gz"), "other").unwrap();
+        fs::write(dir.join("game (1).tar.gz"), "data").unwrap();
+        let reused = bundle_target(&bundle_file(&path), DIR_ARCHIVE, &mut HashSet::new()).unwrap();
+        assert_eq!(reused.path, format!("archive/{id}/game (1).tar.gz"));
+        assert!(reused.exists);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn file_path_is_under_the_archive_directory() {
+        let config = config_init_test();
+        let root = config.get_root();
+        let id = uuid::Uuid::new_v4().to_string();
+        let metadata = MetadataBuilder::default().title("Entry").build().unwrap();
+        let inside = root.join("archive").join(&id).join("game.7z");
+        let outside = root.join(&id).join("game.7z");
+        for source in [&inside, &outside] {
+            fs::create_dir_all(source.parent().unwrap()).unwrap();
+            fs::write(source, "data").unwrap();
+        }
+        let root = root.canonicalize().unwrap();
+
+        assert_eq!(
+            bundle_file_path(&root, DIR_ARCHIVE, &metadata, &inside),
+            format!("archive/{id}/game.7z")
+        );
+        assert_eq!(
+            bundle_file_path(&root, DIR_ARCHIVE, &metadata, &outside),
+            format!("archive/{BUNDLE_EXTERNAL_DIR}/{}/game.7z", metadata.id)
+        );
+        fs::remove_dir_all(inside.parent().unwrap()).unwrap();
+        fs::remove_dir_all(outside.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn covers_are_packed_under_the_cover_directory() {
+        let config = config_init_test();
+        let root = config.get_root();
+        let id = uuid::Uuid::new_v4().to_string();
+        let dir = root.join(&id);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("game.7z"), "data").unwrap();
+        fs::write(dir.join("front.png"), "image").unwrap();
+        let metadata = MetadataBuilder::default()
+            .title("Entry")
+            .archive_info(ArchiveInfo::ArchiveFile {
+                path: format!("{id}/game.7z"),
+                password: None,
+                format: Default::default(),
+            })
+            .cover(format!("{id}/front.png"))
+            .build()
+            .unwrap();
+        let bare = MetadataBuilder::default()
+            .title("Bare")
+            .cover(format!("{id}/missing.png"))
+            .build()
+            .unwrap();
+
+        let path = dir.join("bundle.tar");
+        let progress = ProgressHandle::silent();
+        let report = bundle_write(&path, vec![metadata.clone(), bare], &progress).unwrap();
+        assert_eq!((report.archives, report.covers), (1, 1));
+        assert_eq!(report.without_archive, ["Bare"]);
+
+        let manifest = bundle_manifest(&path).unwrap();
+        assert_eq!(manifest.version, BUNDLE_VERSION);
+        let cover = manifest.entries[0].cover.clone().unwrap();
+        assert_eq!(
+            cover.path,
+            format!("cover/{BUNDLE_EXTERNAL_DIR}/{}/front.png", metadata.id)
+        );
+        assert_eq!(cover.hash, blake3::hash(b"image").to_hex().to_string());
+        assert!(manifest.entries[1].cover.is_none());
+
+        let target = bundle_target(&cover, DIR_COVER, &mut HashSet::new()).unwrap();
+        assert_eq!(target.path, cover.path);
+        let target = bundle_target(&bundle_file("front.png"), DIR_COVER, &mut HashSet::new());
+        assert_eq!(
+            target.unwrap().path,
+            format!("cover/{BUNDLE_EXTERNAL_DIR}/front.png")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,642 @@
+use crate::DIR_STAGING;
+use crate::data::metadata::{ContentType, DistributionPlatform, Metadata};
+use crate::util::config::config_get;
+use crate::util::progress::ProgressHandle;
+use chrono::{DateTime, Local, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use thiserror::Error;
+use walkdir::WalkDir;
+
+const CATALOG_INDEX: &str = "index.html";
+
+/// Directory of the entry pages within a catalog
+const CATALOG_ENTRY_DIR: &str = "entries";
+
+/// Directory of the cover images within a catalog, copied from the data root
+const CATALOG_COVER_DIR: &str = "covers";
+const CATALOG_STYLE: &str = "style.css";
+const CATALOG_SCRIPT: &str = "search.js";
+
+/// Written into every catalog, only directories holding it are replaced by a new one
+const CATALOG_MARKER: &str = ".m-catalog";
+const CATALOG_PART_EXT: &str = "part";
+
+const STYLE: &str = include_str!("style.css");
+const SCRIPT: &str = include_str!("search.js");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogReport {
+    /// Directory of the catalog, open its `index.html` to view it
+    pub path: String,
+
+    /// Count of entries, each has a page of its own
+    pub entries: usize,
+}
+
+/// Text of the pages, given by the frontend in the language of the application
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CatalogLabels {
+    /// Language tag of the pages, e.g. `zh-CN`
+    pub lang: String,
+    pub title: String,
+
+    /// Line under the title of the index. `{count}` is replaced by the count of entries shown,
+    /// `{total}` by the count of all and `{time}` by the time the catalog was generated.
+    pub summary: String,
+
+    /// Placeholder of the search field
+    pub search: String,
+
+    /// Shown when the search matches no entry
+    pub empty: String,
+
+    /// Link from an entry page back to the index
+    pub back: String,
+    pub content_type: String,
+    pub platform: String,
+    pub developer: String,
+    pub publisher: String,
+    pub version: String,
+    pub size: String,
+    pub tags: String,
+    pub time_created: String,
+    pub time_updated: String,
+
+    /// Names of the content types, the name of the variant is shown for those missing
+    pub content_types: HashMap<ContentType, String>,
+}
+
+impl Default for CatalogLabels {
+    fn default() -> Self {
+        Self {
+            lang: String::from("en"),
+            title: String::from("Collection"),
+            summary: String::from("{count} / {total} entries, generated {time}"),
+            search: String::from("Search titles, aliases, tags, developers or platforms"),
+            empty: String::from("No entry matches"),
+            back: String::from("← Back to the collection"),
+            content_type: String::from("Type"),
+            platform: String::from("Platform"),
+            developer: String::from("Developer"),
+            publisher: String::from("Publisher"),
+            version: String::from("Version"),
+            size: String::from("Size"),
+            tags: String::from("Tags"),
+            time_created: String::from("Added"),
+            time_updated: String::from("Updated"),
+            content_types: HashMap::new(),
+        }
+    }
+}
+
+impl CatalogLabels {
+    fn content_type(&self, content_type: &ContentType) -> String {
+        match self.content_types.get(content_type) {
+            Some(label) => label.clone(),
+            None => format!("{content_type:?}"),
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Query component of a url, spaces and reserved characters percent-encoded
+fn encode_query(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            byte => {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
+    }
+    encoded
+}
+
+/// File stem of the page and cover of an entry, ids from imports are not guaranteed to be
+/// file names
+fn file_stem(id: &str) -> String {
+    let safe = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if safe {
+        id.to_string()
+    } else {
+        blake3::hash(id.as_bytes()).to_hex()[..16].to_string()
+    }
+}
+
+fn page_name(id: &str) -> String {
+    format!("{}.html", file_stem(id))
+}
+
+/// File name of the copy of the cover at `source`, keeping its extension if it has a plain one
+fn cover_name(id: &str, source: &Path) -> String {
+    let ext = source
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| !ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric()));
+    match ext {
+        Some(ext) => format!("{}.{}", file_stem(id), ext.to_lowercase()),
+        None => file_stem(id),
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes}B")
+    } else {
+        format!("{size:.2}{}", UNITS[unit])
+    }
+}
+
+fn format_time(time: &DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+fn platform_label(platform: &DistributionPlatform) -> Option<String> {
+    match platform {
+        DistributionPlatform::Unknown => None,
+        DistributionPlatform::Steam { id } => Some(format!("Steam {id}")),
+        DistributionPlatform::DLSite { id } => Some(format!("DLsite {id}")),
+        DistributionPlatform::Other { name, id } => {
+            Some(format!("{name} {}", id.as_deref().unwrap_or_default()))
+        }
+    }
+    .map(|label| label.trim().to_string())
+    .filter(|label| !label.is_empty())
+}
+
+/// Platform of an entry, linked to its store page if there is one
+fn platform_html(platform: &DistributionPlatform) -> Option<String> {
+    let label = escape(&platform_label(platform)?);
+    Some(match platform.store_url() {
+        Some(url) => format!(
+            r#"<a href="{}" rel="noreferrer" target="_blank">{label}</a>"#,
+            escape(&url)
+        ),
+        None => label,
+    })
+}
+
+fn page(labels: &CatalogLabels, title: &str, base: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="{lang}">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<link rel="stylesheet" href="{base}{CATALOG_STYLE}">
+</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        lang = escape(&labels.lang),
+        title = escape(title),
+    )
+}
+
+/// Lowercase text the search of the index matches against
+fn search_text(metadata: &Metadata, labels: &CatalogLabels) -> String {
+    std::iter::once(metadata.title.as_str())
+        .chain(metadata.alias.iter().map(String::as_str))
+        .chain(metadata.tags.iter().map(String::as_str))
+        .chain(metadata.developer.as_deref())
+        .chain(metadata.publisher.as_deref())
+        .chain(platform_label(&metadata.platform).as_deref())
+        .chain([labels.content_type(&metadata.content_type).as_str()])
+        .collect::<Vec<_>>()
+        .join("\n")
+        .to_lowercase()
+}
+
+fn index_item(metadata: &Metadata, cover: Option<&String>, labels: &CatalogLabels) -> String {
+    let page = format!("{CATALOG_ENTRY_DIR}/{}", page_name(&metadata.id));
+    let mut item = format!(
+        r#"<li data-search="{}">"#,
+        escape(&search_text(metadata, labels))
+    );
+    if let Some(cover) = cover {
+        let _ = write!(
+            item,
+            r#"<a href="{page}"><img class="thumb" src="{CATALOG_COVER_DIR}/{}" alt="" loading="lazy"></a>"#,
+            escape(cover)
+        );
+    }
+    let _ = write!(
+        item,
+        r#"<div><a class="title" href="{page}">{}</a>"#,
+        escape(&metadata.title)
+    );
+    if !metadata.alias.is_empty() {
+        let _ = write!(
+            item,
+            r#"<div class="muted">{}</div>"#,
+            escape(&metadata.alias.join(" / "))
+        );
+    }
+    let details = [
+        Some(escape(&labels.content_type(&metadata.content_type))),
+        metadata.developer.as_deref().map(escape),
+        platform_html(&metadata.platform),
+        (metadata.archive_size > 0).then(|| format_size(metadata.archive_size)),
+    ];
+    let details = details.into_iter().flatten().collect::<Vec<_>>();
+    let _ = write!(item, r#"<div class="muted">{}</div>"#, details.join(" · "));
+    if !metadata.tags.is_empty() {
+        item.push_str(r#"<div class="tags">"#);
+        for tag in &metadata.tags {
+            let _ = write!(
+                item,
+                r#"<button class="tag" type="button">{}</button>"#,
+                escape(tag)
+            );
+        }
+        item.push_str("</div>");
+    }
+    item.push_str("</div></li>\n");
+    item
+}
+
+/// The index of `entries`, `covers` holds the file names of the covers copied by their ids
+fn index_page(
+    entries: &[Metadata],
+    covers: &HashMap<String, String>,
+    labels: &CatalogLabels,
+    time: &DateTime<Utc>,
+) -> String {
+    let items = entries
+        .iter()
+        .map(|metadata| index_item(metadata, covers.get(&metadata.id), labels))
+        .collect::<String>();
+    let count = entries.len();
+    let summary = escape(&labels.summary)
+        .replace("{count}", &format!(r#"<span id="count">{count}</span>"#))
+        .replace("{total}", &count.to_string())
+        .replace("{time}", &format_time(time));
+    let body = format!(
+        r#"<header>
+<h1>{title}</h1>
+<div class="muted">{summary}</div>
+</header>
+<input id="search" type="search" placeholder="{search}" autofocus>
+<ul id="entries">
+{items}</ul>
+<p id="empty" class="muted" hidden>{empty}</p>
+<script src="{CATALOG_SCRIPT}"></script>"#,
+        title = escape(&labels.title),
+        search = escape(&labels.search),
+        empty = escape(&labels.empty),
+    );
+    page(labels, &labels.title, "", &body)
+}
+
+fn entry_page(metadata: &Metadata, cover: Option<&String>, labels: &CatalogLabels) -> String {
+    let mut fields = vec![(
+        &labels.content_type,
+        escape(&labels.content_type(&metadata.content_type)),
+    )];
+    if let Some(platform) = platform_html(&metadata.platform) {
+        fields.push((&labels.platform, platform));
+    }
+    let texts = [
+        (&labels.developer, metadata.developer.as_deref()),
+        (&labels.publisher, metadata.publisher.as_deref()),
+        (&labels.version, Some(metadata.version.as_str())),
+    ];
+    for (label, text) in texts {
+        if let Some(text) = text.filter(|text| !text.trim().is_empty()) {
+            fields.push((label, escape(text)));
+        }
+    }
+    if metadata.archive_size > 0 {
+        fields.push((&labels.size, format_size(metadata.archive_size)));
+    }
+    if !metadata.tags.is_empty() {
+        let tags = metadata
+            .tags
+            .iter()
+            .map(|tag| {
+                format!(
+                    r#"<a class="tag" href="../{CATALOG_INDEX}?q={}">{}</a>"#,
+                    encode_query(tag),
+                    escape(tag)
+                )
+            })
+            .collect::<String>();
+        fields.push((&labels.tags, format!(r#"<span class="tags">{tags}</span>"#)));
+    }
+    fields.push((&labels.time_created, format_time(&metadata.time_created)));
+    fields.push((&labels.time_updated, format_time(&metadata.time_updated)));
+
+    let mut body = format!(
+        r#"<a href="../{CATALOG_INDEX}">{}</a>
+<h1>{}</h1>
+"#,
+        escape(&labels.back),
+        escape(&metadata.title)
+    );
+    if !metadata.alias.is_empty() {
+        let _ = writeln!(
+            body,
+            r#"<div class="muted">{}</div>"#,
+            escape(&metadata.alias.join(" / "))
+        );
+    }
+    if let Some(cover) = cover {
+        let _ = writeln!(
+            body,
+            r#"<img class="cover" src="../{CATALOG_COVER_DIR}/{}" alt="">"#,
+            escape(cover)
+        );
+    }
+    body.push_str("<dl>\n");
+    for (label, value) in fields {
+        let _ = writeln!(body, "<dt>{}</dt><dd>{value}</dd>", escape(label));
+    }
+    body.push_str("</dl>");
+    if let Some(description) = metadata
+        .description
+        .as_deref()
+        .filter(|text| !text.trim().is_empty())
+    {
+        let _ = write!(
+            body,
+            "\n<div class=\"description\">{}</div>",
+            escape(description.trim())
+        );
+    }
+    page(labels, &metadata.title, "../", &body)
+}
+
+/// A directory may be replaced by a catalog if it is empty or holds an earlier one, known by
+/// its [`CATALOG_MARKER`]
+fn replaceable(path: &Path) -> CatalogResult<bool> {
+    if !path.exists() {
+        return Ok(true);
+    }
+    if !path.is_dir() {
+        return Ok(false);
+    }
+    if fs::read_dir(path)?.next().is_none() {
+        return Ok(true);
+    }
+    Ok(path.join(CATALOG_MARKER).is_file())
+}
+
+/// Copy the covers of `entries` found on disk into the catalog at `path`, returns the file
+/// names of the copies by the ids of their entries
+fn copy_covers(path: &Path, entries: &[Metadata]) -> CatalogResult<HashMap<String, String>> {
+    let cover_dir = path.join(CATALOG_COVER_DIR);
+    let mut covers = HashMap::new();
+    for metadata in entries {
+        let Some(cover) = &metadata.cover else {
+            continue;
+        };
+        let source = config_get().resolve_to_root(Path::new(cover));
+        if !source.is_file() {
+            warn!("Cover {} of {} not found", source.display(), metadata.id);
+            continue;
+        }
+        let name = cover_name(&metadata.id, &source);
+        fs::create_dir_all(&cover_dir)?;
+        fs::copy(&source, cover_dir.join(&name))?;
+        covers.insert(metadata.id.clone(), name);
+    }
+    Ok(covers)
+}
+
+fn write_catalog(
+    path: &Path,
+    entries: &[Metadata],
+    labels: &CatalogLabels,
+    progress: &ProgressHandle,
+) -> CatalogResult<()> {
+    let entry_dir = path.join(CATALOG_ENTRY_DIR);
+    fs::create_dir_all(&entry_dir)?;
+    fs::write(path.join(CATALOG_MARKER), Utc::now().to_rfc3339())?;
+    fs::write(path.join(CATALOG_STYLE), STYLE)?;
+    fs::write(path.join(CATALOG_SCRIPT), SCRIPT)?;
+    let covers = copy_covers(path, entries)?;
+    for metadata in entries {
+        progress.check()?;
+        progress.start_file(&metadata.title);
+        fs::write(
+            entry_dir.join(page_name(&metadata.id)),
+            entry_page(metadata, covers.get(&metadata.id), labels),
+        )?;
+        progress.finish_file();
+    }
+    fs::write(
+        path.join(CATALOG_INDEX),
+        index_page(entries, &covers, labels, &Utc::now()),
+    )?;
+    Ok(())
+}
+
+/// Move the directory `from` to `to`, copying it over if they are on different devices
+fn move_dir(from: &Path, to: &Path) -> CatalogResult<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {}
+        result => return Ok(result?),
+    }
+    for entry in WalkDir::new(from) {
+        let entry = entry.map_err(std::io::Error::from)?;
+        let target = to.join(entry.path().strip_prefix(from).unwrap_or(entry.path()));
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    fs::remove_dir_all(from)?;
+    Ok(())
+}
+
+/// Render `entries` into a static site at the directory `path`, an index with a search over
+/// them and a page for each. The site needs nothing but a browser to view, covers are copied
+/// into it and it refers to no archive or other file of the data root.
+pub(crate) fn catalog_write(
+    path: &Path,
+    mut entries: Vec<Metadata>,
+    labels: &CatalogLabels,
+    progress: &ProgressHandle,
+) -> CatalogResult<CatalogReport> {
+    if !replaceable(path)? {
+        return Err(CatalogError::NotEmpty(path.display().to_string()));
+    }
+    entries.sort_by_cached_key(|metadata| metadata.title.to_lowercase());
+    progress.set_total(0, entries.len() as u64);
+
+    // Written aside first, a cancelled export leaves an earlier catalog as it was
+    let staging = config_get().get_root().join(DIR_STAGING);
+    fs::create_dir_all(&staging)?;
+    let part = staging.join(format!("{}.{CATALOG_PART_EXT}", uuid::Uuid::new_v4()));
+    if let Err(e) = write_catalog(&part, &entries, labels, progress) {
+        let _ = fs::remove_dir_all(&part);
+        return Err(e);
+    }
+    if path.exists() {
+        fs::remove_dir_all(path)?;
+    }
+    if let Err(e) = move_dir(&part, path) {
+        let _ = fs::remove_dir_all(&part);
+        return Err(e);
+    }
+    progress.flush();
+
+    info!(
+        "Catalog of {} entries written to {}",
+        entries.len(),
+        path.display()
+    );
+    Ok(CatalogReport {
+        path: path.to_string_lossy().to_string(),
+        entries: entries.len(),
+    })
+}
+
+pub(crate) type CatalogResult<T> = Result<T, CatalogError>;
+
+#[derive(Debug, Error)]
+pub enum CatalogError {
+    #[error("{0} is neither empty nor an earlier catalog")]
+    NotEmpty(String),
+
+    #[error("File system error: {0}")]
+    FileSystemError(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::metadata::MetadataBuilder;
+    use crate::util::config::config_init_test;
+
+    #[test]
+    fn pages_use_the_labels_given() {
+        let metadata = MetadataBuilder::default()
+            .title("<Entry>")
+            .content_type(ContentType::Game)
+            .build()
+            .unwrap();
+        let labels = CatalogLabels {
+            lang: String::from("zh-CN"),
+            title: String::from("收藏目录"),
+            summary: String::from("共 {count} / {total} 个条目 <{time}>"),
+            content_types: HashMap::from([(ContentType::Game, String::from("游戏"))]),
+            ..Default::default()
+        };
+
+        let index = index_page(
+            std::slice::from_ref(&metadata),
+            &HashMap::new(),
+            &labels,
+            &Utc::now(),
+        );
+        assert!(index.contains(r#"<html lang="zh-CN">"#));
+        assert!(index.contains("<title>收藏目录</title>"));
+        assert!(index.contains(r#"共 <span id="count">1</span> / 1 个条目 &lt;"#));
+        assert!(index.contains("&lt;Entry&gt;"));
+        assert!(index.contains("游戏"));
+
+        let entry = entry_page(&metadata, None, &CatalogLabels::default());
+        assert!(entry.contains(r#"<html lang="en">"#));
+        assert!(entry.contains("<dt>Type</dt><dd>Game</dd>"));
+    }
+
+    #[test]
+    fn catalogs_are_marked_and_show_covers() {
+        let root = config_init_test().get_root();
+        let name = uuid::Uuid::new_v4().to_string();
+        let cover = root.join(format!("{name}.PNG"));
+        fs::write(&cover, b"png").unwrap();
+        let entries = vec![
+            MetadataBuilder::default()
+                .id("with-cover")
+                .title("With Cover")
+                .cover(Some(cover.to_string_lossy().to_string()))
+                .build()
+                .unwrap(),
+            MetadataBuilder::default()
+                .id("missing-cover")
+                .title("Missing Cover")
+                .cover(Some(format!("{name}-missing.png")))
+                .build()
+                .unwrap(),
+        ];
+
+        // A directory with files but no marker is not an earlier catalog
+        let path = root.join(format!("{name}-catalog"));
+        fs::create_dir_all(path.join(CATALOG_ENTRY_DIR)).unwrap();
+        fs::write(path.join(CATALOG_INDEX), "").unwrap();
+        let labels = CatalogLabels::default();
+        let progress = ProgressHandle::silent();
+        assert!(matches!(
+            catalog_write(&path, entries.clone(), &labels, &progress),
+            Err(CatalogError::NotEmpty(_))
+        ));
+
+        fs::remove_dir_all(&path).unwrap();
+        catalog_write(&path, entries.clone(), &labels, &progress).unwrap();
+        assert!(path.join(CATALOG_MARKER).is_file());
+        assert_eq!(
+            fs::read(path.join(CATALOG_COVER_DIR).join("with-cover.png")).unwrap(),
+            b"png"
+        );
+        let index = fs::read_to_string(path.join(CATALOG_INDEX)).unwrap();
+        assert!(index.contains(r#"src="covers/with-cover.png""#));
+        assert_eq!(index.matches(r#"class="thumb""#).count(), 1);
+        let page = path.join(CATALOG_ENTRY_DIR).join(page_name("with-cover"));
+        let page = fs::read_to_string(page).unwrap();
+        assert!(page.contains(r#"src="../covers/with-cover.png""#));
+
+        // The marker lets a later export replace it, nothing is left behind in staging
+        catalog_write(&path, entries, &labels, &progress).unwrap();
+        let staging = root.join(DIR_STAGING);
+        let leftover = fs::read_dir(&staging).unwrap().any(|entry| {
+            let entry = entry.unwrap().file_name();
+            entry.to_string_lossy().ends_with(CATALOG_PART_EXT)
+        });
+        assert!(!leftover);
+
+        fs::remove_dir_all(&path).unwrap();
+        fs::remove_file(&cover).unwrap();
+    }
+}
@@ -0,0 +1,30 @@
+// Filters the entries of the index by the words typed, all of which have to match
+(function () {
+  const input = document.getElementById('search');
+  const count = document.getElementById('count');
+  const empty = document.getElementById('empty');
+  const items = Array.from(document.querySelectorAll('#entries > li'));
+
+  const apply = () => {
+    const terms = input.value.toLowerCase().split(/\s+/).filter(Boolean);
+    let shown = 0;
+    for (const item of items) {
+      const match = terms.every((term) => item.dataset.search.includes(term));
+      item.hidden = !match;
+      if (match) shown++;
+    }
+    count.textContent = String(shown);
+    empty.hidden = shown > 0;
+  };
+
+  // Tags of entry pages link back here with the tag as query
+  input.value = new URLSearchParams(location.search).get('q') ?? '';
+  input.addEventListener('input', apply);
+  for (const tag of document.querySelectorAll('#entries .tag')) {
+    tag.addEventListener('click', () => {
+      input.value = tag.textContent;
+      apply();
+    });
+  }
+  apply();
+})();
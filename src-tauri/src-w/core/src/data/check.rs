@@ -0,0 +1,138 @@
+use crate::Whether::{That, This};
+use crate::data::metadata::{ArchiveInfo, DeployInfo, Metadata};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A problem with a stored entry found by a library check
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub enum CheckIssue {
+    /// The record could not be decoded, repairing moves it into the quarantine
+    Undecodable { error: String },
+
+    /// The archive, common file or directory of the entry no longer exists
+    ArchiveMissing,
+
+    /// The target of a deployment no longer exists, repairing drops the deployment
+    DeploymentStale { deployment: String },
+
+    /// The single deployment of older versions is still stored, repairing upgrades it
+    LegacyDeploy,
+
+    /// The stored size differs from the source, repairing records the actual size
+    SizeMismatch { stored: u64, actual: u64 },
+}
+
+impl CheckIssue {
+    /// Whether a repair pass fixes the issue, the others are exported for a manual look
+    pub fn repairable(&self) -> bool {
+        !matches!(
+            self,
+            CheckIssue::Undecodable { .. } | CheckIssue::ArchiveMissing
+        )
+    }
+}
+
+/// Issues of a single record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckFinding {
+    pub id: String,
+
+    /// Unset if the record could not be decoded
+    pub title: Option<String>,
+    pub issues: Vec<CheckIssue>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckReport {
+    /// Count of records checked
+    pub checked: usize,
+    pub findings: Vec<CheckFinding>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepairReport {
+    /// Count of issues fixed
+    pub repaired: usize,
+
+    /// Count of undecodable records moved into the quarantine
+    pub quarantined: usize,
+
+    /// Issues left for a manual look, exported along with their records
+    pub unresolved: Vec<CheckFinding>,
+
+    /// File the unresolved issues were exported to, unset if there were none
+    pub export: Option<String>,
+}
+
+/// An unresolved finding as written to the export, with the record as relaxed extended JSON
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct CheckExport {
+    #[serde(flatten)]
+    pub finding: CheckFinding,
+    pub document: Option<Value>,
+}
+
+/// Decode a stored record and check it, `None` if it could not be decoded
+pub(crate) fn check_record(raw: &[u8]) -> (Option<Metadata>, Vec<CheckIssue>) {
+    match bson::from_slice::<Metadata>(raw) {
+        Ok(metadata) => {
+            let issues = check_entry(&metadata);
+            (Some(metadata), issues)
+        }
+        Err(e) => (
+            None,
+            vec![CheckIssue::Undecodable {
+                error: e.to_string(),
+            }],
+        ),
+    }
+}
+
+fn check_entry(metadata: &Metadata) -> Vec<CheckIssue> {
+    let mut issues = Vec::new();
+    if metadata.archive_info != ArchiveInfo::Unset {
+        match metadata.archive_info.try_resolve() {
+            This(_) => {
+                let actual = metadata.archive_info.calculate_size();
+                if actual != metadata.archive_size {
+                    issues.push(CheckIssue::SizeMismatch {
+                        stored: metadata.archive_size,
+                        actual,
+                    });
+                }
+            }
+            That(_) => issues.push(CheckIssue::ArchiveMissing),
+        }
+    }
+    if metadata.deploy_info != DeployInfo::Unset || metadata.deploy_manifest.is_some() {
+        issues.push(CheckIssue::LegacyDeploy);
+    }
+    for deployment in &metadata.deployments {
+        if let That(_) = deployment.info.try_resolve() {
+            issues.push(CheckIssue::DeploymentStale {
+                deployment: deployment.id.clone(),
+            });
+        }
+    }
+    issues
+}
+
+/// Fix the repairable issues of an entry, returns those left
+pub(crate) fn repair_entry(metadata: &mut Metadata, issues: Vec<CheckIssue>) -> Vec<CheckIssue> {
+    let mut left = Vec::new();
+    for issue in issues {
+        match &issue {
+            CheckIssue::LegacyDeploy => {
+                metadata.upgrade_legacy_deploy();
+            }
+            CheckIssue::DeploymentStale { deployment } => {
+                metadata.deployments.retain(|d| d.id != *deployment);
+            }
+            CheckIssue::SizeMismatch { actual, .. } => {
+                metadata.archive_size = *actual;
+            }
+            CheckIssue::Undecodable { .. } | CheckIssue::ArchiveMissing => left.push(issue),
+        }
+    }
+    left
+}
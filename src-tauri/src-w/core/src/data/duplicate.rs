@@ -0,0 +1,290 @@
+use crate::data::metadata::{DistributionPlatform, Metadata, MetadataError, MetadataOptional};
+use crate::util::progress::ProgressHandle;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use strsim::normalized_levenshtein;
+
+/// Names at least this similar are considered the same work
+const TITLE_SIMILARITY: f64 = 0.9;
+
+/// Names shorter than this only match exactly, short titles easily look alike
+const TITLE_MIN_FUZZY_LEN: usize = 6;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub enum DuplicateReason {
+    /// Same id on the same distribution platform
+    Platform { platform: DistributionPlatform },
+
+    /// Same content hash of the archive or source
+    Hash { hash: String },
+
+    /// Titles or aliases close to each other
+    Title,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    pub reason: DuplicateReason,
+
+    /// Ids of the entries, the oldest first
+    pub ids: Vec<String>,
+}
+
+/// An entry likely to be the same work as one about to be created
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateMatch {
+    /// Id of the library entry, unset for a record created along which is not stored yet
+    pub id: Option<String>,
+    pub title: String,
+    pub reason: DuplicateReason,
+}
+
+/// Lowercase letters and digits only, so spacing and punctuation do not matter
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn names_match(a: &str, b: &str) -> bool {
+    if a == b {
+        return true;
+    }
+    let short = a.chars().count().min(b.chars().count());
+    short >= TITLE_MIN_FUZZY_LEN && normalized_levenshtein(a, b) >= TITLE_SIMILARITY
+}
+
+/// Why `candidate` is likely the same work as `entry` by the rules of [`find_duplicates`],
+/// `None` if it is not
+pub(crate) fn duplicate_reason(candidate: &Metadata, entry: &Metadata) -> Option<DuplicateReason> {
+    let keys = (candidate.platform.index_key(), entry.platform.index_key());
+    if let (Some(a), Some(b)) = &keys
+        && a == b
+    {
+        let platform = entry.platform.clone();
+        return Some(DuplicateReason::Platform { platform });
+    }
+    if let (Some(a), Some(b)) = (&candidate.archive_hash, &entry.archive_hash)
+        && a == b
+    {
+        return Some(DuplicateReason::Hash { hash: a.clone() });
+    }
+    // Entries with different platform ids are never the same work
+    if let (Some(_), Some(_)) = &keys {
+        return None;
+    }
+    let names = |metadata: &Metadata| {
+        std::iter::once(&metadata.title)
+            .chain(&metadata.alias)
+            .map(|name| normalize(name))
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (names(candidate), names(entry));
+    a.iter()
+        .any(|a| b.iter().any(|b| names_match(a, b)))
+        .then_some(DuplicateReason::Title)
+}
+
+/// The entry `opt` would create as far as [`check_duplicates`] compares it. Its archive is
+/// left alone, creating or hashing it would take far longer than the check.
+pub(crate) fn duplicate_candidate(opt: &MetadataOptional) -> Result<Metadata, MetadataError> {
+    let opt = MetadataOptional {
+        title: opt.title.clone(),
+        alias: opt.alias.clone(),
+        platform: opt.platform.clone(),
+        ..Default::default()
+    };
+    Metadata::init(opt, &ProgressHandle::silent())
+}
+
+/// Entries of the `library` and records of `pending` likely to be the same work as `candidate`.
+///
+/// This is the check run before entries are created, whether by hand or imported from another
+/// tool. `pending` holds the records created along with `candidate`, those are not stored yet.
+pub(crate) fn check_duplicates(
+    candidate: &Metadata,
+    library: &[Metadata],
+    pending: &[Metadata],
+) -> Vec<DuplicateMatch> {
+    let library = library.iter().filter_map(|entry| {
+        duplicate_reason(candidate, entry).map(|reason| DuplicateMatch {
+            id: Some(entry.id.clone()),
+            title: entry.title.clone(),
+            reason,
+        })
+    });
+    let pending = pending.iter().filter_map(|record| {
+        duplicate_reason(candidate, record).map(|reason| DuplicateMatch {
+            id: None,
+            title: record.title.clone(),
+            reason,
+        })
+    });
+    library.chain(pending).collect()
+}
+
+fn find_root(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
+
+/// Group entries which are likely the same work.
+///
+/// An entry could appear in several groups with different reasons. Title groups are only
+/// reported if not already covered by a platform or hash group, and entries with different
+/// platform ids are never grouped by title.
+pub fn find_duplicates(entries: &[Metadata]) -> Vec<DuplicateGroup> {
+    let mut groups = Vec::new();
+    let make_group = |reason: DuplicateReason, mut indices: Vec<usize>| {
+        indices.sort_by_key(|&index| entries[index].time_created);
+        DuplicateGroup {
+            reason,
+            ids: indices
+                .into_iter()
+                .map(|index| entries[index].id.clone())
+                .collect(),
+        }
+    };
+
+    let mut by_platform = BTreeMap::<String, Vec<usize>>::new();
+    let mut by_hash = BTreeMap::<&str, Vec<usize>>::new();
+    for (index, entry) in entries.iter().enumerate() {
+        if let Some(key) = entry.platform.index_key() {
+            by_platform.entry(key).or_default().push(index);
+        }
+        if let Some(hash) = &entry.archive_hash {
+            by_hash.entry(hash.as_str()).or_default().push(index);
+        }
+    }
+    for indices in by_platform.into_values().filter(|v| v.len() > 1) {
+        let platform = entries[indices[0]].platform.clone();
+        groups.push(make_group(DuplicateReason::Platform { platform }, indices));
+    }
+    for (hash, indices) in by_hash.into_iter().filter(|(_, v)| v.len() > 1) {
+        let hash = hash.to_string();
+        groups.push(make_group(DuplicateReason::Hash { hash }, indices));
+    }
+
+    let names = entries
+        .iter()
+        .map(|entry| {
+            std::iter::once(&entry.title)
+                .chain(&entry.alias)
+                .map(|name| normalize(name))
+                .filter(|name| !name.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut parents = (0..entries.len()).collect::<Vec<_>>();
+    for i in 0..entries.len() {
+        for j in i + 1..entries.len() {
+            let (a, b) = (&entries[i], &entries[j]);
+            if let (Some(a), Some(b)) = (a.platform.index_key(), b.platform.index_key())
+                && a != b
+            {
+                continue;
+            }
+            let matched = names[i]
+                .iter()
+                .any(|a| names[j].iter().any(|b| names_match(a, b)));
+            if matched {
+                let (root_i, root_j) = (find_root(&mut parents, i), find_root(&mut parents, j));
+                parents[root_j] = root_i;
+            }
+        }
+    }
+
+    let covered = groups
+        .iter()
+        .map(|group| group.ids.iter().collect::<BTreeSet<_>>())
+        .collect::<Vec<_>>();
+    let mut by_title = BTreeMap::<usize, Vec<usize>>::new();
+    for index in 0..entries.len() {
+        let root = find_root(&mut parents, index);
+        by_title.entry(root).or_default().push(index);
+    }
+    let mut title_groups = Vec::new();
+    for indices in by_title.into_values().filter(|v| v.len() > 1) {
+        let group = make_group(DuplicateReason::Title, indices);
+        let ids = group.ids.iter().collect::<BTreeSet<_>>();
+        if !covered.iter().any(|covered| ids.is_subset(covered)) {
+            title_groups.push(group);
+        }
+    }
+    groups.extend(title_groups);
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::metadata::MetadataBuilder;
+    use chrono::{TimeDelta, Utc};
+
+    fn entry(id: &str, title: &str, platform: DistributionPlatform, age: i64) -> Metadata {
+        MetadataBuilder::default()
+            .id(id)
+            .title(title)
+            .platform(platform)
+            .time_created(Utc::now() - TimeDelta::days(age))
+            .build()
+            .unwrap()
+    }
+
+    fn steam(id: &str) -> DistributionPlatform {
+        DistributionPlatform::Steam { id: id.to_string() }
+    }
+
+    #[test]
+    fn different_platform_ids_never_match_by_title() {
+        let entries = [
+            entry("a", "Summer Pockets", steam("100"), 2),
+            entry("b", "Summer Pockets", steam("200"), 1),
+        ];
+        assert!(find_duplicates(&entries).is_empty());
+        assert_eq!(duplicate_reason(&entries[0], &entries[1]), None);
+    }
+
+    #[test]
+    fn entries_without_platform_id_match_by_title() {
+        let entries = [
+            entry("new", "Summer Pockets", steam("100"), 1),
+            entry("old", "Summer Pockets REFLECTION", steam(" 100 "), 3),
+            entry("bare", "summer pockets", DistributionPlatform::Unknown, 2),
+        ];
+        let groups = find_duplicates(&entries);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(
+            groups[0].reason,
+            DuplicateReason::Platform {
+                platform: steam("100")
+            }
+        );
+        assert_eq!(groups[0].ids, ["old", "new"]);
+        // The entry without a platform id still matches the title of either
+        assert_eq!(groups[1].reason, DuplicateReason::Title);
+        assert_eq!(groups[1].ids, ["bare", "new"]);
+        assert_eq!(
+            duplicate_reason(&entries[2], &entries[0]),
+            Some(DuplicateReason::Title)
+        );
+    }
+
+    #[test]
+    fn short_titles_only_match_exactly() {
+        let entries = [
+            entry("a", "Ever17", DistributionPlatform::Unknown, 2),
+            entry("b", "Ever18", DistributionPlatform::Unknown, 1),
+            entry("c", "Little Busters!", DistributionPlatform::Unknown, 1),
+            entry("d", "Little Buster", DistributionPlatform::Unknown, 0),
+        ];
+        let groups = find_duplicates(&entries);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].ids, ["c", "d"]);
+    }
+}
@@ -0,0 +1,82 @@
+use super::{
+    CsvMapping, DEFAULT_SEPARATOR, ExternalError, ExternalResult, external_platform, split_list,
+};
+use crate::data::metadata::{ContentType, MetadataOptional};
+use crate::data::transfer::csv_records;
+
+fn content_type(name: &str) -> ContentType {
+    match name.trim().to_lowercase().as_str() {
+        "game" => ContentType::Game,
+        "novel" => ContentType::Novel,
+        "comic" => ContentType::Comic,
+        "anime" => ContentType::Anime,
+        "music" => ContentType::Music,
+        "movie" => ContentType::Movie,
+        "software" => ContentType::Software,
+        _ => ContentType::Other,
+    }
+}
+
+pub(super) fn columns(data: &str) -> ExternalResult<Vec<String>> {
+    Ok(csv_records(data)?.into_iter().next().unwrap_or_default())
+}
+
+pub(super) fn read(data: &str, mapping: &CsvMapping) -> ExternalResult<Vec<MetadataOptional>> {
+    let mut records = csv_records(data)?.into_iter();
+    let Some(header) = records.next() else {
+        return Ok(Vec::new());
+    };
+    // Index of each mapped column, unmapped fields stay unset
+    let position = |column: Option<&String>| -> ExternalResult<Option<usize>> {
+        let Some(column) = column.filter(|column| !column.is_empty()) else {
+            return Ok(None);
+        };
+        header
+            .iter()
+            .position(|name| name.trim() == column.trim())
+            .map(Some)
+            .ok_or_else(|| ExternalError::MissingColumn(column.clone()))
+    };
+    let title = position(Some(&mapping.title))?
+        .ok_or_else(|| ExternalError::MissingColumn("title".to_string()))?;
+    let alias = position(mapping.alias.as_ref())?;
+    let tags = position(mapping.tags.as_ref())?;
+    let kind = position(mapping.content_type.as_ref())?;
+    let platform = position(mapping.platform.as_ref())?;
+    let platform_id = position(mapping.platform_id.as_ref())?;
+    let description = position(mapping.description.as_ref())?;
+    let developer = position(mapping.developer.as_ref())?;
+    let publisher = position(mapping.publisher.as_ref())?;
+    let version = position(mapping.version.as_ref())?;
+    let separator = match mapping.separator.as_str() {
+        "" => DEFAULT_SEPARATOR,
+        separator => separator,
+    };
+
+    Ok(records
+        .map(|record| {
+            let cell = |index: Option<usize>| {
+                index
+                    .and_then(|index| record.get(index))
+                    .map(|cell| cell.trim().to_string())
+                    .filter(|cell| !cell.is_empty())
+            };
+            let list = |index: Option<usize>| cell(index).map(|cell| split_list(&cell, separator));
+            MetadataOptional {
+                title: cell(Some(title)),
+                alias: list(alias),
+                tags: list(tags),
+                content_type: cell(kind).map(|name| content_type(&name)),
+                platform: Some(external_platform(
+                    cell(platform).as_deref(),
+                    cell(platform_id).as_deref(),
+                )),
+                description: cell(description),
+                developer: cell(developer),
+                publisher: cell(publisher),
+                version: cell(version),
+                ..Default::default()
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,94 @@
+use super::{ExternalError, ExternalResult, external_platform, parse_time, split_list};
+use crate::data::metadata::{ContentType, MetadataOptional};
+use roxmltree::{Document, Node};
+use std::collections::HashMap;
+
+/// Prefixes of Steam launch urls, followed by the app id
+const STEAM_URL_PREFIXES: [&str; 2] = ["steam://rungameid/", "steam://run/"];
+
+/// Text of the child element `name`, `None` if missing or blank
+fn child_text(node: Node, name: &str) -> Option<String> {
+    node.children()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+/// App id of a game launched through a Steam url
+fn steam_id(application_path: &str) -> Option<String> {
+    let rest = STEAM_URL_PREFIXES
+        .iter()
+        .find_map(|prefix| application_path.strip_prefix(prefix))?;
+    let id = rest
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect::<String>();
+    Some(id).filter(|id| !id.is_empty())
+}
+
+fn record(game: Node, alternate_names: &HashMap<String, Vec<String>>) -> MetadataOptional {
+    let title = child_text(game, "Title");
+    let mut alias = child_text(game, "SortTitle")
+        .filter(|name| Some(name) != title.as_ref())
+        .into_iter()
+        .collect::<Vec<_>>();
+    if let Some(names) = child_text(game, "ID").and_then(|id| alternate_names.get(&id)) {
+        alias.extend(names.iter().cloned());
+    }
+    let mut tags = child_text(game, "Genre")
+        .map(|genre| split_list(&genre, ";"))
+        .unwrap_or_default();
+    tags.extend(child_text(game, "Series"));
+
+    let source = child_text(game, "Source");
+    let id = child_text(game, "ApplicationPath").and_then(|path| steam_id(&path));
+    // Games launched through Steam are often added without a source
+    let source = source.or_else(|| id.as_ref().map(|_| "Steam".to_string()));
+    MetadataOptional {
+        title,
+        alias: Some(alias),
+        tags: Some(tags),
+        content_type: Some(ContentType::Game),
+        platform: Some(external_platform(source.as_deref(), id.as_deref())),
+        description: child_text(game, "Notes"),
+        developer: child_text(game, "Developer"),
+        publisher: child_text(game, "Publisher"),
+        version: child_text(game, "Version"),
+        time_created: child_text(game, "DateAdded")
+            .as_deref()
+            .and_then(parse_time),
+        ..Default::default()
+    }
+}
+
+/// `Game` elements of a LaunchBox XML, with their `AlternateName` elements as aliases
+pub(super) fn read(data: &str) -> ExternalResult<Vec<MetadataOptional>> {
+    let document = Document::parse(data)?;
+    let root = document.root_element();
+    if !root.has_tag_name("LaunchBox") {
+        return Err(ExternalError::FormatError(
+            "LaunchBox",
+            format!("unexpected root element {}", root.tag_name().name()),
+        ));
+    }
+
+    let mut alternate_names: HashMap<String, Vec<String>> = HashMap::new();
+    for alternate in root
+        .children()
+        .filter(|node| node.has_tag_name("AlternateName"))
+    {
+        if let (Some(id), Some(name)) = (
+            child_text(alternate, "GameId"),
+            child_text(alternate, "Name"),
+        ) {
+            alternate_names.entry(id).or_default().push(name);
+        }
+    }
+    Ok(root
+        .children()
+        .filter(|node| node.has_tag_name("Game"))
+        .map(|game| record(game, &alternate_names))
+        .collect())
+}
@@ -0,0 +1,384 @@
+mod csv;
+mod launchbox;
+mod playnite;
+
+use crate::data::duplicate::DuplicateMatch;
+use crate::data::metadata::{DistributionPlatform, MetadataOptional};
+use crate::data::transfer::TransferError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+
+/// Separator of list columns if none is given
+const DEFAULT_SEPARATOR: &str = ";";
+
+/// Columns of a CSV file holding the fields of an entry, only the title is required
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct CsvMapping {
+    pub title: String,
+    pub alias: Option<String>,
+    pub tags: Option<String>,
+
+    /// Names as in [`ContentType`](crate::data::metadata::ContentType), case-insensitive
+    pub content_type: Option<String>,
+
+    /// Name of the platform, e.g. `Steam` or `DLSite`
+    pub platform: Option<String>,
+
+    /// Id of the work on the platform
+    pub platform_id: Option<String>,
+    pub description: Option<String>,
+    pub developer: Option<String>,
+    pub publisher: Option<String>,
+    pub version: Option<String>,
+
+    /// Characters separating the items of the alias and tags columns, `;` if empty
+    pub separator: String,
+}
+
+/// Tool an external library was exported from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ExternalSource {
+    /// JSON array of games, as written by the library export extensions of Playnite
+    Playnite,
+
+    /// Games XML of LaunchBox, as found in `Data/Platforms`
+    LaunchBox,
+
+    /// Any CSV file with a header row
+    Csv { mapping: Box<CsvMapping> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalCandidate {
+    /// Position among the records of the file, selects the record for the import
+    pub index: usize,
+    pub opt: MetadataOptional,
+
+    /// Library entries and earlier records likely to be the same work, see
+    /// [`check_duplicates`](crate::data::duplicate::check_duplicates)
+    pub duplicates: Vec<DuplicateMatch>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalPreview {
+    pub candidates: Vec<ExternalCandidate>,
+
+    /// Count of records left out as they have no title
+    pub skipped: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExternalImportReport {
+    /// Ids of the entries created
+    pub created: Vec<String>,
+
+    /// Selected records left out as they look like duplicates now, e.g. of entries added since
+    /// the preview, and were not confirmed
+    pub duplicates: Vec<ExternalCandidate>,
+}
+
+/// Platform of a work from the name of the store it came from and its id there
+fn external_platform(name: Option<&str>, id: Option<&str>) -> DistributionPlatform {
+    let name = name.map(str::trim).filter(|name| !name.is_empty());
+    let id = id.map(str::trim).filter(|id| !id.is_empty());
+    let Some(name) = name else {
+        return DistributionPlatform::Unknown;
+    };
+    match (name.to_lowercase().as_str(), id) {
+        ("steam", Some(id)) => DistributionPlatform::Steam { id: id.to_string() },
+        ("dlsite", Some(id)) => DistributionPlatform::DLSite {
+            id: id.to_uppercase(),
+        },
+        _ => DistributionPlatform::Other {
+            name: name.to_string(),
+            id: id.map(str::to_string),
+        },
+    }
+}
+
+/// Non-empty items of a list value separated by any of `separators`
+fn split_list(value: &str, separators: &str) -> Vec<String> {
+    value
+        .split(|c| separators.contains(c))
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Read the external library at `path` mapped to entry fields. Records without a title are
+/// left out, their count is returned along.
+pub(crate) fn external_read(
+    path: &Path,
+    source: &ExternalSource,
+) -> ExternalResult<(Vec<MetadataOptional>, usize)> {
+    let data = fs::read_to_string(path)?;
+    let data = data.trim_start_matches('\u{feff}');
+    let records = match source {
+        ExternalSource::Playnite => playnite::read(data)?,
+        ExternalSource::LaunchBox => launchbox::read(data)?,
+        ExternalSource::Csv { mapping } => csv::read(data, mapping)?,
+    };
+    let total = records.len();
+    let records = records
+        .into_iter()
+        .filter(|opt| {
+            opt.title
+                .as_ref()
+                .is_some_and(|title| !title.trim().is_empty())
+        })
+        .collect::<Vec<_>>();
+    let skipped = total - records.len();
+    Ok((records, skipped))
+}
+
+/// Header row of the CSV file at `path`, to pick a [`CsvMapping`] from
+pub(crate) fn external_csv_columns(path: &Path) -> ExternalResult<Vec<String>> {
+    let data = fs::read_to_string(path)?;
+    csv::columns(data.trim_start_matches('\u{feff}'))
+}
+
+pub(crate) type ExternalResult<T> = Result<T, ExternalError>;
+
+#[derive(Debug, Error)]
+pub enum ExternalError {
+    #[error("Not a {0} export: {1}")]
+    FormatError(&'static str, String),
+
+    #[error("No column {0} in the CSV file")]
+    MissingColumn(String),
+
+    #[error("XML error: {0}")]
+    XmlError(#[from] roxmltree::Error),
+
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("CSV error: {0}")]
+    CsvError(#[from] TransferError),
+
+    #[error("File system error: {0}")]
+    FileSystemError(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::metadata::ContentType;
+    use tempfile::tempdir;
+
+    const PLAYNITE: &str = r#"{"Games": [
+        {"Name": "Hollow Knight", "Source": {"Name": "Steam"}, "GameId": "367520",
+         "Genres": [{"Name": "Metroidvania"}], "Tags": ["Indie", "Metroidvania"],
+         "Developers": [{"Name": "Team Cherry"}], "Added": "2021-03-01T10:00:00Z"},
+        {"Name": "Celeste ", "SortingName": "Celeste!", "Categories": ["Platformer"],
+         "Publishers": ["Matt Makes Games", "Other"]},
+        {"Name": "", "Source": "Steam"},
+        {"Name": "Some Work", "Source": "DLsite", "GameId": "rj123456", "Version": 1.2}
+    ]}"#;
+
+    const LAUNCHBOX: &str = r#"<?xml version="1.0" standalone="yes"?>
+<LaunchBox>
+  <Game>
+    <ID>g1</ID>
+    <Title>Hades</Title>
+    <SortTitle>Hades</SortTitle>
+    <Genre>Action; Roguelike</Genre>
+    <Series>Supergiant</Series>
+    <ApplicationPath>steam://rungameid/1145360</ApplicationPath>
+    <DateAdded>2022-01-02T03:04:05.1234567+08:00</DateAdded>
+    <Developer>Supergiant Games</Developer>
+  </Game>
+  <Game>
+    <ID>g2</ID>
+    <Title>Hollow Knight</Title>
+    <Source>GOG</Source>
+  </Game>
+  <AlternateName>
+    <GameId>g1</GameId>
+    <Name>Hades I</Name>
+  </AlternateName>
+</LaunchBox>"#;
+
+    const CSV: &str = "\u{feff}Name,Aliases,Kind,Store,Store Id,Maker\r\n\
+        Hades,Hadēs|H,novel,steam,1145360,SG\r\n\
+        \"Title, with comma\",,Comic,,,\r\n\
+        ,x,,,,\r\n";
+
+    fn read(data: &str, source: &ExternalSource) -> ExternalResult<(Vec<MetadataOptional>, usize)> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("library");
+        fs::write(&path, data).unwrap();
+        external_read(&path, source)
+    }
+
+    #[test]
+    fn playnite_export() {
+        let (records, skipped) = read(PLAYNITE, &ExternalSource::Playnite).unwrap();
+        assert_eq!(skipped, 1);
+        assert_eq!(records.len(), 3);
+
+        let hollow = &records[0];
+        assert_eq!(hollow.title.as_deref(), Some("Hollow Knight"));
+        assert_eq!(
+            hollow.platform,
+            Some(DistributionPlatform::Steam {
+                id: String::from("367520")
+            })
+        );
+        assert_eq!(
+            hollow.tags,
+            Some(vec![String::from("Metroidvania"), String::from("Indie")])
+        );
+        assert_eq!(hollow.developer.as_deref(), Some("Team Cherry"));
+        assert_eq!(hollow.content_type, Some(ContentType::Game));
+        assert!(hollow.time_created.is_some());
+
+        let celeste = &records[1];
+        assert_eq!(celeste.title.as_deref(), Some("Celeste"));
+        assert_eq!(celeste.alias, Some(vec![String::from("Celeste!")]));
+        assert_eq!(
+            celeste.publisher.as_deref(),
+            Some("Matt Makes Games, Other")
+        );
+        assert_eq!(celeste.platform, Some(DistributionPlatform::Unknown));
+
+        let work = &records[2];
+        assert_eq!(
+            work.platform,
+            Some(DistributionPlatform::DLSite {
+                id: String::from("RJ123456")
+            })
+        );
+        assert_eq!(work.version.as_deref(), Some("1.2"));
+    }
+
+    #[test]
+    fn playnite_rejects_other_json() {
+        assert!(matches!(
+            read(r#"{"Items": []}"#, &ExternalSource::Playnite),
+            Err(ExternalError::FormatError("Playnite", _))
+        ));
+        assert!(matches!(
+            read("[{", &ExternalSource::Playnite),
+            Err(ExternalError::JsonError(_))
+        ));
+    }
+
+    #[test]
+    fn launchbox_export() {
+        let (records, skipped) = read(LAUNCHBOX, &ExternalSource::LaunchBox).unwrap();
+        assert_eq!(skipped, 0);
+        assert_eq!(records.len(), 2);
+
+        let hades = &records[0];
+        assert_eq!(hades.title.as_deref(), Some("Hades"));
+        assert_eq!(hades.alias, Some(vec![String::from("Hades I")]));
+        assert_eq!(
+            hades.tags,
+            Some(vec![
+                String::from("Action"),
+                String::from("Roguelike"),
+                String::from("Supergiant")
+            ])
+        );
+        assert_eq!(
+            hades.platform,
+            Some(DistributionPlatform::Steam {
+                id: String::from("1145360")
+            })
+        );
+        assert_eq!(
+            hades.time_created.map(|time| time.to_rfc3339()),
+            Some(String::from("2022-01-01T19:04:05.123456700+00:00"))
+        );
+
+        assert_eq!(
+            records[1].platform,
+            Some(DistributionPlatform::Other {
+                name: String::from("GOG"),
+                id: None
+            })
+        );
+    }
+
+    #[test]
+    fn launchbox_rejects_other_xml() {
+        assert!(matches!(
+            read("<Playnite/>", &ExternalSource::LaunchBox),
+            Err(ExternalError::FormatError("LaunchBox", _))
+        ));
+    }
+
+    #[test]
+    fn csv_mapped_columns() {
+        let mapping = CsvMapping {
+            title: String::from("Name"),
+            alias: Some(String::from("Aliases")),
+            content_type: Some(String::from("Kind")),
+            platform: Some(String::from("Store")),
+            platform_id: Some(String::from("Store Id")),
+            developer: Some(String::from("Maker")),
+            separator: String::from("|"),
+            ..Default::default()
+        };
+        let source = ExternalSource::Csv {
+            mapping: Box::new(mapping.clone()),
+        };
+        let (records, skipped) = read(CSV, &source).unwrap();
+        assert_eq!(skipped, 1);
+        assert_eq!(records.len(), 2);
+
+        let hades = &records[0];
+        assert_eq!(hades.title.as_deref(), Some("Hades"));
+        assert_eq!(
+            hades.alias,
+            Some(vec![String::from("Hadēs"), String::from("H")])
+        );
+        assert_eq!(hades.content_type, Some(ContentType::Novel));
+        assert_eq!(
+            hades.platform,
+            Some(DistributionPlatform::Steam {
+                id: String::from("1145360")
+            })
+        );
+        assert_eq!(hades.developer.as_deref(), Some("SG"));
+        assert_eq!(hades.tags, None);
+
+        let comma = &records[1];
+        assert_eq!(comma.title.as_deref(), Some("Title, with comma"));
+        assert_eq!(comma.content_type, Some(ContentType::Comic));
+        assert_eq!(comma.platform, Some(DistributionPlatform::Unknown));
+
+        let source = ExternalSource::Csv {
+            mapping: Box::new(CsvMapping {
+                tags: Some(String::from("Genres")),
+                ..mapping
+            }),
+        };
+        assert!(matches!(
+            read(CSV, &source),
+            Err(ExternalError::MissingColumn(column)) if column == "Genres"
+        ));
+    }
+
+    #[test]
+    fn csv_columns_skip_bom() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("library.csv");
+        fs::write(&path, CSV).unwrap();
+        let columns = external_csv_columns(&path).unwrap();
+        assert_eq!(columns[0], "Name");
+        assert_eq!(columns.len(), 6);
+    }
+}
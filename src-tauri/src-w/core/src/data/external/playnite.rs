@@ -0,0 +1,85 @@
+use super::{ExternalError, ExternalResult, external_platform, parse_time};
+use crate::data::metadata::{ContentType, MetadataOptional};
+use serde_json::{Map, Value};
+
+/// Name of a value, Playnite writes linked items such as tags as objects with a `Name`
+fn name_of(value: &Value) -> Option<String> {
+    match value {
+        Value::String(name) => Some(name.trim().to_string()).filter(|name| !name.is_empty()),
+        Value::Object(object) => object.get("Name").and_then(name_of),
+        _ => None,
+    }
+}
+
+/// Names of a field holding a single item or a list of them
+fn names(game: &Map<String, Value>, key: &str) -> Vec<String> {
+    match game.get(key) {
+        Some(Value::Array(items)) => items.iter().filter_map(name_of).collect(),
+        Some(value) => name_of(value).into_iter().collect(),
+        None => Vec::new(),
+    }
+}
+
+fn text(game: &Map<String, Value>, key: &str) -> Option<String> {
+    match game.get(key)? {
+        Value::String(value) => Some(value.trim().to_string()).filter(|value| !value.is_empty()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn record(game: &Map<String, Value>) -> MetadataOptional {
+    let title = text(game, "Name");
+    let alias = text(game, "SortingName")
+        .filter(|name| Some(name) != title.as_ref())
+        .into_iter()
+        .collect();
+    let mut tags = Vec::new();
+    for key in ["Genres", "Categories", "Tags"] {
+        for tag in names(game, key) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    let join = |key: &str| Some(names(game, key).join(", ")).filter(|names| !names.is_empty());
+    MetadataOptional {
+        title,
+        alias: Some(alias),
+        tags: Some(tags),
+        content_type: Some(ContentType::Game),
+        platform: Some(external_platform(
+            names(game, "Source").first().map(String::as_str),
+            text(game, "GameId").as_deref(),
+        )),
+        description: text(game, "Description"),
+        developer: join("Developers"),
+        publisher: join("Publishers"),
+        version: text(game, "Version"),
+        time_created: text(game, "Added").as_deref().and_then(parse_time),
+        ..Default::default()
+    }
+}
+
+/// Games of a Playnite export, either a plain array or an object holding it under `Games`
+pub(super) fn read(data: &str) -> ExternalResult<Vec<MetadataOptional>> {
+    let games = match serde_json::from_str::<Value>(data)? {
+        Value::Array(games) => Some(games),
+        Value::Object(mut object) => match object.remove("Games") {
+            Some(Value::Array(games)) => Some(games),
+            _ => None,
+        },
+        _ => None,
+    };
+    let Some(games) = games else {
+        return Err(ExternalError::FormatError(
+            "Playnite",
+            "no list of games".to_string(),
+        ));
+    };
+    Ok(games
+        .iter()
+        .filter_map(Value::as_object)
+        .map(record)
+        .collect())
+}
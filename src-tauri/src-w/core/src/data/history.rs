@@ -0,0 +1,200 @@
+use crate::data::metadata::Metadata;
+use bson::{Bson, Document};
+use chrono::{DateTime, Utc};
+use redb::{ReadTransaction, ReadableTable, TableDefinition, WriteTransaction};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use thiserror::Error;
+
+/// Change records keyed by entry id and a sequence increasing per entry
+const HISTORY_TABLE: TableDefinition<(&str, u64), Vec<u8>> = TableDefinition::new("HISTORY");
+
+/// Records kept per entry, the oldest are dropped beyond this
+const HISTORY_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub enum ChangeOperation {
+    Create,
+    Patch,
+    Remove,
+    Deploy,
+    DeployOff,
+
+    /// Deployment repaired, which may record its manifest, or issues fixed by a library repair
+    Repair,
+
+    /// Content hash recorded by a library verification
+    Verify,
+    Merge,
+    Import,
+
+    /// Removed by clearing the whole library
+    Clear,
+
+    /// Restored out of the trash
+    TrashRestore,
+
+    /// Restored out of the quarantine
+    Recover,
+
+    /// Restored to an earlier version from the history
+    Restore,
+
+    /// Reverted by an undo
+    Undo,
+
+    /// Applied again by a redo
+    Redo,
+
+    /// Replaced by the content of a backup
+    BackupRestore,
+}
+
+/// A top-level field of [`Metadata`] which changed, values as relaxed extended JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+
+    /// Unset if the field did not exist before, e.g. on create
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeRecord {
+    /// Id of the changed entry
+    pub id: String,
+    pub seq: u64,
+    pub time: DateTime<Utc>,
+    pub operation: ChangeOperation,
+    pub changes: Vec<FieldChange>,
+
+    /// The entry after this change, unset if it was removed
+    pub snapshot: Option<Metadata>,
+}
+
+/// Create the history table if missing
+pub(crate) fn history_init(write: &WriteTransaction) -> Result<(), redb::TableError> {
+    write.open_table(HISTORY_TABLE)?;
+    Ok(())
+}
+
+/// Field level difference between two versions of an entry, in the field order of `after`
+pub(crate) fn field_diff(
+    before: Option<&Metadata>,
+    after: Option<&Metadata>,
+) -> HistoryResult<Vec<FieldChange>> {
+    let to_document = |metadata: Option<&Metadata>| match metadata {
+        Some(metadata) => bson::to_document(metadata),
+        None => Ok(Document::new()),
+    };
+    let (before, after) = (to_document(before)?, to_document(after)?);
+    let to_json = |value: Option<&Bson>| value.map(|value| value.clone().into_relaxed_extjson());
+    let changes = after
+        .keys()
+        .chain(before.keys().filter(|key| !after.contains_key(key)))
+        .filter(|key| before.get(key) != after.get(key))
+        .map(|key| FieldChange {
+            field: key.clone(),
+            before: to_json(before.get(key)),
+            after: to_json(after.get(key)),
+        })
+        .collect();
+    Ok(changes)
+}
+
+/// Append a change of the entry `key` from `before` to `after`, nothing is recorded if no
+/// field changed. Older records beyond [`HISTORY_LIMIT`] are dropped.
+pub(crate) fn history_record(
+    write: &WriteTransaction,
+    key: &str,
+    operation: ChangeOperation,
+    before: Option<&Metadata>,
+    after: Option<&Metadata>,
+) -> HistoryResult<()> {
+    let changes = field_diff(before, after)?;
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let mut table = write.open_table(HISTORY_TABLE)?;
+    let seqs = table
+        .range((key, 0)..=(key, u64::MAX))?
+        .map(|entry| entry.map(|(k, _)| k.value().1))
+        .collect::<Result<Vec<_>, _>>()?;
+    let record = ChangeRecord {
+        id: key.to_string(),
+        seq: seqs.last().map_or(0, |seq| seq + 1),
+        time: Utc::now(),
+        operation,
+        changes,
+        snapshot: after.cloned(),
+    };
+    table.insert((key, record.seq), bson::to_vec(&record)?)?;
+
+    let excess = (seqs.len() + 1).saturating_sub(HISTORY_LIMIT);
+    for seq in &seqs[..excess] {
+        table.remove((key, *seq))?;
+    }
+    Ok(())
+}
+
+/// Change records of an entry, the oldest first
+pub(crate) fn history_list(read: &ReadTransaction, key: &str) -> HistoryResult<Vec<ChangeRecord>> {
+    let table = read.open_table(HISTORY_TABLE)?;
+    let mut records = Vec::new();
+    for entry in table.range((key, 0)..=(key, u64::MAX))? {
+        records.push(bson::from_slice(&entry?.1.value())?);
+    }
+    Ok(records)
+}
+
+/// The latest `limit` change records of the whole library, the newest first
+pub(crate) fn history_recent(
+    read: &ReadTransaction,
+    limit: usize,
+) -> HistoryResult<Vec<ChangeRecord>> {
+    let table = read.open_table(HISTORY_TABLE)?;
+    let mut records = Vec::new();
+    for entry in table.iter()? {
+        records.push(bson::from_slice::<ChangeRecord>(&entry?.1.value())?);
+    }
+    records.sort_by_key(|record| Reverse(record.time));
+    records.truncate(limit);
+    Ok(records)
+}
+
+pub(crate) fn history_get(
+    read: &ReadTransaction,
+    key: &str,
+    seq: u64,
+) -> HistoryResult<ChangeRecord> {
+    let table = read.open_table(HISTORY_TABLE)?;
+    match table.get((key, seq))? {
+        Some(raw) => Ok(bson::from_slice(&raw.value())?),
+        None => Err(HistoryError::NotFound(key.to_string(), seq)),
+    }
+}
+
+pub(crate) type HistoryResult<T> = Result<T, HistoryError>;
+
+#[derive(Debug, Error)]
+pub enum HistoryError {
+    #[error("History record {1} of {0} not found")]
+    NotFound(String, u64),
+
+    #[error("History record {1} of {0} has no version to restore")]
+    NoSnapshot(String, u64),
+
+    #[error("History table error: {0}")]
+    TableError(#[from] redb::TableError),
+
+    #[error("History storage error: {0}")]
+    StorageError(#[from] redb::StorageError),
+
+    #[error("BSON serialization error: {0}")]
+    SerializationError(#[from] bson::ser::Error),
+
+    #[error("BSON deserialization error: {0}")]
+    DeserializationError(#[from] bson::de::Error),
+}
@@ -0,0 +1,279 @@
+use crate::data::history::{HistoryResult, field_diff};
+use crate::data::metadata::Metadata;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Fields left out when comparing a local entry with an imported one
+const IGNORED_FIELDS: [&str; 1] = ["time_updated"];
+
+/// How an imported entry is applied to a local entry with the same id
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Default)]
+pub enum ImportMode {
+    /// The imported entry replaces the local one
+    #[default]
+    Overwrite,
+
+    /// The local entry is kept as it is
+    Skip,
+
+    /// The entry updated last is kept
+    KeepNewer,
+
+    /// Both are combined field by field, see [`Metadata::merge`]. Values set on both sides
+    /// are taken from the entry updated last.
+    Merge,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportItem {
+    pub id: String,
+    pub title: String,
+
+    /// Fields the local entry differs in, from the imported entry for conflicts
+    /// and from what was written otherwise
+    pub fields: Vec<String>,
+}
+
+/// What an import did, or would do for a dry run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub mode: ImportMode,
+    pub dry_run: bool,
+    pub added: Vec<ImportItem>,
+    pub updated: Vec<ImportItem>,
+    pub unchanged: Vec<ImportItem>,
+
+    /// Local entries differing from the imported ones, which were kept over them
+    pub conflicts: Vec<ImportItem>,
+}
+
+impl ImportReport {
+    pub(crate) fn new(mode: ImportMode, dry_run: bool) -> Self {
+        Self {
+            mode,
+            dry_run,
+            added: Vec::new(),
+            updated: Vec::new(),
+            unchanged: Vec::new(),
+            conflicts: Vec::new(),
+        }
+    }
+
+    /// File `item` under the outcome of `resolution`, returns the entry to write if any
+    pub(crate) fn record(
+        &mut self,
+        mut item: ImportItem,
+        resolution: ImportResolution,
+    ) -> Option<Metadata> {
+        match resolution {
+            ImportResolution::Add(metadata) => {
+                self.added.push(item);
+                Some(metadata)
+            }
+            ImportResolution::Update(metadata, fields) => {
+                item.fields = fields;
+                self.updated.push(item);
+                Some(metadata)
+            }
+            ImportResolution::Unchanged => {
+                self.unchanged.push(item);
+                None
+            }
+            ImportResolution::Conflict(fields) => {
+                item.fields = fields;
+                self.conflicts.push(item);
+                None
+            }
+        }
+    }
+}
+
+pub(crate) enum ImportResolution {
+    Add(Metadata),
+    Update(Metadata, Vec<String>),
+    Unchanged,
+    Conflict(Vec<String>),
+}
+
+/// Names of the fields two entries differ in
+fn differing_fields(local: &Metadata, other: &Metadata) -> HistoryResult<Vec<String>> {
+    Ok(field_diff(Some(local), Some(other))?
+        .into_iter()
+        .map(|change| change.field)
+        .filter(|field| !IGNORED_FIELDS.contains(&field.as_str()))
+        .collect())
+}
+
+/// Combine both versions of an entry, the one updated last takes precedence
+fn merge(local: &Metadata, imported: Metadata) -> Metadata {
+    let mut merged = if local.time_updated >= imported.time_updated {
+        local.clone().merge(imported, false)
+    } else {
+        imported.merge(local.clone(), false)
+    };
+    // Both versions carry the local deployments
+    let mut seen = HashSet::new();
+    merged
+        .deployments
+        .retain(|deployment| seen.insert(deployment.id.clone()));
+    merged
+}
+
+/// Decide how `imported` is applied over `local`, the entry with the same id if any.
+/// Deployments only apply to this machine, those of `local` are kept in every mode.
+pub(crate) fn import_resolve(
+    local: Option<&Metadata>,
+    mut imported: Metadata,
+    mode: ImportMode,
+) -> HistoryResult<ImportResolution> {
+    let Some(local) = local else {
+        return Ok(ImportResolution::Add(imported));
+    };
+    imported.deployments.clone_from(&local.deployments);
+    let fields = differing_fields(local, &imported)?;
+    if fields.is_empty() {
+        return Ok(ImportResolution::Unchanged);
+    }
+    let resolution = match mode {
+        ImportMode::Overwrite => ImportResolution::Update(imported, fields),
+        ImportMode::Skip => ImportResolution::Conflict(fields),
+        ImportMode::KeepNewer if imported.time_updated > local.time_updated => {
+            ImportResolution::Update(imported, fields)
+        }
+        ImportMode::KeepNewer => ImportResolution::Conflict(fields),
+        ImportMode::Merge => {
+            let merged = merge(local, imported);
+            let fields = differing_fields(local, &merged)?;
+            if fields.is_empty() {
+                ImportResolution::Unchanged
+            } else {
+                ImportResolution::Update(merged, fields)
+            }
+        }
+    };
+    Ok(resolution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::metadata::{DeployInfo, DeployMode, Deployment, MetadataBuilder};
+    use chrono::{TimeDelta, Utc};
+
+    fn entry(title: &str, tags: &[&str], age: i64) -> Metadata {
+        MetadataBuilder::default()
+            .id("entry")
+            .title(title)
+            .tags(tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>())
+            .time_updated(Utc::now() - TimeDelta::days(age))
+            .build()
+            .unwrap()
+    }
+
+    fn deployment(id: &str) -> Deployment {
+        Deployment {
+            id: id.to_string(),
+            info: DeployInfo::Directory {
+                path: format!("/games/{id}"),
+                mode: DeployMode::Copy,
+            },
+            time: Utc::now(),
+            manifest: None,
+        }
+    }
+
+    /// A local entry deployed here and an imported one carrying a deployment of elsewhere
+    fn pair(local_age: i64, imported_age: i64) -> (Metadata, Metadata) {
+        let mut local = entry("Local", &["a"], local_age);
+        local.deployments.push(deployment("here"));
+        let mut imported = entry("Imported", &["b"], imported_age);
+        imported.deployments.push(deployment("elsewhere"));
+        (local, imported)
+    }
+
+    fn deployment_ids(metadata: &Metadata) -> Vec<&str> {
+        metadata
+            .deployments
+            .iter()
+            .map(|deployment| deployment.id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn resolve_without_local_adds() {
+        let (_, imported) = pair(0, 0);
+        let resolution = import_resolve(None, imported, ImportMode::Skip).unwrap();
+        let ImportResolution::Add(added) = resolution else {
+            panic!("expected an addition");
+        };
+        assert_eq!(deployment_ids(&added), ["elsewhere"]);
+    }
+
+    #[test]
+    fn resolve_identical_is_unchanged() {
+        let (local, _) = pair(1, 0);
+        let mut imported = local.clone();
+        imported.time_updated = Utc::now();
+        imported.deployments.clear();
+        for mode in [
+            ImportMode::Overwrite,
+            ImportMode::Skip,
+            ImportMode::KeepNewer,
+            ImportMode::Merge,
+        ] {
+            let resolution = import_resolve(Some(&local), imported.clone(), mode).unwrap();
+            assert!(matches!(resolution, ImportResolution::Unchanged));
+        }
+    }
+
+    #[test]
+    fn resolve_overwrite_keeps_local_deployments() {
+        let (local, imported) = pair(0, 1);
+        let resolution = import_resolve(Some(&local), imported, ImportMode::Overwrite).unwrap();
+        let ImportResolution::Update(updated, fields) = resolution else {
+            panic!("expected an update");
+        };
+        assert_eq!(updated.title, "Imported");
+        assert_eq!(deployment_ids(&updated), ["here"]);
+        assert!(fields.contains(&"title".to_string()));
+        assert!(!fields.contains(&"deployments".to_string()));
+    }
+
+    #[test]
+    fn resolve_skip_reports_conflict() {
+        let (local, imported) = pair(1, 0);
+        let resolution = import_resolve(Some(&local), imported, ImportMode::Skip).unwrap();
+        let ImportResolution::Conflict(fields) = resolution else {
+            panic!("expected a conflict");
+        };
+        assert!(fields.contains(&"title".to_string()));
+    }
+
+    #[test]
+    fn resolve_keep_newer_compares_update_times() {
+        let (local, imported) = pair(1, 0);
+        let resolution = import_resolve(Some(&local), imported, ImportMode::KeepNewer).unwrap();
+        let ImportResolution::Update(updated, _) = resolution else {
+            panic!("expected the newer import to be taken");
+        };
+        assert_eq!(updated.title, "Imported");
+        assert_eq!(deployment_ids(&updated), ["here"]);
+
+        let (local, imported) = pair(0, 1);
+        let resolution = import_resolve(Some(&local), imported, ImportMode::KeepNewer).unwrap();
+        assert!(matches!(resolution, ImportResolution::Conflict(_)));
+    }
+
+    #[test]
+    fn resolve_merge_combines_and_keeps_local_deployments() {
+        let (local, imported) = pair(1, 0);
+        let resolution = import_resolve(Some(&local), imported, ImportMode::Merge).unwrap();
+        let ImportResolution::Update(merged, _) = resolution else {
+            panic!("expected a merge");
+        };
+        assert_eq!(merged.title, "Imported");
+        assert_eq!(merged.alias, ["Local"]);
+        assert_eq!(merged.tags, ["b", "a"]);
+        assert_eq!(deployment_ids(&merged), ["here"]);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::data::metadata::Metadata;
+use redb::{MultimapTableDefinition, ReadTransaction, WriteTransaction};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+const TAG_INDEX: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("INDEX_TAG");
+const PLATFORM_INDEX: MultimapTableDefinition<&str, &str> =
+    MultimapTableDefinition::new("INDEX_PLATFORM");
+const DEVELOPER_INDEX: MultimapTableDefinition<&str, &str> =
+    MultimapTableDefinition::new("INDEX_DEVELOPER");
+const CONTENT_TYPE_INDEX: MultimapTableDefinition<&str, &str> =
+    MultimapTableDefinition::new("INDEX_CONTENT_TYPE");
+
+/// Secondary indexes of the library, each maps a field value to the ids having it
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub enum LibraryIndex {
+    Tag,
+
+    /// Keyed by [`crate::data::metadata::DistributionPlatform::index_key`]
+    Platform,
+    Developer,
+
+    /// Keyed by the variant name, e.g. `Game`
+    ContentType,
+}
+
+impl LibraryIndex {
+    const ALL: [LibraryIndex; 4] = [
+        LibraryIndex::Tag,
+        LibraryIndex::Platform,
+        LibraryIndex::Developer,
+        LibraryIndex::ContentType,
+    ];
+
+    fn table(&self) -> MultimapTableDefinition<'static, &'static str, &'static str> {
+        match self {
+            LibraryIndex::Tag => TAG_INDEX,
+            LibraryIndex::Platform => PLATFORM_INDEX,
+            LibraryIndex::Developer => DEVELOPER_INDEX,
+            LibraryIndex::ContentType => CONTENT_TYPE_INDEX,
+        }
+    }
+
+    /// Keys of `metadata` in this index
+    fn keys(&self, metadata: &Metadata) -> Vec<String> {
+        match self {
+            LibraryIndex::Tag => metadata.tags.clone(),
+            LibraryIndex::Platform => metadata.platform.index_key().into_iter().collect(),
+            LibraryIndex::Developer => metadata
+                .developer
+                .as_deref()
+                .map(str::trim)
+                .filter(|developer| !developer.is_empty())
+                .map(str::to_string)
+                .into_iter()
+                .collect(),
+            LibraryIndex::ContentType => vec![format!("{:?}", metadata.content_type)],
+        }
+    }
+}
+
+/// Create the index tables if missing
+pub(crate) fn index_init(write: &WriteTransaction) -> Result<(), redb::TableError> {
+    for index in LibraryIndex::ALL {
+        write.open_multimap_table(index.table())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn index_insert(write: &WriteTransaction, metadata: &Metadata) -> IndexResult<()> {
+    for index in LibraryIndex::ALL {
+        let mut table = write.open_multimap_table(index.table())?;
+        for key in index.keys(metadata) {
+            table.insert(key.as_str(), metadata.id.as_str())?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn index_remove(write: &WriteTransaction, metadata: &Metadata) -> IndexResult<()> {
+    for index in LibraryIndex::ALL {
+        let mut table = write.open_multimap_table(index.table())?;
+        for key in index.keys(metadata) {
+            table.remove(key.as_str(), metadata.id.as_str())?;
+        }
+    }
+    Ok(())
+}
+
+/// Drop every index entry, e.g. before rebuilding them
+pub(crate) fn index_clear(write: &WriteTransaction) -> IndexResult<()> {
+    for index in LibraryIndex::ALL {
+        write.delete_multimap_table(index.table())?;
+        write.open_multimap_table(index.table())?;
+    }
+    Ok(())
+}
+
+/// Replace every index with the entries of `entries`
+pub(crate) fn index_rebuild(write: &WriteTransaction, entries: &[Metadata]) -> IndexResult<()> {
+    index_clear(write)?;
+    for metadata in entries {
+        index_insert(write, metadata)?;
+    }
+    Ok(())
+}
+
+/// Ids having `key` in `index`
+pub(crate) fn index_lookup(
+    read: &ReadTransaction,
+    index: LibraryIndex,
+    key: &str,
+) -> IndexResult<Vec<String>> {
+    let table = read.open_multimap_table(index.table())?;
+    let mut ids = Vec::new();
+    for id in table.get(key)? {
+        ids.push(id?.value().to_string());
+    }
+    Ok(ids)
+}
+
+pub(crate) type IndexResult<T> = Result<T, IndexError>;
+
+#[derive(Debug, Error)]
+pub enum IndexError {
+    #[error("Index table error: {0}")]
+    TableError(#[from] redb::TableError),
+
+    #[error("Index storage error: {0}")]
+    StorageError(#[from] redb::StorageError),
+}
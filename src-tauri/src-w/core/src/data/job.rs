@@ -0,0 +1,736 @@
+use crate::data::catalog::CatalogLabels;
+use crate::data::external::ExternalSource;
+use crate::data::import::ImportMode;
+use crate::data::library::{
+    LibraryError, internal_lib, lib_backup_restore, lib_bundle_export, lib_bundle_import,
+    lib_catalog_export, lib_check, lib_deploy, lib_deploy_off, lib_duplicates, lib_export,
+    lib_external_import, lib_external_preview, lib_get, lib_import, lib_orphan_adopt, lib_repair,
+    lib_repair_deployment, lib_trash_empty, lib_update, lib_verify, lib_verify_deployment,
+};
+use crate::data::metadata::{ArchiveInfo, DeployMode, MetadataOptional};
+use crate::data::transfer::{ExportFilter, TransferFormat};
+use crate::util::progress::{Progress, ProgressHandle, progress_start};
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use redb::{ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+use thiserror::Error;
+
+const JOB_TABLE: TableDefinition<&str, Vec<u8>> = TableDefinition::new("JOB");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum JobKind {
+    /// Create or update an entry, including creating its archive
+    Update {
+        opt: Box<MetadataOptional>,
+
+        /// Whether an archive password was taken out before storing the job, see
+        /// [`JobKind::take_secret`]
+        #[serde(default)]
+        redacted: bool,
+    },
+    Deploy {
+        key: String,
+        target: String,
+        #[serde(default)]
+        mode: DeployMode,
+    },
+    DeployOff {
+        key: String,
+
+        /// Empty for jobs queued before deployments had ids, see [`job_deployment`]
+        #[serde(default)]
+        deployment: String,
+    },
+    VerifyDeployment {
+        key: String,
+        deployment: String,
+    },
+    RepairDeployment {
+        key: String,
+        deployment: String,
+    },
+    /// Check the sources of all entries
+    Verify,
+
+    /// Check the stored records for issues, see [`lib_check`]
+    Check,
+
+    /// Fix the issues found by a check, see [`lib_repair`]
+    Repair,
+
+    /// Find groups of entries which are likely the same work, see [`lib_duplicates`]
+    Duplicates,
+
+    /// Purge every trashed entry, see [`lib_trash_empty`]
+    TrashEmpty {
+        #[serde(default)]
+        delete_archive: bool,
+    },
+
+    /// Replace the library with a backup, see [`lib_backup_restore`]
+    BackupRestore {
+        id: String,
+    },
+    /// Create stub entries for orphaned archives, see [`lib_orphan_adopt`]
+    OrphanAdopt {
+        paths: Vec<String>,
+    },
+    /// Import entries from a file, see [`lib_import`]
+    Import {
+        #[serde(default)]
+        path: Option<String>,
+        #[serde(default)]
+        format: Option<TransferFormat>,
+        #[serde(default)]
+        mode: ImportMode,
+        #[serde(default)]
+        dry_run: bool,
+    },
+    /// Export entries to a file, see [`lib_export`]
+    Export {
+        #[serde(default)]
+        path: Option<String>,
+        #[serde(default)]
+        format: Option<TransferFormat>,
+        #[serde(default)]
+        filter: ExportFilter,
+    },
+    /// Import entries and their archives from a bundle, see [`lib_bundle_import`]
+    BundleImport {
+        path: String,
+        #[serde(default)]
+        mode: ImportMode,
+        #[serde(default)]
+        dry_run: bool,
+    },
+    /// Export entries and their archives to a bundle, see [`lib_bundle_export`]
+    BundleExport {
+        #[serde(default)]
+        path: Option<String>,
+        #[serde(default)]
+        filter: ExportFilter,
+    },
+    /// Render entries into a static HTML catalog, see [`lib_catalog_export`]
+    CatalogExport {
+        #[serde(default)]
+        path: Option<String>,
+        #[serde(default)]
+        filter: ExportFilter,
+        #[serde(default)]
+        labels: Box<CatalogLabels>,
+    },
+    /// List the records of a library exported by another tool, see [`lib_external_preview`]
+    ExternalPreview {
+        path: String,
+        source: ExternalSource,
+    },
+    /// Create entries from a library exported by another tool, see [`lib_external_import`]
+    ExternalImport {
+        path: String,
+        source: ExternalSource,
+        selected: Vec<usize>,
+
+        /// Selected records to create even if they look like duplicates
+        #[serde(default)]
+        confirmed: Vec<usize>,
+    },
+}
+
+impl JobKind {
+    pub fn label(&self) -> String {
+        match self {
+            JobKind::Update { opt, .. } => match &opt.id {
+                Some(id) => format!("Update {id}"),
+                None => "Create".to_string(),
+            },
+            JobKind::Deploy { key, .. } => format!("Deploy {key}"),
+            JobKind::DeployOff { key, .. } => format!("Deploy off {key}"),
+            JobKind::VerifyDeployment { key, .. } => format!("Verify deployment of {key}"),
+            JobKind::RepairDeployment { key, .. } => format!("Repair deployment of {key}"),
+            JobKind::Verify => "Verify library".to_string(),
+            JobKind::Check => "Check library".to_string(),
+            JobKind::Repair => "Repair library".to_string(),
+            JobKind::Duplicates => "Find duplicates".to_string(),
+            JobKind::TrashEmpty { .. } => "Empty trash".to_string(),
+            JobKind::BackupRestore { .. } => "Restore backup".to_string(),
+            JobKind::OrphanAdopt { paths } => format!("Adopt {} orphaned archives", paths.len()),
+            JobKind::Import { dry_run: false, .. } => "Import library".to_string(),
+            JobKind::Import { dry_run: true, .. } => "Preview library import".to_string(),
+            JobKind::Export { .. } => "Export library".to_string(),
+            JobKind::BundleImport { dry_run: false, .. } => "Import bundle".to_string(),
+            JobKind::BundleImport { dry_run: true, .. } => "Preview bundle import".to_string(),
+            JobKind::BundleExport { .. } => "Export bundle".to_string(),
+            JobKind::CatalogExport { .. } => "Export catalog".to_string(),
+            JobKind::ExternalPreview { .. } => "Read another library".to_string(),
+            JobKind::ExternalImport { selected, .. } => {
+                format!("Import {} entries from another library", selected.len())
+            }
+        }
+    }
+
+    /// Take the archive password out of an update, so the stored job holds none
+    fn take_secret(&mut self) -> Option<String> {
+        let JobKind::Update { opt, redacted } = self else {
+            return None;
+        };
+        let Some(ArchiveInfo::ArchiveFile { password, .. }) = &mut opt.archive_info else {
+            return None;
+        };
+        let secret = password.take();
+        *redacted |= secret.is_some();
+        secret
+    }
+
+    /// The job to run with the password taken by [`JobKind::take_secret`] put back,
+    /// `None` if it is needed but was lost with a restart
+    fn with_secret(&self, secret: Option<&String>) -> Option<JobKind> {
+        let mut kind = self.clone();
+        if let JobKind::Update { opt, redacted } = &mut kind
+            && *redacted
+        {
+            let secret = secret?;
+            if let Some(ArchiveInfo::ArchiveFile { password, .. }) = &mut opt.archive_info {
+                *password = Some(secret.clone());
+            }
+            *redacted = false;
+        }
+        Some(kind)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub enum JobState {
+    Queued,
+    Running,
+    Finished,
+    Failed { error: String },
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub kind: JobKind,
+    pub state: JobState,
+
+    /// Output of a finished job, e.g. the id of the created entry or the deploy off report
+    #[serde(default)]
+    pub result: Option<Value>,
+
+    /// Progress of the last run, live while the job is running
+    #[serde(default)]
+    pub progress: Option<Progress>,
+
+    #[serde(default)]
+    pub attempts: u32,
+
+    pub time_created: DateTime<Utc>,
+
+    #[serde(default)]
+    pub time_started: Option<DateTime<Utc>>,
+
+    #[serde(default)]
+    pub time_finished: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobProgress {
+    pub job: String,
+    pub progress: Progress,
+}
+
+#[derive(Debug, Clone)]
+pub enum JobEvent {
+    /// The job has been queued or changed its state
+    Updated(Box<Job>),
+    Progress(JobProgress),
+}
+
+type JobListener = Box<dyn Fn(JobEvent) + Send + Sync>;
+
+struct JobRunner {
+    queue: Mutex<VecDeque<String>>,
+    signal: Condvar,
+    running: Mutex<Option<(String, ProgressHandle)>>,
+
+    /// Archive passwords of update jobs by job id, only kept in memory
+    secrets: Mutex<HashMap<String, String>>,
+    listener: JobListener,
+}
+
+fn runner() -> JobResult<&'static Arc<JobRunner>> {
+    RUNNER.get().ok_or(JobError::NotInitialized)
+}
+
+static RUNNER: OnceLock<Arc<JobRunner>> = OnceLock::new();
+
+fn internal_get(id: &str) -> JobResult<Job> {
+    let read = internal_lib().begin_read()?;
+    let table = read.open_table(JOB_TABLE)?;
+    match table.get(id)? {
+        Some(raw) => Ok(bson::from_slice(&raw.value())?),
+        None => Err(JobError::NotFound(id.to_string())),
+    }
+}
+
+fn internal_set(job: &Job) -> JobResult<()> {
+    let write = internal_lib().begin_write()?;
+    {
+        let mut table = write.open_table(JOB_TABLE)?;
+        table.insert(job.id.as_str(), bson::to_vec(job)?)?;
+    }
+    write.commit()?;
+    Ok(())
+}
+
+fn internal_all() -> JobResult<Vec<Job>> {
+    let read = internal_lib().begin_read()?;
+    let table = read.open_table(JOB_TABLE)?;
+    let mut result = Vec::new();
+    for entry in table.iter()? {
+        let (_, raw) = entry?;
+        match bson::from_slice::<Job>(&raw.value()) {
+            Ok(job) => result.push(job),
+            Err(e) => error!("Failed to deserialize Job: {}", e),
+        }
+    }
+    result.sort_by_key(|job| job.time_created);
+    Ok(result)
+}
+
+/// Save the job and notify the listener
+fn publish(job: &Job) -> JobResult<()> {
+    internal_set(job)?;
+    if let Some(runner) = RUNNER.get() {
+        (runner.listener)(JobEvent::Updated(Box::new(job.clone())));
+    }
+    Ok(())
+}
+
+/// Start the background worker, jobs queued before the last shutdown are picked up again
+/// and jobs interrupted while running are marked as failed so they could be retried.
+pub fn job_init(listener: impl Fn(JobEvent) + Send + Sync + 'static) {
+    fn init_table() -> JobResult<()> {
+        let write = internal_lib().begin_write()?;
+        {
+            write.open_table(JOB_TABLE)?;
+        }
+        write.commit()?;
+        Ok(())
+    }
+
+    init_table().expect("Unable to init job table");
+
+    let runner = Arc::new(JobRunner {
+        queue: Mutex::new(VecDeque::new()),
+        signal: Condvar::new(),
+        running: Mutex::new(None),
+        secrets: Mutex::new(HashMap::new()),
+        listener: Box::new(listener),
+    });
+    if RUNNER.set(runner.clone()).is_err() {
+        warn!("Job runner already initialized");
+        return;
+    }
+
+    let jobs = internal_all().expect("Unable to load jobs");
+    for mut job in jobs {
+        match job.state {
+            JobState::Queued => runner.queue.lock().unwrap().push_back(job.id.clone()),
+            JobState::Running => {
+                warn!("Job {} was interrupted by shutdown", job.id);
+                job.state = JobState::Failed {
+                    error: "Interrupted by application shutdown".to_string(),
+                };
+                job.time_finished = Some(Utc::now());
+                if let Err(e) = internal_set(&job) {
+                    error!("Failed to update interrupted job {}: {}", job.id, e);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    thread::Builder::new()
+        .name("job-worker".to_string())
+        .spawn(move || worker(runner))
+        .expect("Failed to spawn job worker");
+}
+
+fn worker(runner: Arc<JobRunner>) {
+    loop {
+        // The job is marked as running before the queue is released, so it could not be
+        // cancelled in between as if it was still queued
+        let mut queue = runner.queue.lock().unwrap();
+        let Some(id) = queue.pop_front() else {
+            drop(runner.signal.wait(queue).unwrap());
+            continue;
+        };
+
+        let mut job = match internal_get(&id) {
+            Ok(job) if job.state == JobState::Queued => job,
+            Ok(_) => continue,
+            Err(e) => {
+                error!("Failed to load queued job {id}: {e}");
+                continue;
+            }
+        };
+
+        let progress = {
+            let runner = runner.clone();
+            let job_id = id.clone();
+            progress_start(job.kind.label(), move |progress| {
+                (runner.listener)(JobEvent::Progress(JobProgress {
+                    job: job_id.clone(),
+                    progress: progress.clone(),
+                }))
+            })
+        };
+        *runner.running.lock().unwrap() = Some((id.clone(), progress.clone()));
+        drop(queue);
+
+        info!("Running job {id}: {}", job.kind.label());
+        job.state = JobState::Running;
+        job.attempts += 1;
+        job.time_started = Some(Utc::now());
+        job.time_finished = None;
+        job.result = None;
+        if let Err(e) = publish(&job) {
+            error!("Failed to update job {id}: {e}");
+        }
+
+        let secret = runner.secrets.lock().unwrap().get(&id).cloned();
+        let result = match job.kind.with_secret(secret.as_ref()) {
+            Some(kind) => execute(&kind, &progress).map_err(|e| e.to_string()),
+            None => Err(String::from(
+                "The archive password is not kept across restarts, submit the update again",
+            )),
+        };
+
+        *runner.running.lock().unwrap() = None;
+        job.progress = Some(progress.snapshot());
+        job.time_finished = Some(Utc::now());
+        job.state = match result {
+            Ok(output) => {
+                info!("Job {id} finished");
+                runner.secrets.lock().unwrap().remove(&id);
+                job.result = output;
+                JobState::Finished
+            }
+            Err(_) if progress.is_cancelled() => {
+                info!("Job {id} cancelled");
+                JobState::Cancelled
+            }
+            Err(e) => {
+                error!("Job {id} failed: {e}");
+                JobState::Failed { error: e }
+            }
+        };
+        if let Err(e) = publish(&job) {
+            error!("Failed to update job {id}: {e}");
+        }
+    }
+}
+
+/// Deployment a job works on. Jobs queued before deployments had ids name none, they meant
+/// the single deployment an entry had then.
+fn job_deployment(key: &str, deployment: &str) -> Result<String, LibraryError> {
+    if !deployment.is_empty() {
+        return Ok(deployment.to_string());
+    }
+    match lib_get(key)?.deployments.as_slice() {
+        [deployment] => Ok(deployment.id.clone()),
+        deployments => Err(LibraryError::DeploymentUnnamed(
+            key.to_string(),
+            deployments.len(),
+        )),
+    }
+}
+
+fn execute(kind: &JobKind, progress: &ProgressHandle) -> Result<Option<Value>, LibraryError> {
+    match kind {
+        JobKind::Update { opt, .. } => lib_update(*opt.clone(), progress).map(|id| Some(id.into())),
+        JobKind::Deploy { key, target, mode } => {
+            lib_deploy(key, target, *mode, progress).map(|id| Some(id.into()))
+        }
+        JobKind::DeployOff { key, deployment } => {
+            let deployment = job_deployment(key, deployment)?;
+            Ok(serde_json::to_value(lib_deploy_off(key, &deployment, progress)?).ok())
+        }
+        JobKind::VerifyDeployment { key, deployment } => {
+            Ok(serde_json::to_value(lib_verify_deployment(key, deployment, progress)?).ok())
+        }
+        JobKind::RepairDeployment { key, deployment } => {
+            Ok(serde_json::to_value(lib_repair_deployment(key, deployment, progress)?).ok())
+        }
+        JobKind::Verify => Ok(serde_json::to_value(lib_verify(progress)?).ok()),
+        JobKind::Check => Ok(serde_json::to_value(lib_check(progress)?).ok()),
+        JobKind::Repair => Ok(serde_json::to_value(lib_repair(progress)?).ok()),
+        JobKind::Duplicates => Ok(serde_json::to_value(lib_duplicates()?).ok()),
+        JobKind::TrashEmpty { delete_archive } => {
+            lib_trash_empty(*delete_archive, progress).map(|count| Some(count.into()))
+        }
+        JobKind::BackupRestore { id } => Ok(serde_json::to_value(lib_backup_restore(id)?).ok()),
+        JobKind::OrphanAdopt { paths } => {
+            Ok(serde_json::to_value(lib_orphan_adopt(paths, progress)?).ok())
+        }
+        JobKind::Import {
+            path,
+            format,
+            mode,
+            dry_run,
+        } => Ok(serde_json::to_value(lib_import(path.as_deref(), *format, *mode, *dry_run)?).ok()),
+        JobKind::Export {
+            path,
+            format,
+            filter,
+        } => Ok(serde_json::to_value(lib_export(path.as_deref(), *format, filter)?).ok()),
+        JobKind::BundleImport {
+            path,
+            mode,
+            dry_run,
+        } => Ok(serde_json::to_value(lib_bundle_import(path, *mode, *dry_run, progress)?).ok()),
+        JobKind::BundleExport { path, filter } => {
+            Ok(serde_json::to_value(lib_bundle_export(path.as_deref(), filter, progress)?).ok())
+        }
+        JobKind::CatalogExport {
+            path,
+            filter,
+            labels,
+        } => Ok(serde_json::to_value(lib_catalog_export(
+            path.as_deref(),
+            filter,
+            labels,
+            progress,
+        )?)
+        .ok()),
+        JobKind::ExternalPreview { path, source } => {
+            Ok(serde_json::to_value(lib_external_preview(path, source, progress)?).ok())
+        }
+        JobKind::ExternalImport {
+            path,
+            source,
+            selected,
+            confirmed,
+        } => Ok(serde_json::to_value(lib_external_import(
+            path, source, selected, confirmed, progress,
+        )?)
+        .ok()),
+    }
+}
+
+/// Queue a new job, returns its id
+pub fn job_submit(mut kind: JobKind) -> JobResult<String> {
+    let runner = runner()?;
+    let secret = kind.take_secret();
+    let job = Job {
+        id: uuid::Uuid::new_v4().to_string(),
+        kind,
+        state: JobState::Queued,
+        result: None,
+        progress: None,
+        attempts: 0,
+        time_created: Utc::now(),
+        time_started: None,
+        time_finished: None,
+    };
+    if let Some(secret) = secret {
+        runner
+            .secrets
+            .lock()
+            .unwrap()
+            .insert(job.id.clone(), secret);
+    }
+    publish(&job)?;
+    info!("Job {} submitted: {}", job.id, job.kind.label());
+
+    runner.queue.lock().unwrap().push_back(job.id.clone());
+    runner.signal.notify_one();
+    Ok(job.id)
+}
+
+pub fn job_get(id: &str) -> JobResult<Job> {
+    let mut job = internal_get(id)?;
+    if let Some((running, progress)) = runner()?.running.lock().unwrap().as_ref()
+        && running == id
+    {
+        job.progress = Some(progress.snapshot());
+    }
+    Ok(job)
+}
+
+pub fn job_list() -> JobResult<Vec<Job>> {
+    let mut jobs = internal_all()?;
+    if let Some((running, progress)) = runner()?.running.lock().unwrap().as_ref()
+        && let Some(job) = jobs.iter_mut().find(|job| &job.id == running)
+    {
+        job.progress = Some(progress.snapshot());
+    }
+    Ok(jobs)
+}
+
+/// Cancel a queued or running job, returns false if the job has already ended
+pub fn job_cancel(id: &str) -> JobResult<bool> {
+    let runner = runner()?;
+    let mut queue = runner.queue.lock().unwrap();
+    if let Some((running, progress)) = runner.running.lock().unwrap().as_ref()
+        && running == id
+    {
+        info!("Cancelling running job {id}");
+        progress.cancel();
+        return Ok(true);
+    }
+
+    let mut job = internal_get(id)?;
+    let Some(index) = queue.iter().position(|queued| queued == id) else {
+        return Ok(false);
+    };
+    queue.remove(index);
+    drop(queue);
+
+    job.state = JobState::Cancelled;
+    job.time_finished = Some(Utc::now());
+    publish(&job)?;
+    info!("Cancelled queued job {id}");
+    Ok(true)
+}
+
+/// Queue a failed or cancelled job again
+pub fn job_retry(id: &str) -> JobResult<()> {
+    let runner = runner()?;
+    let mut job = internal_get(id)?;
+    match job.state {
+        JobState::Failed { .. } | JobState::Cancelled => {}
+        _ => return Err(JobError::InvalidState(id.to_string())),
+    }
+    job.state = JobState::Queued;
+    job.progress = None;
+    job.time_started = None;
+    job.time_finished = None;
+    publish(&job)?;
+    info!("Job {id} queued for retry");
+
+    runner.queue.lock().unwrap().push_back(job.id);
+    runner.signal.notify_one();
+    Ok(())
+}
+
+/// Remove all jobs which have ended from the history
+pub fn job_clear() -> JobResult<usize> {
+    let ended = internal_all()?
+        .into_iter()
+        .filter(|job| !matches!(job.state, JobState::Queued | JobState::Running))
+        .map(|job| job.id)
+        .collect::<Vec<_>>();
+
+    let write = internal_lib().begin_write()?;
+    {
+        let mut table = write.open_table(JOB_TABLE)?;
+        for id in &ended {
+            table.remove(id.as_str())?;
+        }
+    }
+    write.commit()?;
+    if let Some(runner) = RUNNER.get() {
+        let mut secrets = runner.secrets.lock().unwrap();
+        for id in &ended {
+            secrets.remove(id);
+        }
+    }
+    Ok(ended.len())
+}
+
+type JobResult<T> = Result<T, JobError>;
+
+#[derive(Debug, Error)]
+pub enum JobError {
+    #[error("Job with id {0} not found")]
+    NotFound(String),
+
+    #[error("Job with id {0} is not in a state allowing this operation")]
+    InvalidState(String),
+
+    #[error("Job runner is not initialized")]
+    NotInitialized,
+
+    #[error("Database transaction error: {0}")]
+    TransactionError(Box<redb::TransactionError>),
+
+    #[error("Database table error: {0}")]
+    TableError(#[from] redb::TableError),
+
+    #[error("Database storage error: {0}")]
+    StorageError(#[from] redb::StorageError),
+
+    #[error("Database commit error: {0}")]
+    CommitError(#[from] redb::CommitError),
+
+    #[error("BSON serialization error: {0}")]
+    SerializationError(#[from] bson::ser::Error),
+
+    #[error("BSON deserialization error: {0}")]
+    DeserializationError(#[from] bson::de::Error),
+}
+
+impl From<redb::TransactionError> for JobError {
+    fn from(e: redb::TransactionError) -> Self {
+        JobError::TransactionError(Box::new(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(password: Option<&str>) -> JobKind {
+        JobKind::Update {
+            opt: Box::new(MetadataOptional {
+                archive_info: Some(ArchiveInfo::ArchiveFile {
+                    path: String::from("game.7z"),
+                    password: password.map(String::from),
+                    format: Default::default(),
+                }),
+                ..Default::default()
+            }),
+            redacted: false,
+        }
+    }
+
+    #[test]
+    fn stored_updates_hold_no_password() {
+        let mut kind = update(Some("secret"));
+        let secret = kind.take_secret();
+        assert_eq!(secret.as_deref(), Some("secret"));
+        let stored = bson::to_vec(&kind).unwrap();
+        assert!(!stored.windows(6).any(|w| w == b"secret"));
+
+        let restored = kind.with_secret(secret.as_ref()).unwrap();
+        let JobKind::Update { opt, redacted } = restored else {
+            unreachable!()
+        };
+        assert!(!redacted);
+        assert!(matches!(
+            opt.archive_info,
+            Some(ArchiveInfo::ArchiveFile { password: Some(ref p), .. }) if p == "secret"
+        ));
+        assert!(kind.with_secret(None).is_none());
+    }
+
+    #[test]
+    fn updates_without_password_need_no_secret() {
+        let mut kind = update(None);
+        assert!(kind.take_secret().is_none());
+        assert!(matches!(
+            kind.with_secret(None),
+            Some(JobKind::Update {
+                redacted: false,
+                ..
+            })
+        ));
+    }
+}
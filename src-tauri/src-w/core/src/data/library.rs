@@ -1,9 +1,62 @@
-use crate::data::metadata::{Metadata, MetadataError, MetadataOptional};
+use crate::data::backup::{
+    BackupError, BackupInfo, BackupRestoreReport, backup_delete, backup_describe, backup_list,
+    backup_load, backup_on_open, backup_snapshot,
+};
+use crate::data::bundle::{
+    BUNDLE_EXT, BundleError, BundleExportReport, BundleFile, BundleImportReport, BundleTarget,
+    bundle_extract, bundle_manifest, bundle_target, bundle_write,
+};
+use crate::data::catalog::{CatalogError, CatalogLabels, CatalogReport, catalog_write};
+use crate::data::check::{
+    CheckExport, CheckFinding, CheckReport, RepairReport, check_record, repair_entry,
+};
+use crate::data::duplicate::{
+    DuplicateGroup, DuplicateMatch, check_duplicates, duplicate_candidate, find_duplicates,
+};
+use crate::data::external::{
+    ExternalCandidate, ExternalError, ExternalImportReport, ExternalPreview, ExternalSource,
+    external_csv_columns, external_read,
+};
+use crate::data::history::{
+    ChangeOperation, ChangeRecord, HistoryError, history_get, history_init, history_list,
+    history_recent, history_record,
+};
+use crate::data::import::{ImportItem, ImportMode, ImportReport, ImportResolution, import_resolve};
+use crate::data::index::{
+    IndexError, LibraryIndex, index_clear, index_init, index_insert, index_lookup, index_rebuild,
+    index_remove,
+};
+use crate::data::manifest::{
+    DeployOffReport, DriftReport, ManifestError, manifest_get, manifest_init, manifest_insert,
+    manifest_remove,
+};
+use crate::data::metadata::{
+    ArchiveInfo, DeployMode, IntegrityReport, IntegrityStatus, Metadata, MetadataError,
+    MetadataOptional,
+};
+use crate::data::orphan::{OrphanArchive, orphan_scan};
+use crate::data::query::{LibraryQuery, QueryPage};
+use crate::data::save::{SaveError, SaveSnapshot, save_list, save_move, save_rollback};
+use crate::data::schema::{
+    QuarantinedRecord, SchemaError, decode_all, quarantine_list, quarantine_move,
+    quarantine_restore, quarantine_take, raw_document, schema_migrate,
+};
+use crate::data::transfer::{
+    ExportFilter, ExportReport, TransferError, TransferFormat, transfer_decode, transfer_encode,
+};
+use crate::data::trash::{
+    TrashEntry, TrashError, trash_init, trash_insert, trash_list, trash_take,
+};
+use crate::data::undo::{EntryChange, UndoState, UndoStep, UndoSummary, undo_stack, undoable};
 use crate::util::config::config_get;
-use chrono::Local;
+use crate::util::progress::ProgressHandle;
+use crate::{DIR_ARCHIVE, DIR_COVER};
+use chrono::{Local, TimeDelta, Utc};
 use const_format::concatcp;
 use log::{error, info, warn};
-use redb::{Database, ReadableTable, TableDefinition};
+use redb::{Database, ReadableTable, TableDefinition, WriteTransaction};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 use std::{error, fs};
 use thiserror::Error;
@@ -12,48 +65,32 @@ const LIB_FILE_STEM: &str = "library";
 const LIB_FILE_EXT: &str = "bin";
 const LIB_EXPORT_EXT: &str = "json";
 const LIB_FILE_NAME: &str = concatcp!(LIB_FILE_STEM, ".", LIB_FILE_EXT);
-const LIB_EXPORT_FILE_NAME: &str = concatcp!(LIB_FILE_STEM, ".", LIB_EXPORT_EXT);
+/// Exports of issues a repair could not fix are named `library.check.{timestamp}.json`
+const LIB_CHECK_INFIX: &str = "check";
+const LIB_BUNDLE_INFIX: &str = "bundle";
+/// Catalogs are written to `library.catalog` in the data root if no directory is given
+const LIB_CATALOG_INFIX: &str = "catalog";
 
-const BACKUP_DIR: &str = "backup";
+/// Name of the snapshot taken of the library before a backup is restored over it
+const BACKUP_RESTORE_SNAPSHOT: &str = "before-restore";
 
-const LIB_TABLE: TableDefinition<&str, Vec<u8>> = TableDefinition::new("LIBRARY");
+pub(crate) const LIB_TABLE: TableDefinition<&str, Vec<u8>> = TableDefinition::new("LIBRARY");
 
-fn internal_lib() -> &'static Database {
+pub(crate) fn internal_lib() -> &'static Database {
     static DB: OnceLock<Database> = OnceLock::new();
 
-    fn init_db() -> Database {
+    /// Open the database, returns the backup taken of it if there was one
+    fn init_db() -> (Database, Option<PathBuf>) {
         let root_path = config_get().get_root();
         if !root_path.exists() {
             fs::create_dir_all(&root_path).expect("Failed to create application data directory");
         }
 
-        let backup_dir = root_path.join(BACKUP_DIR);
-        if !backup_dir.exists() {
-            fs::create_dir_all(&backup_dir).expect("Failed to create backup directory");
-        }
-
         let bin_path = root_path.join(LIB_FILE_NAME);
-        if bin_path.exists() {
-            let timestamp = Local::now().format("%Y%m%d%H%M%S");
-            let backup_file =
-                backup_dir.join(format!("{LIB_FILE_STEM}.{timestamp}.{LIB_FILE_EXT}"));
-            fs::copy(&bin_path, &backup_file).expect("Failed to copy backup binary");
-
-            let mut backups = fs::read_dir(&backup_dir)
-                .expect("Failed to read backup directory")
-                .filter_map(|e| e.ok())
-                .filter(|e| e.path().is_file())
-                .collect::<Vec<_>>();
-            backups.sort_by_key(|e| e.metadata().and_then(|m| m.modified()).ok());
-
-            if backups.len() > 5 {
-                for entry in &backups[..backups.len() - 5] {
-                    let _ = fs::remove_file(entry.path());
-                }
-            }
-        }
+        let backup = backup_on_open(&bin_path).expect("Failed to back up library");
 
-        Database::create(bin_path).expect("Unable to open library")
+        let db = Database::create(bin_path).expect("Unable to open library");
+        (db, backup)
     }
 
     fn init_table(db: &Database) -> Result<(), Box<dyn error::Error>> {
@@ -62,13 +99,23 @@ fn internal_lib() -> &'static Database {
             let table = write.open_table(LIB_TABLE)?;
             table.get("SOME_KEY")?;
         }
+        index_init(&write)?;
+        history_init(&write)?;
+        trash_init(&write)?;
+        manifest_init(&write)?;
         write.commit()?;
         Ok(())
     }
 
     DB.get_or_init(|| {
-        let db = init_db();
+        let (db, backup) = init_db();
         init_table(&db).expect("Unable to init library table");
+        if let Some(backup) = &backup
+            && let Err(e) = backup_describe(&db, backup)
+        {
+            warn!("Failed to describe backup {}: {}", backup.display(), e);
+        }
+        schema_migrate(&db, backup.as_deref()).expect("Unable to migrate library");
         db
     })
 }
@@ -80,7 +127,7 @@ fn internal_get(key: &str) -> LibraryResult<Metadata> {
         Some(raw) => raw,
         None => return Err(LibraryError::NotFound(key.to_string())),
     };
-    match bson::from_slice(&raw.value()) {
+    match bson::from_slice::<Metadata>(&raw.value()) {
         Ok(v) => Ok(v),
         Err(e) => {
             error!("Failed to deserialize Metadata: {}", e);
@@ -89,49 +136,292 @@ fn internal_get(key: &str) -> LibraryResult<Metadata> {
     }
 }
 
-fn internal_set(key: &str, value: Metadata) -> LibraryResult<()> {
-    let write = internal_lib().begin_write()?;
+fn internal_set(key: &str, value: Metadata, operation: ChangeOperation) -> LibraryResult<()> {
+    let mut write = LibraryWrite::begin(operation)?;
+    write.set(key, &value)?;
+    write.commit()
+}
+
+/// Apply `change` to the entry as it is stored now. Long operations work on a copy read before
+/// they started, writing that copy back would drop whatever changed in the meantime.
+/// Fails with [`LibraryError::NotFound`] if the entry was removed or trashed since.
+fn internal_apply(
+    key: &str,
+    operation: ChangeOperation,
+    change: impl FnOnce(&mut Metadata),
+) -> LibraryResult<()> {
+    let mut write = LibraryWrite::begin(operation)?;
+    let Some(mut metadata) = write.get(key)? else {
+        write.abort()?;
+        warn!("Metadata {key} is gone, dropping the result of {operation:?}");
+        return Err(LibraryError::NotFound(key.to_string()));
+    };
+    change(&mut metadata);
+    write.set(key, &metadata)?;
+    write.commit()
+}
+
+/// Write transaction keeping the indexes and the history in sync with the entries.
+/// The changes are recorded for undo on commit if `operation` is undoable.
+struct LibraryWrite {
+    write: WriteTransaction,
+    operation: ChangeOperation,
+    changes: Vec<EntryChange>,
+}
+
+impl LibraryWrite {
+    fn begin(operation: ChangeOperation) -> LibraryResult<Self> {
+        Ok(Self {
+            write: internal_lib().begin_write()?,
+            operation,
+            changes: Vec::new(),
+        })
+    }
+
+    /// The entry as seen inside this transaction
+    fn get(&self, key: &str) -> LibraryResult<Option<Metadata>> {
+        let table = self.write.open_table(LIB_TABLE)?;
+        let raw = table.get(key)?.map(|raw| raw.value());
+        Ok(raw.map(|raw| bson::from_slice(&raw)).transpose()?)
+    }
+
+    /// Insert or replace an entry, a copy of it left in the trash is dropped.
+    /// Manifests attached to its deployments are stored apart, those of deployments
+    /// no longer listed are dropped.
+    fn set(&mut self, key: &str, value: &Metadata) -> LibraryResult<()> {
+        let mut value = value.clone();
+        for (deployment, manifest) in value.take_manifests() {
+            manifest_insert(&self.write, &deployment, &manifest)?;
+        }
+        let (_, old) = take_entry(&self.write, key)?;
+        trash_take(&self.write, key)?;
+        {
+            let mut table = self.write.open_table(LIB_TABLE)?;
+            table.insert(key, bson::to_vec(&value)?)?;
+        }
+        index_insert(&self.write, &value)?;
+        if let Some(old) = &old {
+            let dropped = old
+                .deployments
+                .iter()
+                .filter(|deployment| value.get_deployment(&deployment.id).is_none())
+                .map(|deployment| deployment.id.as_str())
+                .collect::<Vec<_>>();
+            manifest_release(&self.write, &dropped)?;
+        }
+        history_record(&self.write, key, self.operation, old.as_ref(), Some(&value))?;
+        self.changes.push(EntryChange {
+            key: key.to_string(),
+            before: old,
+            after: Some(value),
+            trashed: false,
+        });
+        Ok(())
+    }
+
+    /// Remove an entry along with the manifests of its deployments, returns false if
+    /// there was none
+    fn remove(&mut self, key: &str) -> LibraryResult<bool> {
+        let (removed, old) = take_entry(&self.write, key)?;
+        if let Some(old) = &old {
+            let dropped = old
+                .deployments
+                .iter()
+                .map(|deployment| deployment.id.as_str())
+                .collect::<Vec<_>>();
+            manifest_release(&self.write, &dropped)?;
+        }
+        if old.is_some() {
+            history_record(&self.write, key, self.operation, old.as_ref(), None)?;
+            self.changes.push(EntryChange {
+                key: key.to_string(),
+                before: old,
+                after: None,
+                trashed: false,
+            });
+        }
+        Ok(removed)
+    }
+
+    /// Remove an entry into the trash, returns false if there was none
+    fn trash(&mut self, key: &str) -> LibraryResult<bool> {
+        let (removed, old) = take_entry(&self.write, key)?;
+        if let Some(old) = old {
+            trash_insert(&self.write, &old)?;
+            history_record(&self.write, key, self.operation, Some(&old), None)?;
+            self.changes.push(EntryChange {
+                key: key.to_string(),
+                before: Some(old),
+                after: None,
+                trashed: true,
+            });
+        }
+        Ok(removed)
+    }
+
+    /// Drop everything written, nothing is recorded for undo
+    fn abort(self) -> LibraryResult<()> {
+        self.write.abort()?;
+        Ok(())
+    }
+
+    /// Commit the transaction, must not be called while holding [`undo_stack`]
+    /// for an undoable operation
+    fn commit(self) -> LibraryResult<()> {
+        self.write.commit()?;
+        if undoable(self.operation) && !self.changes.is_empty() {
+            undo_stack().push(UndoStep {
+                operation: self.operation,
+                time: Utc::now(),
+                changes: self.changes,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Drop the stored manifests of the `deployments` no stored entry lists anymore,
+/// merged entries take over the deployments of the other one
+fn manifest_release(write: &WriteTransaction, deployments: &[&str]) -> LibraryResult<()> {
+    if deployments.is_empty() {
+        return Ok(());
+    }
+    let mut listed = HashSet::new();
+    for entry in write.open_table(LIB_TABLE)?.iter()? {
+        if let Ok(metadata) = bson::from_slice::<Metadata>(&entry?.1.value()) {
+            listed.extend(
+                metadata
+                    .deployments
+                    .into_iter()
+                    .map(|deployment| deployment.id),
+            );
+        }
+    }
+    for deployment in deployments {
+        if !listed.contains(*deployment) {
+            manifest_remove(write, deployment)?;
+        }
+    }
+    Ok(())
+}
+
+/// Attach the stored manifest to a deployment of `metadata`, see [`Deployment::manifest`]
+fn manifest_attach(metadata: &mut Metadata, deployment: &str) -> LibraryResult<()> {
+    let manifest = manifest_get(&internal_lib().begin_read()?, deployment)?;
+    if let Some(item) = metadata
+        .deployments
+        .iter_mut()
+        .find(|item| item.id == deployment)
     {
-        let mut table = write.open_table(LIB_TABLE)?;
-        let raw = bson::to_vec(&value)?;
-        table.insert(key, raw)?;
+        item.manifest = manifest;
     }
-    write.commit()?;
     Ok(())
 }
 
+/// Remove the stored record and its index entries, returns whether there was one and
+/// the decoded record
+fn take_entry(write: &WriteTransaction, key: &str) -> LibraryResult<(bool, Option<Metadata>)> {
+    let raw = {
+        let mut table = write.open_table(LIB_TABLE)?;
+        table.remove(key)?.map(|raw| raw.value())
+    };
+    let Some(raw) = raw else {
+        return Ok((false, None));
+    };
+    match bson::from_slice::<Metadata>(&raw) {
+        Ok(old) => {
+            index_remove(write, &old)?;
+            Ok((true, Some(old)))
+        }
+        Err(e) => {
+            // Stale index entries are skipped on lookup and dropped by a rebuild
+            warn!("Failed to deserialize replaced metadata {}: {}", key, e);
+            Ok((true, None))
+        }
+    }
+}
+
 pub fn lib_init() {
     internal_lib();
+    if let Err(e) = lib_trash_expire() {
+        error!("Failed to purge expired trash: {}", e);
+    }
 }
 
 pub fn lib_get(key: &str) -> LibraryResult<Metadata> {
     internal_get(key)
 }
 
+/// All entries, records failing to decode are moved into the quarantine and left out
 pub fn lib_get_all() -> LibraryResult<Vec<Metadata>> {
-    let read = internal_lib().begin_read()?;
-    let table = read.open_table(LIB_TABLE)?;
     let mut result = Vec::new();
-    for entry in table.iter()? {
-        let (_, raw) = entry?;
-        match bson::from_slice(&raw.value()) {
-            Ok(v) => result.push(v),
-            Err(e) => {
-                error!("Failed to deserialize Metadata: {}", e);
-                return Err(LibraryError::DeserializationError(e));
+    let mut failed = Vec::new();
+    {
+        let read = internal_lib().begin_read()?;
+        let table = read.open_table(LIB_TABLE)?;
+        for entry in table.iter()? {
+            let (key, raw) = entry?;
+            match bson::from_slice::<Metadata>(&raw.value()) {
+                Ok(v) => result.push(v),
+                Err(e) => {
+                    error!("Failed to deserialize Metadata {}: {}", key.value(), e);
+                    failed.push(key.value().to_string());
+                }
             }
         }
     }
+    if !failed.is_empty() {
+        let write = internal_lib().begin_write()?;
+        for key in &failed {
+            quarantine_move(&write, key)?;
+        }
+        write.commit()?;
+    }
     Ok(result)
 }
 
-pub fn lib_update(mut opt: MetadataOptional) -> LibraryResult<String> {
+/// Search the library, see [`LibraryQuery`]
+pub fn lib_query(query: &LibraryQuery) -> LibraryResult<QueryPage> {
+    Ok(query.apply(lib_get_all()?))
+}
+
+/// Entries having `key` in `index`, e.g. all entries tagged with `key`
+pub fn lib_find(index: LibraryIndex, key: &str) -> LibraryResult<Vec<Metadata>> {
+    let read = internal_lib().begin_read()?;
+    let table = read.open_table(LIB_TABLE)?;
+    let mut result = Vec::new();
+    for id in index_lookup(&read, index, key)? {
+        let Some(raw) = table.get(id.as_str())? else {
+            warn!("Index {:?} refers to missing metadata {}", index, id);
+            continue;
+        };
+        let mut metadata = bson::from_slice::<Metadata>(&raw.value())?;
+        metadata.upgrade_legacy_deploy();
+        result.push(metadata);
+    }
+    Ok(result)
+}
+
+/// Recreate every index from the stored entries, returns the count of indexed entries
+pub fn lib_rebuild_index() -> LibraryResult<usize> {
+    let write = internal_lib().begin_write()?;
+    let entries = decode_all(&write)?;
+    index_rebuild(&write, &entries)?;
+    let count = entries.len();
+    write.commit()?;
+    info!("Rebuilt library indexes for {} entries", count);
+    Ok(count)
+}
+
+pub fn lib_update(mut opt: MetadataOptional, progress: &ProgressHandle) -> LibraryResult<String> {
     if let Some(id) = opt.id.clone() {
         info!("Updating metadata with id: {}", id);
         match internal_get(id.as_str()) {
-            Ok(mut metadata) => {
-                metadata = metadata.patch(opt)?;
-                internal_set(id.as_str(), metadata)?;
+            Ok(metadata) => {
+                let patched = metadata.patch(opt.clone(), progress)?;
+                internal_apply(id.as_str(), ChangeOperation::Patch, |metadata| {
+                    metadata.take_patched(patched, &opt)
+                })?;
                 Ok(id)
             }
             Err(LibraryError::NotFound(_)) => {
@@ -140,9 +430,9 @@ pub fn lib_update(mut opt: MetadataOptional) -> LibraryResult<String> {
                     id
                 );
                 opt.id = None;
-                let metadata = Metadata::init(opt)?;
+                let metadata = Metadata::init(opt, progress)?;
                 let id = metadata.id.clone();
-                internal_set(id.as_str(), metadata)?;
+                internal_set(id.as_str(), metadata, ChangeOperation::Create)?;
                 Ok(id)
             }
             Err(e) => {
@@ -151,78 +441,731 @@ pub fn lib_update(mut opt: MetadataOptional) -> LibraryResult<String> {
             }
         }
     } else {
-        let metadata = Metadata::init(opt)?;
+        let metadata = Metadata::init(opt, progress)?;
         let id = metadata.id.clone();
         info!("Creating new metadata with id: {}", id);
-        internal_set(id.as_str(), metadata)?;
+        internal_set(id.as_str(), metadata, ChangeOperation::Create)?;
         Ok(id)
     }
 }
 
 pub fn lib_remove(key: &str) -> LibraryResult<bool> {
-    let write = internal_lib().begin_write()?;
-    let removed = {
-        let mut table = write.open_table(LIB_TABLE)?;
-        table.remove(key)?.is_some()
-    };
+    let mut write = LibraryWrite::begin(ChangeOperation::Remove)?;
+    let removed = write.trash(key)?;
     write.commit()?;
     Ok(removed)
 }
 
 pub fn lib_clear() -> LibraryResult<()> {
-    let write = internal_lib().begin_write()?;
-    {
-        write.delete_table(LIB_TABLE)?;
-    }
-    write.commit()?;
-
-    let write = internal_lib().begin_write()?;
-    {
-        let table = write.open_table(LIB_TABLE)?;
-        table.get("SOME_KEY")?;
+    let mut write = LibraryWrite::begin(ChangeOperation::Clear)?;
+    let keys = {
+        let table = write.write.open_table(LIB_TABLE)?;
+        let mut keys = Vec::new();
+        for entry in table.iter()? {
+            keys.push(entry?.0.value().to_string());
+        }
+        keys
+    };
+    for key in keys {
+        write.trash(&key)?;
     }
+    // Drop what is left of undecodable records as well
+    index_clear(&write.write)?;
     write.commit()?;
 
     info!("Library cleared");
     Ok(())
 }
 
-pub fn lib_deploy(key: &str, target: &str) -> LibraryResult<()> {
+/// Deploy an entry to `target` as an additional deployment, returns the deployment id
+pub fn lib_deploy(
+    key: &str,
+    target: &str,
+    mode: DeployMode,
+    progress: &ProgressHandle,
+) -> LibraryResult<String> {
     let mut metadata = internal_get(key)?;
-    if metadata.deploy(target)? {
-        info!("Successfully deployed metadata with key: {}", key);
-        internal_set(key, metadata)?;
-        Ok(())
+    let archive_info = metadata.archive_info.clone();
+    if let Some(id) = metadata.deploy(target, mode, progress)? {
+        info!("Successfully deployed metadata with key: {} as {}", key, id);
+        let deployment = metadata.get_deployment(&id).cloned();
+        internal_apply(key, ChangeOperation::Deploy, |current| {
+            current.deployments.extend(deployment);
+            current.mark_update();
+        })
+        .inspect_err(|_| warn!("Deployment {id} at {target} is left unrecorded"))?;
+        Ok(id)
     } else {
         error!("Failed to deploy metadata with key: {}", key);
-        internal_set(key, metadata)?;
+        // The archive info was found invalid, only record that if nothing replaced it since
+        internal_apply(key, ChangeOperation::Deploy, |current| {
+            if current.archive_info == archive_info {
+                current.archive_info = metadata.archive_info;
+                current.mark_update();
+            }
+        })?;
         Err(LibraryError::DeployError)
     }
 }
 
-pub fn lib_deploy_off(key: &str) -> LibraryResult<()> {
+pub fn lib_deploy_off(
+    key: &str,
+    deployment: &str,
+    progress: &ProgressHandle,
+) -> LibraryResult<DeployOffReport> {
     let mut metadata = internal_get(key)?;
-    if metadata.deploy_off()? {
-        info!("Successfully removed deployed metadata with key: {}", key);
-        internal_set(key, metadata)?;
-        Ok(())
+    manifest_attach(&mut metadata, deployment)?;
+    let report = metadata.deploy_off(deployment, progress)?;
+    // The deployment is dropped either way, see `Metadata::deploy_off`
+    internal_apply(key, ChangeOperation::DeployOff, |current| {
+        current.deployments.retain(|item| item.id != deployment);
+        current.mark_update();
+    })?;
+    if let Some(report) = report {
+        info!(
+            "Successfully removed deployment {} of metadata with key: {}",
+            deployment, key
+        );
+        Ok(report)
     } else {
-        error!("Failed to remove deployed metadata with key: {}", key);
-        internal_set(key, metadata)?;
+        error!(
+            "Failed to remove deployment {} of metadata with key: {}",
+            deployment, key
+        );
         Err(LibraryError::DeployError)
     }
 }
 
-pub fn lib_export() -> LibraryResult<()> {
-    let all_metadata = lib_get_all()?;
-    let export_path = config_get().get_root().join(LIB_EXPORT_FILE_NAME);
-    fs::write(&export_path, serde_json::to_string_pretty(&all_metadata)?)?;
-    info!("Library exported to {}", export_path.display());
+pub fn lib_verify_deployment(
+    key: &str,
+    deployment: &str,
+    progress: &ProgressHandle,
+) -> LibraryResult<DriftReport> {
+    let mut metadata = internal_get(key)?;
+    manifest_attach(&mut metadata, deployment)?;
+    Ok(metadata.verify_deployment(deployment, progress)?)
+}
+
+/// Repair missing and modified files of a deployment, returns the drift found before repairing
+pub fn lib_repair_deployment(
+    key: &str,
+    deployment: &str,
+    progress: &ProgressHandle,
+) -> LibraryResult<DriftReport> {
+    let mut metadata = internal_get(key)?;
+    manifest_attach(&mut metadata, deployment)?;
+    let recorded = metadata
+        .get_deployment(deployment)
+        .is_some_and(|item| item.manifest.is_some());
+    let report = metadata.repair_deployment(deployment, progress)?;
+    // Only a manifest missing before is recorded by a repair
+    if !recorded {
+        let manifest = metadata
+            .get_deployment(deployment)
+            .and_then(|item| item.manifest.clone());
+        internal_apply(key, ChangeOperation::Repair, |current| {
+            if let Some(item) = current
+                .deployments
+                .iter_mut()
+                .find(|item| item.id == deployment)
+            {
+                item.manifest = manifest;
+                current.mark_update();
+            }
+        })?;
+    }
+    info!(
+        "Repaired deployment {} of metadata with key: {}",
+        deployment, key
+    );
+    Ok(report)
+}
+
+/// Rehash the sources of all entries and test their archives, entries without a stored hash
+/// get the current one recorded
+pub fn lib_verify(progress: &ProgressHandle) -> LibraryResult<Vec<IntegrityReport>> {
+    let mut reports = Vec::new();
+    for mut metadata in lib_get_all()? {
+        let status = metadata.verify_source(progress);
+        if progress.is_cancelled() {
+            return Err(MetadataError::Cancelled.into());
+        }
+        if status == IntegrityStatus::Recorded {
+            let recorded = internal_apply(&metadata.id, ChangeOperation::Verify, |current| {
+                if current.archive_hash.is_none() && current.archive_info == metadata.archive_info {
+                    current.archive_hash = metadata.archive_hash.clone();
+                }
+            });
+            match recorded {
+                Ok(()) => {}
+                // Removed while verifying, there is nothing left to record the hash on
+                Err(LibraryError::NotFound(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        reports.push(IntegrityReport {
+            id: metadata.id,
+            title: metadata.title,
+            status,
+        });
+    }
+    let issues = reports
+        .iter()
+        .filter(|report| {
+            !matches!(
+                report.status,
+                IntegrityStatus::Intact | IntegrityStatus::Recorded | IntegrityStatus::Unset
+            )
+        })
+        .count();
+    info!(
+        "Verified {} entries, {} with integrity issues",
+        reports.len(),
+        issues
+    );
+    Ok(reports)
+}
+
+/// Every stored record as it is, undecodable ones included
+fn raw_records() -> LibraryResult<Vec<(String, Vec<u8>)>> {
+    let read = internal_lib().begin_read()?;
+    let table = read.open_table(LIB_TABLE)?;
+    let mut records = Vec::new();
+    for entry in table.iter()? {
+        let (key, raw) = entry?;
+        records.push((key.value().to_string(), raw.value()));
+    }
+    Ok(records)
+}
+
+/// Walk the stored records and report what is wrong with them, nothing is changed
+pub fn lib_check(progress: &ProgressHandle) -> LibraryResult<CheckReport> {
+    let records = raw_records()?;
+    progress.set_total(0, records.len() as u64);
+    let mut findings = Vec::new();
+    for (key, raw) in &records {
+        if progress.is_cancelled() {
+            return Err(MetadataError::Cancelled.into());
+        }
+        progress.start_file(key.as_str());
+        let (metadata, issues) = check_record(raw);
+        if !issues.is_empty() {
+            findings.push(CheckFinding {
+                id: key.clone(),
+                title: metadata.map(|metadata| metadata.title),
+                issues,
+            });
+        }
+        progress.finish_file();
+    }
+    progress.flush();
+    info!(
+        "Checked {} records, {} with issues",
+        records.len(),
+        findings.len()
+    );
+    Ok(CheckReport {
+        checked: records.len(),
+        findings,
+    })
+}
+
+/// Check the library and fix what can be fixed. Undecodable records are moved into the
+/// quarantine, they and the other issues left are exported to a file in the data root.
+pub fn lib_repair(progress: &ProgressHandle) -> LibraryResult<RepairReport> {
+    let records = raw_records()?;
+    progress.set_total(0, records.len() as u64);
+    let mut checked = Vec::new();
+    for (key, raw) in records {
+        if progress.is_cancelled() {
+            return Err(MetadataError::Cancelled.into());
+        }
+        progress.start_file(key.as_str());
+        let (metadata, issues) = check_record(&raw);
+        if !issues.is_empty() {
+            checked.push((key, raw, metadata, issues));
+        }
+        progress.finish_file();
+    }
+    progress.flush();
+
+    let mut report = RepairReport {
+        repaired: 0,
+        quarantined: 0,
+        unresolved: Vec::new(),
+        export: None,
+    };
+    let mut exports = Vec::new();
+    let mut write = LibraryWrite::begin(ChangeOperation::Repair)?;
+    for (key, raw, metadata, issues) in checked {
+        let current = {
+            let table = write.write.open_table(LIB_TABLE)?;
+            table.get(key.as_str())?.map(|raw| raw.value())
+        };
+        if current.as_ref() != Some(&raw) {
+            warn!(
+                "Metadata {} changed while checking, left for the next check",
+                key
+            );
+            continue;
+        }
+        let title = metadata.as_ref().map(|metadata| metadata.title.clone());
+        let left = match metadata {
+            Some(mut metadata) => {
+                let found = issues.len();
+                let left = repair_entry(&mut metadata, issues);
+                if left.len() < found {
+                    write.set(&key, &metadata)?;
+                    report.repaired += found - left.len();
+                }
+                left
+            }
+            None => {
+                quarantine_move(&write.write, &key)?;
+                report.quarantined += 1;
+                issues
+            }
+        };
+        if !left.is_empty() {
+            let finding = CheckFinding {
+                id: key,
+                title,
+                issues: left,
+            };
+            exports.push(CheckExport {
+                finding: finding.clone(),
+                document: raw_document(&raw),
+            });
+            report.unresolved.push(finding);
+        }
+    }
+    write.commit()?;
+
+    if !exports.is_empty() {
+        let timestamp = Local::now().format("%Y%m%d%H%M%S");
+        let export_path = config_get().get_root().join(format!(
+            "{LIB_FILE_STEM}.{LIB_CHECK_INFIX}.{timestamp}.{LIB_EXPORT_EXT}"
+        ));
+        fs::write(&export_path, serde_json::to_string_pretty(&exports)?)?;
+        info!(
+            "Unresolved check issues exported to {}",
+            export_path.display()
+        );
+        report.export = Some(export_path.to_string_lossy().to_string());
+    }
+    info!(
+        "Repaired {} issues, quarantined {} records, {} entries left unresolved",
+        report.repaired,
+        report.quarantined,
+        report.unresolved.len()
+    );
+    Ok(report)
+}
+
+/// Groups of entries which are likely the same work, see [`find_duplicates`]
+pub fn lib_duplicates() -> LibraryResult<Vec<DuplicateGroup>> {
+    Ok(find_duplicates(&lib_get_all()?))
+}
+
+/// Library entries likely to be the same work as the entry `opt` would create, checked before
+/// an entry is created by hand
+pub fn lib_duplicates_of(opt: &MetadataOptional) -> LibraryResult<Vec<DuplicateMatch>> {
+    let candidate = duplicate_candidate(opt)?;
+    Ok(check_duplicates(&candidate, &lib_get_all()?, &[]))
+}
+
+/// Merge `secondary` into `primary` and move it into the trash, save snapshots are moved along.
+/// The archive of `secondary` is kept instead if `keep_secondary_archive` is set.
+pub fn lib_merge(
+    primary: &str,
+    secondary: &str,
+    keep_secondary_archive: bool,
+) -> LibraryResult<Metadata> {
+    if primary == secondary {
+        return Err(LibraryError::MergeError(primary.to_string()));
+    }
+    let target = internal_get(primary)?;
+    let source = internal_get(secondary)?;
+    let merged = target.merge(source, keep_secondary_archive);
+
+    let mut write = LibraryWrite::begin(ChangeOperation::Merge)?;
+    write.set(primary, &merged)?;
+    write.trash(secondary)?;
+    write.commit()?;
+
+    // The entries are merged already, a failure here only leaves the snapshots behind
+    if let Err(err) = save_move(secondary, primary) {
+        error!("Failed to move save snapshots of {secondary} to {primary}: {err}");
+    }
+    info!("Merged metadata {} into {}", secondary, primary);
+    Ok(merged)
+}
+
+/// Change records of an entry, the oldest first
+pub fn lib_history(key: &str) -> LibraryResult<Vec<ChangeRecord>> {
+    let read = internal_lib().begin_read()?;
+    Ok(history_list(&read, key)?)
+}
+
+/// The latest `limit` changes of the whole library, the newest first
+pub fn lib_history_recent(limit: usize) -> LibraryResult<Vec<ChangeRecord>> {
+    let read = internal_lib().begin_read()?;
+    Ok(history_recent(&read, limit)?)
+}
+
+/// Restore an entry to the version after the change `seq`, also if it was removed since.
+///
+/// Only the data is restored, the archive and deployments of the existing entry are kept
+/// as they reflect the files on disk.
+pub fn lib_history_restore(key: &str, seq: u64) -> LibraryResult<Metadata> {
+    let record = history_get(&internal_lib().begin_read()?, key, seq)?;
+    let mut restored = record
+        .snapshot
+        .ok_or_else(|| HistoryError::NoSnapshot(key.to_string(), seq))?;
+    match internal_get(key) {
+        Ok(current) => {
+            restored.archive_info = current.archive_info;
+            restored.archive_size = current.archive_size;
+            restored.archive_hash = current.archive_hash;
+            restored.deployments = current.deployments;
+        }
+        Err(LibraryError::NotFound(_)) => {}
+        Err(e) => return Err(e),
+    }
+    restored.time_updated = Utc::now();
+    internal_set(key, restored.clone(), ChangeOperation::Restore)?;
+    info!("Restored metadata {} to history record {}", key, seq);
+    Ok(restored)
+}
+
+/// Revert the latest undoable operation, returns what was undone or `None` if nothing was left.
+///
+/// Fails without changing anything if one of its entries was changed since, the operation
+/// is dropped then as it could not apply anymore.
+pub fn lib_undo() -> LibraryResult<Option<UndoSummary>> {
+    let mut stack = undo_stack();
+    let Some(step) = stack.pop_undo() else {
+        return Ok(None);
+    };
+    match apply_step(ChangeOperation::Undo, step.inverse()) {
+        Ok(()) => {
+            info!(
+                "Undone {:?} of {} entries",
+                step.operation,
+                step.changes.len()
+            );
+            let summary = step.summary();
+            stack.push_redo(step);
+            Ok(Some(summary))
+        }
+        Err(e @ LibraryError::UndoConflict(_)) => Err(e),
+        Err(e) => {
+            stack.push_undo(step);
+            Err(e)
+        }
+    }
+}
+
+/// Apply again the latest undone operation, returns what was redone or `None` if nothing was left.
+/// Conflicts are handled like [`lib_undo`].
+pub fn lib_redo() -> LibraryResult<Option<UndoSummary>> {
+    let mut stack = undo_stack();
+    let Some(step) = stack.pop_redo() else {
+        return Ok(None);
+    };
+    match apply_step(ChangeOperation::Redo, step.changes.clone()) {
+        Ok(()) => {
+            info!(
+                "Redone {:?} of {} entries",
+                step.operation,
+                step.changes.len()
+            );
+            let summary = step.summary();
+            stack.push_undo(step);
+            Ok(Some(summary))
+        }
+        Err(e @ LibraryError::UndoConflict(_)) => Err(e),
+        Err(e) => {
+            stack.push_redo(step);
+            Err(e)
+        }
+    }
+}
+
+pub fn lib_undo_state() -> UndoState {
+    undo_stack().state()
+}
+
+/// Set every entry of `changes` from `before` to `after` in one transaction, removed entries
+/// go into the trash again if they did so first
+fn apply_step(operation: ChangeOperation, changes: Vec<EntryChange>) -> LibraryResult<()> {
+    let mut write = LibraryWrite::begin(operation)?;
+    for change in changes {
+        if write.get(&change.key)? != change.before {
+            return Err(LibraryError::UndoConflict(change.key));
+        }
+        match &change.after {
+            Some(metadata) => write.set(&change.key, metadata)?,
+            None if change.trashed => {
+                write.trash(&change.key)?;
+            }
+            None => {
+                write.remove(&change.key)?;
+            }
+        }
+    }
+    write.commit()
+}
+
+/// Entries in the trash, the latest removed first
+pub fn lib_trash_list() -> LibraryResult<Vec<TrashEntry>> {
+    Ok(trash_list(&internal_lib().begin_read()?)?)
+}
+
+/// Put a trashed entry back into the library
+pub fn lib_trash_restore(key: &str) -> LibraryResult<Metadata> {
+    let mut write = LibraryWrite::begin(ChangeOperation::TrashRestore)?;
+    let entry =
+        trash_take(&write.write, key)?.ok_or_else(|| TrashError::NotFound(key.to_string()))?;
+    write.set(key, &entry.metadata)?;
+    write.commit()?;
+    info!("Restored metadata {} from trash", key);
+    Ok(entry.metadata)
+}
+
+/// Drop a trashed entry for good, its archive in the data root is deleted as well
+/// if `delete_archive` is set and no other entry refers to it
+pub fn lib_trash_purge(key: &str, delete_archive: bool) -> LibraryResult<bool> {
+    let write = internal_lib().begin_write()?;
+    let entry = trash_take(&write, key)?;
+    if let Some(entry) = &entry {
+        let dropped = entry
+            .metadata
+            .deployments
+            .iter()
+            .map(|deployment| deployment.id.as_str())
+            .collect::<Vec<_>>();
+        manifest_release(&write, &dropped)?;
+    }
+    write.commit()?;
+    let Some(entry) = entry else {
+        return Ok(false);
+    };
+    if delete_archive {
+        purge_archive(&entry.metadata)?;
+    }
+    info!("Purged metadata {} from trash", key);
+    Ok(true)
+}
+
+/// Purge every trashed entry, returns the count purged
+pub fn lib_trash_empty(delete_archive: bool, progress: &ProgressHandle) -> LibraryResult<usize> {
+    let entries = lib_trash_list()?;
+    progress.set_total(0, entries.len() as u64);
+    for entry in &entries {
+        if progress.is_cancelled() {
+            return Err(MetadataError::Cancelled.into());
+        }
+        progress.start_file(&entry.metadata.title);
+        lib_trash_purge(&entry.metadata.id, delete_archive)?;
+        progress.finish_file();
+    }
+    progress.flush();
+    Ok(entries.len())
+}
+
+/// Purge entries kept in the trash longer than configured, returns the count purged
+pub fn lib_trash_expire() -> LibraryResult<usize> {
+    let days = config_get().get_trash_retention_days();
+    if days == 0 {
+        return Ok(0);
+    }
+    let deadline = Utc::now() - TimeDelta::days(days.into());
+    let delete_archive = config_get().get_trash_delete_archive();
+    let mut purged = 0;
+    for entry in lib_trash_list()? {
+        if entry.time_deleted < deadline {
+            lib_trash_purge(&entry.metadata.id, delete_archive)?;
+            purged += 1;
+        }
+    }
+    if purged > 0 {
+        info!("Purged {} entries expired from trash", purged);
+    }
+    Ok(purged)
+}
+
+/// Delete the archive of a purged entry if it is in the data root and unused by others
+fn purge_archive(metadata: &Metadata) -> LibraryResult<()> {
+    let Some(path) = metadata.archive_info.managed_path() else {
+        return Ok(());
+    };
+    let in_use = lib_get_all()?
+        .iter()
+        .chain(lib_trash_list()?.iter().map(|entry| &entry.metadata))
+        .any(|other| other.archive_info.managed_path().as_ref() == Some(&path));
+    if in_use {
+        info!(
+            "Archive {} is still in use, kept after purging {}",
+            path.display(),
+            metadata.id
+        );
+    } else if path.is_dir() {
+        fs::remove_dir_all(&path)?;
+    } else {
+        fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+/// Archive files in the data root no live or trashed entry refers to
+pub fn lib_orphan_scan() -> LibraryResult<Vec<OrphanArchive>> {
+    let entries = lib_get_all()?;
+    let trashed = lib_trash_list()?;
+    Ok(orphan_scan(
+        entries
+            .iter()
+            .chain(trashed.iter().map(|entry| &entry.metadata)),
+    )?)
+}
+
+/// The orphans among `paths`, scanned again so nothing adopted since is touched
+fn orphans_of(paths: &[String]) -> LibraryResult<Vec<OrphanArchive>> {
+    let mut orphans = lib_orphan_scan()?;
+    orphans.retain(|orphan| paths.contains(&orphan.path));
+    Ok(orphans)
+}
+
+/// Delete orphaned archives, returns the count deleted
+pub fn lib_orphan_delete(paths: &[String]) -> LibraryResult<usize> {
+    let orphans = orphans_of(paths)?;
+    for orphan in &orphans {
+        fs::remove_file(config_get().resolve_to_root(Path::new(&orphan.path)))?;
+        info!("Deleted orphaned archive {}", orphan.path);
+    }
+    Ok(orphans.len())
+}
+
+/// Create a stub entry for each orphaned archive, returns the ids created
+pub fn lib_orphan_adopt(paths: &[String], progress: &ProgressHandle) -> LibraryResult<Vec<String>> {
+    let orphans = orphans_of(paths)?;
+    let mut entries = Vec::with_capacity(orphans.len());
+    for orphan in &orphans {
+        if progress.is_cancelled() {
+            return Err(MetadataError::Cancelled.into());
+        }
+        entries.push(Metadata::init(orphan.stub(), progress)?);
+    }
+    let mut write = LibraryWrite::begin(ChangeOperation::Create)?;
+    for metadata in &entries {
+        write.set(&metadata.id, metadata)?;
+    }
+    write.commit()?;
+    info!("Created {} entries for orphaned archives", entries.len());
+    Ok(entries.into_iter().map(|metadata| metadata.id).collect())
+}
+
+pub fn lib_save_list(key: &str) -> LibraryResult<Vec<SaveSnapshot>> {
+    let metadata = internal_get(key)?;
+    Ok(save_list(&metadata.id)?)
+}
+
+/// Roll back the saves of an entry, restored right away into `deployment` if given
+pub fn lib_save_rollback(key: &str, snapshot: &str, deployment: Option<&str>) -> LibraryResult<()> {
+    let metadata = internal_get(key)?;
+    let root = match deployment {
+        Some(deployment) => metadata
+            .get_deployment(deployment)
+            .ok_or_else(|| MetadataError::DeploymentNotFound(deployment.to_string()))?
+            .info
+            .root()
+            .filter(|root| root.exists()),
+        None => None,
+    };
+    save_rollback(&metadata.id, snapshot, root.as_deref())?;
+    info!("Rolled back saves of {} to snapshot {}", key, snapshot);
     Ok(())
 }
 
-pub fn lib_import() -> LibraryResult<()> {
-    let import_path = config_get().get_root().join(LIB_EXPORT_FILE_NAME);
+pub fn lib_quarantine_list() -> LibraryResult<Vec<QuarantinedRecord>> {
+    let read = internal_lib().begin_read()?;
+    Ok(quarantine_list(&read)?)
+}
+
+/// Restore quarantined records which decode now, returns the count restored
+pub fn lib_quarantine_retry() -> LibraryResult<usize> {
+    let mut write = LibraryWrite::begin(ChangeOperation::Recover)?;
+    let entries = quarantine_restore(&write.write)?;
+    let restored = entries.len();
+    for mut metadata in entries {
+        // Quarantined before the migrations had a chance to upgrade it
+        metadata.upgrade_legacy_deploy();
+        write.set(&metadata.id, &metadata)?;
+    }
+    write.commit()?;
+    info!("Restored {} entries from quarantine", restored);
+    Ok(restored)
+}
+
+/// Drop a quarantined record for good
+pub fn lib_quarantine_remove(key: &str) -> LibraryResult<bool> {
+    let write = internal_lib().begin_write()?;
+    let removed = quarantine_take(&write, key)?.is_some();
+    write.commit()?;
+    Ok(removed)
+}
+
+/// Resolve the file of an export or import, `library.<ext>` in the data root if no path is given.
+/// The format is guessed from the extension if not given.
+fn transfer_target(
+    path: Option<&str>,
+    format: Option<TransferFormat>,
+) -> LibraryResult<(PathBuf, TransferFormat)> {
+    let path = match path {
+        Some(path) => config_get().resolve_to_root(Path::new(path)),
+        None => config_get().get_root().join(format!(
+            "{LIB_FILE_STEM}.{}",
+            format.unwrap_or_default().extension()
+        )),
+    };
+    let Some(format) = format.or_else(|| TransferFormat::from_path(&path)) else {
+        return Err(TransferError::UnknownFormat(path.display().to_string()).into());
+    };
+    Ok((path, format))
+}
+
+/// Export the entries selected by `filter` to `path`, see [`transfer_target`]
+pub fn lib_export(
+    path: Option<&str>,
+    format: Option<TransferFormat>,
+    filter: &ExportFilter,
+) -> LibraryResult<ExportReport> {
+    let (export_path, format) = transfer_target(path, format)?;
+    let entries = filter.apply(lib_get_all()?);
+    fs::write(&export_path, transfer_encode(&entries, format)?)?;
+    info!(
+        "Exported {} entries to {} as {:?}",
+        entries.len(),
+        export_path.display(),
+        format
+    );
+    Ok(ExportReport {
+        path: export_path.to_string_lossy().to_string(),
+        format,
+        entries: entries.len(),
+    })
+}
+
+/// Import entries from `path`, see [`transfer_target`]. Existing entries are treated
+/// according to `mode`. Nothing is written for a dry run, the report tells what would
+/// have been done.
+pub fn lib_import(
+    path: Option<&str>,
+    format: Option<TransferFormat>,
+    mode: ImportMode,
+    dry_run: bool,
+) -> LibraryResult<ImportReport> {
+    let (import_path, format) = transfer_target(path, format)?;
     if !import_path.exists() {
         return Err(LibraryError::FileSystemError(std::io::Error::new(
             std::io::ErrorKind::NotFound,
@@ -231,20 +1174,368 @@ pub fn lib_import() -> LibraryResult<()> {
     }
 
     let data = fs::read_to_string(&import_path)?;
-    let metadata_list = serde_json::from_str::<Vec<Metadata>>(&data)?;
+    let metadata_list = transfer_decode(&data, format)?;
 
-    let write = internal_lib().begin_write()?;
+    let mut report = ImportReport::new(mode, dry_run);
+    let mut write = LibraryWrite::begin(ChangeOperation::Import)?;
+    for mut metadata in metadata_list {
+        metadata.upgrade_legacy_deploy();
+        let local = write.get(&metadata.id)?;
+        let item = ImportItem {
+            id: metadata.id.clone(),
+            title: metadata.title.clone(),
+            fields: Vec::new(),
+        };
+        if let Some(metadata) = report.record(item, import_resolve(local.as_ref(), metadata, mode)?)
+        {
+            write.set(&metadata.id, &metadata)?;
+        }
+    }
+    // Writing within the transaction keeps repeated ids in the file consistent for a dry run
+    if dry_run {
+        write.abort()?;
+    } else {
+        write.commit()?;
+        info!("Library imported from {}", import_path.display());
+    }
+    info!(
+        "Import {:?}: {} added, {} updated, {} unchanged, {} conflicts",
+        mode,
+        report.added.len(),
+        report.updated.len(),
+        report.unchanged.len(),
+        report.conflicts.len()
+    );
+    Ok(report)
+}
+
+/// Export the entries selected by `filter` into a bundle with their archives, at `path`
+/// or `library.bundle.tar` in the data root
+pub fn lib_bundle_export(
+    path: Option<&str>,
+    filter: &ExportFilter,
+    progress: &ProgressHandle,
+) -> LibraryResult<BundleExportReport> {
+    let bundle_path = match path {
+        Some(path) => config_get().resolve_to_root(Path::new(path)),
+        None => config_get()
+            .get_root()
+            .join(format!("{LIB_FILE_STEM}.{LIB_BUNDLE_INFIX}.{BUNDLE_EXT}")),
+    };
+    let entries = filter.apply(lib_get_all()?);
+    Ok(bundle_write(&bundle_path, entries, progress)?)
+}
+
+/// Import the entries of the bundle at `path` as [`lib_import`] does. Their archives and covers
+/// are placed under the data root and the entries rewritten to refer to them, only the files of
+/// entries which are written or whose files are missing are extracted.
+pub fn lib_bundle_import(
+    path: &str,
+    mode: ImportMode,
+    dry_run: bool,
+    progress: &ProgressHandle,
+) -> LibraryResult<BundleImportReport> {
+    let bundle_path = config_get().resolve_to_root(Path::new(path));
+    let manifest = bundle_manifest(&bundle_path)?;
+
+    /// Place of `file` under `dir`, decided once for every entry referring to it
+    fn target_of(
+        targets: &mut HashMap<String, BundleTarget>,
+        taken: &mut HashSet<String>,
+        file: &BundleFile,
+        dir: &str,
+    ) -> LibraryResult<String> {
+        if !targets.contains_key(&file.path) {
+            let target = bundle_target(file, dir, taken)?;
+            targets.insert(file.path.clone(), target);
+        }
+        Ok(targets[&file.path].path.clone())
+    }
+
+    let mut taken = HashSet::new();
+    let mut targets: HashMap<String, BundleTarget> = HashMap::new();
+    // Each entry with the files it refers to once imported, if those are the ones in the bundle
+    let mut planned = Vec::with_capacity(manifest.entries.len());
+    for entry in manifest.entries {
+        let mut metadata = entry.metadata;
+        if let Some(file) = &entry.file {
+            let target = target_of(&mut targets, &mut taken, file, DIR_ARCHIVE)?;
+            if let ArchiveInfo::ArchiveFile { path, .. } = &mut metadata.archive_info {
+                *path = target;
+            }
+        }
+        match &entry.cover {
+            Some(cover) => {
+                metadata.cover = Some(target_of(&mut targets, &mut taken, cover, DIR_COVER)?)
+            }
+            // Covers of other machines are only usable if bundled
+            None => metadata.cover = None,
+        }
+        let local = match internal_get(&metadata.id) {
+            Ok(local) => Some(local),
+            Err(LibraryError::NotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        let item = ImportItem {
+            id: metadata.id.clone(),
+            title: metadata.title.clone(),
+            fields: Vec::new(),
+        };
+        let resolution = import_resolve(local.as_ref(), metadata, mode)?;
+        // Whether the entry written refers to `file` where it is placed
+        let refers = |file: &BundleFile, path: fn(&Metadata) -> Option<&str>| match &resolution {
+            ImportResolution::Add(metadata) | ImportResolution::Update(metadata, _) => {
+                path(metadata) == Some(targets[&file.path].path.as_str())
+            }
+            ImportResolution::Unchanged => true,
+            ImportResolution::Conflict(_) => false,
+        };
+        let files = [
+            entry.file.filter(|file| {
+                refers(file, |metadata| match &metadata.archive_info {
+                    ArchiveInfo::ArchiveFile { path, .. } => Some(path),
+                    _ => None,
+                })
+            }),
+            entry
+                .cover
+                .filter(|cover| refers(cover, |metadata| metadata.cover.as_deref())),
+        ];
+        planned.push((item, files, resolution));
+    }
+
+    let mut files = HashMap::new();
+    let mut reused = Vec::new();
+    for file in planned
+        .iter()
+        .flat_map(|(_, files, _)| files.iter().flatten())
     {
-        let mut table = write.open_table(LIB_TABLE)?;
-        for metadata in metadata_list {
-            let raw = bson::to_vec(&metadata)?;
-            table.insert(metadata.id.as_str(), raw)?;
+        let target = &targets[&file.path];
+        if target.exists {
+            reused.push(target.path.clone());
+        } else {
+            files.insert(file.path.clone(), (file.clone(), target.clone()));
         }
     }
+    reused.sort();
+    reused.dedup();
+    let damaged = if dry_run {
+        HashSet::new()
+    } else {
+        bundle_extract(&bundle_path, &files, progress)?
+    };
+    let mut extracted = files
+        .iter()
+        .filter(|(name, _)| !damaged.contains(*name))
+        .map(|(_, (_, target))| target.path.clone())
+        .collect::<Vec<_>>();
+    extracted.sort();
+
+    let mut report = BundleImportReport {
+        import: ImportReport::new(mode, dry_run),
+        extracted,
+        reused,
+        damaged: Vec::new(),
+    };
+    let mut write = LibraryWrite::begin(ChangeOperation::Import)?;
+    for (item, files, resolution) in planned {
+        if files
+            .iter()
+            .flatten()
+            .any(|file| damaged.contains(&file.path))
+        {
+            report.damaged.push(item);
+        } else if let Some(metadata) = report.import.record(item, resolution) {
+            write.set(&metadata.id, &metadata)?;
+        }
+    }
+    if dry_run {
+        write.abort()?;
+    } else {
+        write.commit()?;
+        info!("Library imported from bundle {}", bundle_path.display());
+    }
+    info!(
+        "Bundle import {:?}: {} added, {} updated, {} archives extracted, {} damaged",
+        mode,
+        report.import.added.len(),
+        report.import.updated.len(),
+        report.extracted.len(),
+        report.damaged.len()
+    );
+    Ok(report)
+}
+
+/// Render the entries selected by `filter` into a static HTML catalog at the directory `path`
+/// or `library.catalog` in the data root, see [`catalog_write`]
+pub fn lib_catalog_export(
+    path: Option<&str>,
+    filter: &ExportFilter,
+    labels: &CatalogLabels,
+    progress: &ProgressHandle,
+) -> LibraryResult<CatalogReport> {
+    let catalog_path = match path {
+        Some(path) => config_get().resolve_to_root(Path::new(path)),
+        None => config_get()
+            .get_root()
+            .join(format!("{LIB_FILE_STEM}.{LIB_CATALOG_INFIX}")),
+    };
+    let entries = filter.apply(lib_get_all()?);
+    Ok(catalog_write(&catalog_path, entries, labels, progress)?)
+}
+
+/// Header row of a CSV file to map to entry fields
+pub fn lib_external_columns(path: &str) -> LibraryResult<Vec<String>> {
+    let path = config_get().resolve_to_root(Path::new(path));
+    Ok(external_csv_columns(&path)?)
+}
+
+/// Read the library exported by another tool, with the entries and earlier records of the
+/// same file each record is likely a duplicate of
+pub fn lib_external_preview(
+    path: &str,
+    source: &ExternalSource,
+    progress: &ProgressHandle,
+) -> LibraryResult<ExternalPreview> {
+    let path = config_get().resolve_to_root(Path::new(path));
+    let (records, skipped) = external_read(&path, source)?;
+    let entries = lib_get_all()?;
+    let mut checked: Vec<Metadata> = Vec::with_capacity(records.len());
+    let mut candidates = Vec::with_capacity(records.len());
+    progress.set_total(0, records.len() as u64);
+    for (index, opt) in records.into_iter().enumerate() {
+        if progress.is_cancelled() {
+            return Err(MetadataError::Cancelled.into());
+        }
+        let candidate = duplicate_candidate(&opt)?;
+        progress.start_file(&candidate.title);
+        let duplicates = check_duplicates(&candidate, &entries, &checked);
+        checked.push(candidate);
+        candidates.push(ExternalCandidate {
+            index,
+            opt,
+            duplicates,
+        });
+        progress.finish_file();
+    }
+    progress.flush();
+    Ok(ExternalPreview {
+        candidates,
+        skipped,
+    })
+}
+
+/// Create entries for the records at `selected` of the library exported by another tool,
+/// as listed by [`lib_external_preview`].
+///
+/// The records are checked for duplicates again against the library as it is now, those
+/// looking like one are left out unless listed in `confirmed`.
+pub fn lib_external_import(
+    path: &str,
+    source: &ExternalSource,
+    selected: &[usize],
+    confirmed: &[usize],
+    progress: &ProgressHandle,
+) -> LibraryResult<ExternalImportReport> {
+    let resolved = config_get().resolve_to_root(Path::new(path));
+    let (records, _) = external_read(&resolved, source)?;
+    let library = lib_get_all()?;
+    let mut checked = Vec::with_capacity(selected.len());
+    let mut entries = Vec::with_capacity(selected.len());
+    let mut duplicates = Vec::new();
+    progress.set_total(0, selected.len() as u64);
+    for &index in selected {
+        let Some(opt) = records.get(index) else {
+            continue;
+        };
+        if progress.is_cancelled() {
+            return Err(MetadataError::Cancelled.into());
+        }
+        progress.start_file(opt.title.clone().unwrap_or_default());
+        let candidate = duplicate_candidate(opt)?;
+        let found = check_duplicates(&candidate, &library, &checked);
+        checked.push(candidate);
+        if found.is_empty() || confirmed.contains(&index) {
+            entries.push(Metadata::init(opt.clone(), progress)?);
+        } else {
+            duplicates.push(ExternalCandidate {
+                index,
+                opt: opt.clone(),
+                duplicates: found,
+            });
+        }
+        progress.finish_file();
+    }
+    let mut write = LibraryWrite::begin(ChangeOperation::Import)?;
+    for metadata in &entries {
+        write.set(&metadata.id, metadata)?;
+    }
     write.commit()?;
+    info!(
+        "Imported {} entries from {}, {} left out as duplicates",
+        entries.len(),
+        path,
+        duplicates.len()
+    );
+    Ok(ExternalImportReport {
+        created: entries.into_iter().map(|metadata| metadata.id).collect(),
+        duplicates,
+    })
+}
 
-    info!("Library imported from {}", import_path.display());
-    Ok(())
+pub fn lib_backup_list() -> LibraryResult<Vec<BackupInfo>> {
+    Ok(backup_list()?)
+}
+
+/// Take a named snapshot of the library, returns the id of the backup
+pub fn lib_backup_snapshot(name: &str) -> LibraryResult<String> {
+    let bin_path = config_get().get_root().join(LIB_FILE_NAME);
+    Ok(backup_snapshot(internal_lib(), &bin_path, name)?)
+}
+
+/// Replace the library with the content of a backup. The backup is validated first and
+/// the current library is kept as a snapshot, the restore itself can be undone.
+pub fn lib_backup_restore(id: &str) -> LibraryResult<BackupRestoreReport> {
+    let content = backup_load(id)?;
+    let snapshot = lib_backup_snapshot(BACKUP_RESTORE_SNAPSHOT)?;
+
+    let mut write = LibraryWrite::begin(ChangeOperation::BackupRestore)?;
+    let keys = {
+        let table = write.write.open_table(LIB_TABLE)?;
+        let mut keys = Vec::new();
+        for entry in table.iter()? {
+            keys.push(entry?.0.value().to_string());
+        }
+        keys
+    };
+    let restored = content
+        .entries
+        .iter()
+        .map(|metadata| metadata.id.as_str())
+        .collect::<HashSet<_>>();
+    for key in keys.iter().filter(|key| !restored.contains(key.as_str())) {
+        write.remove(key)?;
+    }
+    for metadata in &content.entries {
+        write.set(&metadata.id, metadata)?;
+    }
+    write.commit()?;
+
+    info!(
+        "Library restored from backup {} with {} entries, {} undecodable skipped",
+        id,
+        content.entries.len(),
+        content.undecodable
+    );
+    Ok(BackupRestoreReport {
+        entries: content.entries.len(),
+        undecodable: content.undecodable,
+        snapshot,
+    })
+}
+
+pub fn lib_backup_delete(id: &str) -> LibraryResult<()> {
+    Ok(backup_delete(id)?)
 }
 
 type LibraryResult<T> = Result<T, LibraryError>;
@@ -257,11 +1548,56 @@ pub enum LibraryError {
     #[error("Failed to deploy metadata due to missing or invalid info")]
     DeployError,
 
+    #[error(
+        "The job was queued before deployments had ids and metadata {0} has {1} deployments, \
+         deploy it off from the entry instead"
+    )]
+    DeploymentUnnamed(String, usize),
+
+    #[error("Cannot merge metadata {0} into itself")]
+    MergeError(String),
+
+    #[error("Metadata {0} was changed since, cannot undo or redo")]
+    UndoConflict(String),
+
     #[error("Metadata internal error: {0}")]
     MetadataError(#[from] MetadataError),
 
+    #[error("Library index error: {0}")]
+    IndexError(#[from] IndexError),
+
+    #[error("Library trash error: {0}")]
+    TrashError(#[from] TrashError),
+
+    #[error("Deploy manifest error: {0}")]
+    ManifestError(#[from] ManifestError),
+
+    #[error("Library history error: {0}")]
+    HistoryError(#[from] HistoryError),
+
+    #[error("Library transfer error: {0}")]
+    TransferError(#[from] TransferError),
+
+    #[error("External library error: {0}")]
+    ExternalError(#[from] ExternalError),
+
+    #[error("Library bundle error: {0}")]
+    BundleError(#[from] BundleError),
+
+    #[error("Library catalog error: {0}")]
+    CatalogError(#[from] CatalogError),
+
+    #[error("Library backup error: {0}")]
+    BackupError(#[from] BackupError),
+
+    #[error("Library schema error: {0}")]
+    SchemaError(#[from] SchemaError),
+
+    #[error("Save snapshot error: {0}")]
+    SaveError(#[from] SaveError),
+
     #[error("Database transaction error: {0}")]
-    TransactionError(#[from] redb::TransactionError),
+    TransactionError(Box<redb::TransactionError>),
 
     #[error("Database table error: {0}")]
     TableError(#[from] redb::TableError),
@@ -284,3 +1620,174 @@ pub enum LibraryError {
     #[error("File system error: {0}")]
     FileSystemError(#[from] std::io::Error),
 }
+
+impl From<redb::TransactionError> for LibraryError {
+    fn from(e: redb::TransactionError) -> Self {
+        LibraryError::TransactionError(Box::new(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::external::CsvMapping;
+    use crate::data::metadata::MetadataBuilder;
+    use crate::util::config::config_init_test;
+    use std::sync::{Mutex, MutexGuard};
+    use tempfile::tempdir;
+
+    /// The library and its undo stack are shared by the whole process
+    static LIBRARY: Mutex<()> = Mutex::new(());
+
+    fn library() -> MutexGuard<'static, ()> {
+        let guard = LIBRARY.lock().unwrap_or_else(|e| e.into_inner());
+        config_init_test();
+        let mut stack = undo_stack();
+        while stack.pop_undo().is_some() {}
+        while stack.pop_redo().is_some() {}
+        guard
+    }
+
+    /// A new entry titled `title`, then renamed to each of `renames` in turn
+    fn entry(title: &str, renames: &[&str]) -> String {
+        let metadata = MetadataBuilder::default().title(title).build().unwrap();
+        let key = metadata.id.clone();
+        internal_set(&key, metadata, ChangeOperation::Create).unwrap();
+        for rename in renames {
+            internal_apply(&key, ChangeOperation::Patch, |metadata| {
+                metadata.title = rename.to_string()
+            })
+            .unwrap();
+        }
+        key
+    }
+
+    fn title(key: &str) -> Option<String> {
+        match lib_get(key) {
+            Ok(metadata) => Some(metadata.title),
+            Err(LibraryError::NotFound(_)) => None,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_round_trip() {
+        let _library = library();
+        let key = entry("First", &["Second"]);
+
+        let undone = lib_undo().unwrap().unwrap();
+        assert_eq!(undone.operation, ChangeOperation::Patch);
+        assert_eq!(title(&key).as_deref(), Some("First"));
+        assert_eq!(
+            lib_undo().unwrap().unwrap().operation,
+            ChangeOperation::Create
+        );
+        assert_eq!(title(&key), None);
+        assert!(lib_undo().unwrap().is_none());
+
+        assert_eq!(
+            lib_redo().unwrap().unwrap().operation,
+            ChangeOperation::Create
+        );
+        assert_eq!(title(&key).as_deref(), Some("First"));
+        lib_redo().unwrap().unwrap();
+        assert_eq!(title(&key).as_deref(), Some("Second"));
+        assert!(lib_redo().unwrap().is_none());
+
+        let state = lib_undo_state();
+        assert_eq!((state.undo_count, state.redo_count), (2, 0));
+        assert!(lib_history(&key).unwrap().len() >= 4);
+    }
+
+    #[test]
+    fn changed_entries_conflict() {
+        let _library = library();
+        let key = entry("First", &["Second"]);
+        lib_undo().unwrap();
+
+        // Verifications are not recorded for undo, the undone rename is still there to redo
+        internal_apply(&key, ChangeOperation::Verify, |metadata| {
+            metadata.archive_hash = Some(String::from("hash"))
+        })
+        .unwrap();
+        assert_eq!(lib_undo_state().redo_count, 1);
+
+        assert!(matches!(lib_redo(), Err(LibraryError::UndoConflict(k)) if k == key));
+        assert_eq!(lib_undo_state().redo_count, 0);
+        assert_eq!(title(&key).as_deref(), Some("First"));
+
+        assert!(matches!(lib_undo(), Err(LibraryError::UndoConflict(k)) if k == key));
+        assert_eq!(lib_undo_state().undo_count, 0);
+        assert_eq!(title(&key).as_deref(), Some("First"));
+    }
+
+    fn trashed(key: &str) -> bool {
+        lib_trash_list()
+            .unwrap()
+            .iter()
+            .any(|entry| entry.metadata.id == key)
+    }
+
+    #[test]
+    fn redone_clear_trashes_again() {
+        let _library = library();
+        let keys = [entry("First", &[]), entry("Second", &[])];
+        lib_clear().unwrap();
+        assert!(keys.iter().all(|key| title(key).is_none() && trashed(key)));
+
+        assert_eq!(
+            lib_undo().unwrap().unwrap().operation,
+            ChangeOperation::Clear
+        );
+        assert!(keys.iter().all(|key| title(key).is_some() && !trashed(key)));
+
+        lib_redo().unwrap().unwrap();
+        assert!(keys.iter().all(|key| title(key).is_none() && trashed(key)));
+    }
+
+    #[test]
+    fn imports_check_the_library_again() {
+        let _library = library();
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let (known, new) = (format!("Celeste {id}"), format!("Hollow Knight {id}"));
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("games.csv");
+        fs::write(&path, format!("Name\n{known}\n{new}\n{new}\n")).unwrap();
+        let path = path.to_string_lossy().to_string();
+        let source = ExternalSource::Csv {
+            mapping: Box::new(CsvMapping {
+                title: String::from("Name"),
+                ..Default::default()
+            }),
+        };
+        let progress = ProgressHandle::silent();
+        let preview = lib_external_preview(&path, &source, &progress).unwrap();
+        assert!(preview.candidates[0].duplicates.is_empty());
+
+        // Added after the preview, as by hand
+        let opt = MetadataOptional {
+            title: Some(known.clone()),
+            ..Default::default()
+        };
+        let found = lib_duplicates_of(&opt).unwrap();
+        assert!(found.is_empty());
+        let key = entry(&known, &[]);
+        let found = lib_duplicates_of(&opt).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id.as_deref(), Some(key.as_str()));
+
+        let report = lib_external_import(&path, &source, &[0, 1, 2], &[], &progress).unwrap();
+        assert_eq!(report.created.len(), 1);
+        assert_eq!(title(&report.created[0]), Some(new.clone()));
+        let left_out = report
+            .duplicates
+            .iter()
+            .map(|candidate| (candidate.index, candidate.duplicates[0].id.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(left_out, [(0, Some(key)), (2, None)]);
+
+        let report = lib_external_import(&path, &source, &[0], &[0], &progress).unwrap();
+        assert_eq!(report.created.len(), 1);
+        assert!(report.duplicates.is_empty());
+    }
+}
@@ -0,0 +1,439 @@
+use crate::util::hash::{hash_file, hash_reader};
+use crate::util::path_ext::PathExt;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use redb::{ReadTransaction, TableDefinition, WriteTransaction};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+use thiserror::Error;
+use walkdir::WalkDir;
+
+/// Manifests of deployments keyed by deployment id, kept apart since they grow with the
+/// deployed tree while entries are read all the time
+pub(crate) const MANIFEST_TABLE: TableDefinition<&str, Vec<u8>> = TableDefinition::new("MANIFEST");
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub enum ManifestEntry {
+    Directory {
+        path: String,
+    },
+    File {
+        path: String,
+        size: u64,
+        hash: String,
+    },
+}
+
+impl ManifestEntry {
+    /// Path relative to the deploy root, using `/` as separator
+    pub fn path(&self) -> &str {
+        match self {
+            ManifestEntry::Directory { path } | ManifestEntry::File { path, .. } => path,
+        }
+    }
+}
+
+/// Every file and directory created by a deployment, relative to the deploy root
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Default)]
+pub struct DeployManifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// Outcome of removing a deployment, entries listed here are left in place
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DeployOffReport {
+    pub removed: u64,
+
+    /// Files changed since deploy
+    pub modified: Vec<String>,
+
+    /// Files created inside deployed directories since deploy
+    pub added: Vec<String>,
+
+    /// Entries which no longer exist
+    pub missing: Vec<String>,
+}
+
+/// Difference between a deployed tree and what was deployed
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DriftReport {
+    /// Entries which no longer exist
+    pub missing: Vec<String>,
+
+    /// Files whose size or content differ
+    pub modified: Vec<String>,
+
+    /// Files inside deployed directories which were not deployed
+    pub extra: Vec<String>,
+}
+
+impl DriftReport {
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.modified.is_empty() && self.extra.is_empty()
+    }
+}
+
+impl DeployManifest {
+    /// Record everything under `root`, which is expected to only contain the deployed content
+    pub fn collect(root: &Path) -> std::io::Result<Self> {
+        Self::collect_dir(root, root)
+    }
+
+    /// Record `dir` itself and everything under it, relative to `root`
+    pub fn collect_dir(root: &Path, dir: &Path) -> std::io::Result<Self> {
+        let min_depth = if dir == root { 1 } else { 0 };
+        let mut entries = Vec::new();
+        for entry in WalkDir::new(dir).min_depth(min_depth).sort_by_file_name() {
+            let entry = entry?;
+            let path = entry.path().relative_name(root);
+            if entry.file_type().is_dir() {
+                entries.push(ManifestEntry::Directory { path });
+            } else {
+                entries.push(Self::file_entry(entry.path(), path)?);
+            }
+        }
+        Ok(Self { entries })
+    }
+
+    /// Record the given files only, e.g. a single file deployed into a shared directory
+    pub fn collect_files(root: &Path, files: &[&Path]) -> std::io::Result<Self> {
+        let entries = files
+            .iter()
+            .map(|file| Self::file_entry(file, file.relative_name(root)))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        Ok(Self { entries })
+    }
+
+    fn file_entry(file: &Path, path: String) -> std::io::Result<ManifestEntry> {
+        let meta = file.symlink_metadata()?;
+        Ok(ManifestEntry::File {
+            path,
+            size: meta.len(),
+            hash: Self::file_hash(file, &meta)?,
+        })
+    }
+
+    /// Links are recorded by their target instead of the content they point to
+    fn file_hash(file: &Path, meta: &fs::Metadata) -> std::io::Result<String> {
+        if meta.is_symlink() {
+            hash_reader(fs::read_link(file)?.to_string_lossy().as_bytes())
+        } else {
+            hash_file(file)
+        }
+    }
+
+    /// Remove the recorded entries from `root`, which were deployed at `deployed`.
+    ///
+    /// Files changed since deploy are kept, so are directories still containing anything
+    /// not removed, links are never followed. Files in `saved` have been backed up elsewhere,
+    /// they are removed even if changed as long as they were deployed or written since.
+    pub fn remove_from(
+        &self,
+        root: &Path,
+        saved: &BTreeSet<String>,
+        deployed: DateTime<Utc>,
+    ) -> std::io::Result<DeployOffReport> {
+        let mut report = DeployOffReport::default();
+        let recorded = self
+            .entries
+            .iter()
+            .map(|entry| entry.path())
+            .collect::<HashSet<_>>();
+
+        for path in saved {
+            let file = root.join(path);
+            let Ok(meta) = file.symlink_metadata() else {
+                continue;
+            };
+            // Files matching the save patterns may have been there before the deployment
+            let written = meta
+                .modified()
+                .is_ok_and(|time| time >= SystemTime::from(deployed));
+            if !meta.is_file() || !(recorded.contains(path.as_str()) || written) {
+                continue;
+            }
+            fs::remove_file(&file)?;
+            report.removed += 1;
+            // Directories created for saves after deploy are not recorded, prune them if empty
+            for dir in file.ancestors().skip(1) {
+                if dir == root
+                    || recorded.contains(dir.relative_name(root).as_str())
+                    || !dir.is_dir_empty()
+                {
+                    break;
+                }
+                fs::remove_dir(dir)?;
+            }
+        }
+
+        report.added = self.unrecorded(root, &recorded)?;
+
+        for entry in &self.entries {
+            let ManifestEntry::File { path, size, hash } = entry else {
+                continue;
+            };
+            if saved.contains(path) {
+                continue;
+            }
+            let file = root.join(path);
+            let Ok(meta) = file.symlink_metadata() else {
+                report.missing.push(path.clone());
+                continue;
+            };
+            if Self::file_changed(&file, &meta, *size, hash)? {
+                warn!("Deployed file modified, keeping it: {}", file.display());
+                report.modified.push(path.clone());
+                continue;
+            }
+            fs::remove_file(&file)?;
+            report.removed += 1;
+        }
+
+        // Deepest directories first, so parents could be empty by the time they are checked
+        let mut dirs = self
+            .entries
+            .iter()
+            .filter(|entry| matches!(entry, ManifestEntry::Directory { .. }))
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>();
+        dirs.sort_by_key(|path| std::cmp::Reverse(path.matches('/').count()));
+        for path in dirs {
+            let dir = root.join(path);
+            if !dir.symlink_metadata().is_ok_and(|meta| meta.is_dir()) {
+                report.missing.push(path.to_string());
+            } else if dir.is_dir_empty() {
+                fs::remove_dir(&dir)?;
+                report.removed += 1;
+            }
+        }
+
+        info!(
+            "Removed {} deployed entries from {}, {} modified, {} added, {} missing",
+            report.removed,
+            root.display(),
+            report.modified.len(),
+            report.added.len(),
+            report.missing.len()
+        );
+        Ok(report)
+    }
+
+    /// Compare the recorded entries against what is currently under `root`, nothing is changed
+    pub fn verify(&self, root: &Path) -> std::io::Result<DriftReport> {
+        let mut report = DriftReport::default();
+        let recorded = self
+            .entries
+            .iter()
+            .map(|entry| entry.path())
+            .collect::<HashSet<_>>();
+
+        for entry in &self.entries {
+            let file = root.join(entry.path());
+            let Ok(meta) = file.symlink_metadata() else {
+                report.missing.push(entry.path().to_string());
+                continue;
+            };
+            let changed = match entry {
+                ManifestEntry::Directory { .. } => !meta.is_dir(),
+                ManifestEntry::File { size, hash, .. } => {
+                    Self::file_changed(&file, &meta, *size, hash)?
+                }
+            };
+            if changed {
+                report.modified.push(entry.path().to_string());
+            }
+        }
+        report.extra = self.unrecorded(root, &recorded)?;
+
+        info!(
+            "Verified deployment at {}, {} missing, {} modified, {} extra",
+            root.display(),
+            report.missing.len(),
+            report.modified.len(),
+            report.extra.len()
+        );
+        Ok(report)
+    }
+
+    fn file_changed(
+        file: &Path,
+        meta: &fs::Metadata,
+        size: u64,
+        hash: &str,
+    ) -> std::io::Result<bool> {
+        Ok(meta.is_dir() || meta.len() != size || Self::file_hash(file, meta)? != hash)
+    }
+
+    /// Files inside recorded directories which are not recorded themselves
+    fn unrecorded(&self, root: &Path, recorded: &HashSet<&str>) -> std::io::Result<Vec<String>> {
+        // Nested directories are walked again, collect into a set to report each file once
+        let mut found = BTreeSet::new();
+        for entry in &self.entries {
+            let ManifestEntry::Directory { path } = entry else {
+                continue;
+            };
+            let dir = root.join(path);
+            if !dir.symlink_metadata().is_ok_and(|meta| meta.is_dir()) {
+                continue;
+            }
+            for item in WalkDir::new(&dir).min_depth(1) {
+                let item = item?;
+                let name = item.path().relative_name(root);
+                if !item.file_type().is_dir() && !recorded.contains(name.as_str()) {
+                    found.insert(name);
+                }
+            }
+        }
+        Ok(found.into_iter().collect())
+    }
+}
+
+/// Create the manifest table if missing
+pub(crate) fn manifest_init(write: &WriteTransaction) -> Result<(), redb::TableError> {
+    write.open_table(MANIFEST_TABLE)?;
+    Ok(())
+}
+
+pub(crate) fn manifest_insert(
+    write: &WriteTransaction,
+    deployment: &str,
+    manifest: &DeployManifest,
+) -> ManifestResult<()> {
+    let mut table = write.open_table(MANIFEST_TABLE)?;
+    table.insert(deployment, bson::to_vec(manifest)?)?;
+    Ok(())
+}
+
+/// Manifest of a deployment, `None` if none was recorded
+pub(crate) fn manifest_get(
+    read: &ReadTransaction,
+    deployment: &str,
+) -> ManifestResult<Option<DeployManifest>> {
+    let table = read.open_table(MANIFEST_TABLE)?;
+    let raw = table.get(deployment)?.map(|raw| raw.value());
+    Ok(raw.map(|raw| bson::from_slice(&raw)).transpose()?)
+}
+
+pub(crate) fn manifest_remove(write: &WriteTransaction, deployment: &str) -> ManifestResult<()> {
+    let mut table = write.open_table(MANIFEST_TABLE)?;
+    table.remove(deployment)?;
+    Ok(())
+}
+
+pub(crate) type ManifestResult<T> = Result<T, ManifestError>;
+
+#[derive(Debug, Error)]
+pub enum ManifestError {
+    #[error("Manifest table error: {0}")]
+    TableError(#[from] redb::TableError),
+
+    #[error("Manifest storage error: {0}")]
+    StorageError(#[from] redb::StorageError),
+
+    #[error("BSON serialization error: {0}")]
+    SerializationError(#[from] bson::ser::Error),
+
+    #[error("BSON deserialization error: {0}")]
+    DeserializationError(#[from] bson::de::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+    use redb::Database;
+    use tempfile::tempdir;
+
+    /// A deployed tree, `a.txt`, `save/slot.sav` and `sub/b.txt`
+    fn deployed(root: &Path) -> DeployManifest {
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir_all(root.join("save")).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("save/slot.sav"), "slot").unwrap();
+        fs::write(root.join("sub/b.txt"), "b").unwrap();
+        DeployManifest::collect(root).unwrap()
+    }
+
+    #[test]
+    fn verify_reports_modified_added_and_missing() {
+        let dir = tempdir().unwrap();
+        let manifest = deployed(dir.path());
+        assert!(manifest.verify(dir.path()).unwrap().is_clean());
+
+        fs::write(dir.path().join("a.txt"), "changed").unwrap();
+        fs::write(dir.path().join("sub/c.txt"), "c").unwrap();
+        fs::remove_file(dir.path().join("sub/b.txt")).unwrap();
+        let report = manifest.verify(dir.path()).unwrap();
+        assert_eq!(report.modified, ["a.txt"]);
+        assert_eq!(report.extra, ["sub/c.txt"]);
+        assert_eq!(report.missing, ["sub/b.txt"]);
+    }
+
+    #[test]
+    fn remove_from_keeps_modified_and_added_files() {
+        let dir = tempdir().unwrap();
+        let manifest = deployed(dir.path());
+        fs::write(dir.path().join("a.txt"), "changed").unwrap();
+        fs::write(dir.path().join("sub/c.txt"), "c").unwrap();
+
+        let report = manifest
+            .remove_from(dir.path(), &BTreeSet::new(), Utc::now())
+            .unwrap();
+        assert_eq!(report.modified, ["a.txt"]);
+        assert_eq!(report.added, ["sub/c.txt"]);
+        assert!(dir.path().join("a.txt").exists());
+        assert!(dir.path().join("sub/c.txt").exists());
+        assert!(!dir.path().join("sub/b.txt").exists());
+        assert!(!dir.path().join("save").exists());
+    }
+
+    #[test]
+    fn remove_from_only_removes_saves_deployed_or_written_since() {
+        let dir = tempdir().unwrap();
+        let manifest = deployed(dir.path());
+        fs::write(dir.path().join("save/slot.sav"), "progress").unwrap();
+        fs::write(dir.path().join("save/other.sav"), "other").unwrap();
+        let saved = ["save/other.sav", "save/slot.sav"]
+            .map(String::from)
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+
+        // Both saves predate a deployment made later, only the deployed one goes
+        let later = Utc::now() + TimeDelta::hours(1);
+        let report = manifest.remove_from(dir.path(), &saved, later).unwrap();
+        assert!(!dir.path().join("save/slot.sav").exists());
+        assert!(dir.path().join("save/other.sav").exists());
+        assert_eq!(report.added, ["save/other.sav"]);
+        assert!(report.modified.is_empty());
+
+        let earlier = Utc::now() - TimeDelta::hours(1);
+        manifest.remove_from(dir.path(), &saved, earlier).unwrap();
+        assert!(!dir.path().join("save").exists());
+    }
+
+    #[test]
+    fn manifests_are_stored_by_deployment() {
+        let dir = tempdir().unwrap();
+        let manifest = deployed(&dir.path().join("deploy"));
+        let db = Database::create(dir.path().join("library.bin")).unwrap();
+
+        let write = db.begin_write().unwrap();
+        manifest_init(&write).unwrap();
+        manifest_insert(&write, "deployment", &manifest).unwrap();
+        write.commit().unwrap();
+        let stored = manifest_get(&db.begin_read().unwrap(), "deployment").unwrap();
+        assert_eq!(stored, Some(manifest));
+
+        let write = db.begin_write().unwrap();
+        manifest_remove(&write, "deployment").unwrap();
+        write.commit().unwrap();
+        assert!(
+            manifest_get(&db.begin_read().unwrap(), "deployment")
+                .unwrap()
+                .is_none()
+        );
+    }
+}
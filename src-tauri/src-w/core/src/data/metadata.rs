@@ -1,17 +1,20 @@
 use crate::Whether::{That, This};
-use crate::util::compress::{compress, decompress};
+use crate::data::manifest::{DeployManifest, DeployOffReport, DriftReport, ManifestEntry};
+use crate::data::save::{SaveError, save_latest, save_restore, save_snapshot};
+use crate::util::compress::{ArchiveFormat, CompressError, compress, decompress, test};
 use crate::util::config::config_get;
+use crate::util::hash::{hash_file, hash_path, hash_reader};
 use crate::util::path_ext::PathExt;
-use crate::{DIR_ARCHIVE, Whether};
+use crate::util::progress::ProgressHandle;
+use crate::{DIR_ARCHIVE, DIR_STAGING, Whether};
 use chrono::{DateTime, Utc};
 use derive_builder::Builder;
-use fs_extra::dir;
-use fs_extra::dir::CopyOptions;
 use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use std::ffi::OsStr;
-use std::fs;
 use std::path::{Path, PathBuf};
+use std::{fs, io};
 use thiserror::Error;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Default)]
@@ -30,7 +33,39 @@ pub enum DistributionPlatform {
     },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Default)]
+impl DistributionPlatform {
+    /// Normalized `Platform:id` key, `None` without an id. RJ codes are case-insensitive.
+    pub fn index_key(&self) -> Option<String> {
+        match self {
+            DistributionPlatform::Steam { id } if !id.trim().is_empty() => {
+                Some(format!("Steam:{}", id.trim()))
+            }
+            DistributionPlatform::DLSite { id } if !id.trim().is_empty() => {
+                Some(format!("DLSite:{}", id.trim().to_uppercase()))
+            }
+            DistributionPlatform::Other { name, id: Some(id) } if !id.trim().is_empty() => {
+                Some(format!("Other:{}:{}", name.trim(), id.trim()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Store page of the work, `None` for platforms without a known store
+    pub fn store_url(&self) -> Option<String> {
+        match self {
+            DistributionPlatform::Steam { id } if !id.trim().is_empty() => {
+                Some(format!("https://store.steampowered.com/app/{}/", id.trim()))
+            }
+            DistributionPlatform::DLSite { id } if !id.trim().is_empty() => Some(format!(
+                "https://www.dlsite.com/maniax/work/=/product_id/{}.html",
+                id.trim().to_uppercase()
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash, Default)]
 pub enum ContentType {
     #[default]
     Other,
@@ -50,6 +85,8 @@ pub enum ArchiveInfo {
     ArchiveFile {
         path: String,
         password: Option<String>,
+        #[serde(default)]
+        format: ArchiveFormat,
     },
     CommonFile {
         path: String,
@@ -71,7 +108,7 @@ impl ArchiveInfo {
                 // Handle relative paths by resolving them to the root directory
                 let path = config_get().resolve_to_root(path_seg);
                 if path.exists() {
-                    This(path.into())
+                    This(path)
                 } else {
                     warn!(
                         "The specified archive file path does not exist: {}",
@@ -106,12 +143,105 @@ impl ArchiveInfo {
             }
         }
     }
+    /// Path of an archive file inside the data root, which belongs to the library
+    pub fn managed_path(&self) -> Option<PathBuf> {
+        let ArchiveInfo::ArchiveFile { path, .. } = self else {
+            return None;
+        };
+        let root = config_get().get_root().canonicalize().ok()?;
+        let path = config_get()
+            .resolve_to_root(Path::new(path))
+            .canonicalize()
+            .ok()?;
+        path.starts_with(&root).then_some(path)
+    }
+
     pub fn calculate_size(&self) -> u64 {
         match self.try_resolve() {
             This(path) => path.calculate_size().unwrap_or_default(),
             That(_) => 0,
         }
     }
+
+    /// Content hash of the archive, common file or directory, `None` if it could not be read
+    pub fn calculate_hash(&self) -> Option<String> {
+        match self.try_resolve() {
+            This(path) => hash_path(&path)
+                .inspect_err(|err| warn!("Failed to hash {}: {}", path.display(), err))
+                .ok(),
+            That(_) => None,
+        }
+    }
+}
+
+/// Outcome of checking the source of an entry against its stored hash
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub enum IntegrityStatus {
+    /// Content matches the stored hash
+    Intact,
+
+    /// No hash was stored before, the current one is recorded
+    Recorded,
+
+    Unset,
+    Missing,
+    Mismatch {
+        expected: String,
+        actual: String,
+    },
+
+    /// The content could not be hashed, or the archive could not be read with its password
+    Unreadable {
+        error: String,
+    },
+}
+
+/// Integrity of a single entry as reported by a library-wide verification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    pub id: String,
+    pub title: String,
+    pub status: IntegrityStatus,
+}
+
+/// How the files of the source are placed in the deploy target
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Default)]
+pub enum DeployMode {
+    #[default]
+    Copy,
+    /// Directories are created, files are symbolic links to the source
+    Symlink,
+    /// Directories are created, files are hard links to the source on the same volume
+    HardLink,
+    /// Copy on write clones where the file system supports it, plain copies otherwise
+    Reflink,
+}
+
+impl DeployMode {
+    /// Place the single file `source` at `target`
+    pub fn place_file(
+        &self,
+        source: &Path,
+        target: &Path,
+        progress: &ProgressHandle,
+    ) -> io::Result<()> {
+        match self {
+            DeployMode::Copy => return source.copy_file_to(target, progress),
+            DeployMode::Symlink => {
+                let source = std::path::absolute(source)?;
+                #[cfg(unix)]
+                std::os::unix::fs::symlink(&source, target)?;
+                #[cfg(windows)]
+                std::os::windows::fs::symlink_file(&source, target)?;
+            }
+            DeployMode::HardLink => fs::hard_link(source, target)?,
+            DeployMode::Reflink => {
+                reflink_copy::reflink_or_copy(source, target)?;
+            }
+        }
+        progress.add_bytes(source.metadata()?.len());
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Default)]
@@ -120,20 +250,40 @@ pub enum DeployInfo {
     Unset,
     Directory {
         path: String,
+        #[serde(default)]
+        mode: DeployMode,
     },
     File {
         path: String,
+        #[serde(default)]
+        mode: DeployMode,
     },
 }
 
 impl DeployInfo {
+    /// Directory deployed content is placed in, the parent for a deployed file
+    pub fn root(&self) -> Option<PathBuf> {
+        match self {
+            DeployInfo::Unset => None,
+            DeployInfo::Directory { path, .. } => Some(PathBuf::from(path)),
+            DeployInfo::File { path, .. } => Path::new(path).parent().map(Path::to_path_buf),
+        }
+    }
+
+    pub fn mode(&self) -> Option<DeployMode> {
+        match self {
+            DeployInfo::Unset => None,
+            DeployInfo::Directory { mode, .. } | DeployInfo::File { mode, .. } => Some(*mode),
+        }
+    }
+
     pub fn try_resolve(&self) -> Whether<PathBuf, DeployInfo> {
         match self {
             DeployInfo::Unset => {
                 warn!("DeployInfo is unset, cannot resolve path.");
                 That(DeployInfo::Unset)
             }
-            DeployInfo::File { path } => {
+            DeployInfo::File { path, .. } => {
                 let path = Path::new(path);
                 if path.exists() {
                     This(path.to_path_buf())
@@ -142,7 +292,7 @@ impl DeployInfo {
                     That(DeployInfo::Unset)
                 }
             }
-            DeployInfo::Directory { path } => {
+            DeployInfo::Directory { path, .. } => {
                 let path = Path::new(path);
                 if path.exists() {
                     This(path.to_path_buf())
@@ -158,6 +308,22 @@ impl DeployInfo {
     }
 }
 
+/// One place the entry is deployed to, an entry could be deployed to several places at once
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Deployment {
+    pub id: String,
+
+    /// Target path and mode of the deployment
+    pub info: DeployInfo,
+    pub time: DateTime<Utc>,
+
+    /// Entries created by the deployment, only attached while working on it. Manifests are
+    /// stored apart under [`Deployment::id`], none exists for deployments made before
+    /// manifests were recorded. Earlier versions embedded them here.
+    #[serde(default, skip_serializing)]
+    pub manifest: Option<DeployManifest>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder)]
 #[builder(pattern = "owned", setter(into))]
 pub struct Metadata {
@@ -196,6 +362,11 @@ pub struct Metadata {
     #[builder(default)]
     pub publisher: Option<String>,
 
+    /// Cover image, a path relative to the data root or an absolute one
+    #[serde(default)]
+    #[builder(default)]
+    pub cover: Option<String>,
+
     #[serde(default = "default_version")]
     #[builder(default = "default_version()")]
     pub version: String,
@@ -208,10 +379,31 @@ pub struct Metadata {
     #[builder(default)]
     pub archive_size: u64,
 
+    /// BLAKE3 of the archive or common file, or of the whole tree for directories
     #[serde(default)]
     #[builder(default)]
+    pub archive_hash: Option<String>,
+
+    #[serde(default)]
+    #[builder(default)]
+    pub deployments: Vec<Deployment>,
+
+    /// Single deployment recorded by older versions, see [`Metadata::upgrade_legacy_deploy`]
+    #[serde(default, skip_serializing)]
+    #[builder(default)]
     pub deploy_info: DeployInfo,
 
+    /// Manifest of the legacy single deployment
+    #[serde(default, skip_serializing)]
+    #[builder(default)]
+    pub deploy_manifest: Option<DeployManifest>,
+
+    /// Glob patterns relative to the deploy root matching save files,
+    /// which are kept in the save store while not deployed
+    #[serde(default)]
+    #[builder(default)]
+    pub save_paths: Vec<String>,
+
     #[serde(default = "Utc::now")]
     #[builder(default = "Utc::now()")]
     pub time_created: DateTime<Utc>,
@@ -221,7 +413,7 @@ pub struct Metadata {
     pub time_updated: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct MetadataOptional {
     pub id: Option<String>,
     pub title: Option<String>,
@@ -232,15 +424,25 @@ pub struct MetadataOptional {
     pub description: Option<String>,
     pub developer: Option<String>,
     pub publisher: Option<String>,
+
+    /// Cover image, an empty path removes it
+    #[serde(default)]
+    pub cover: Option<String>,
     pub version: Option<String>,
     pub archive_info: Option<ArchiveInfo>,
     pub archive_size: Option<u64>,
-    pub deploy_info: Option<DeployInfo>,
+    #[serde(default)]
+    pub save_paths: Option<Vec<String>>,
+
     pub time_created: Option<DateTime<Utc>>,
     pub time_updated: Option<DateTime<Utc>>,
 
     #[serde(default)]
     pub flag_create_archive: bool,
+
+    /// Format of the archive to create, the config default is used if not set
+    #[serde(default)]
+    pub archive_format: Option<ArchiveFormat>,
 }
 
 fn default_id() -> String {
@@ -255,6 +457,7 @@ impl Metadata {
     fn map_builder(
         mut builder: MetadataBuilder,
         opt: MetadataOptional,
+        progress: &ProgressHandle,
     ) -> MetadataResult<MetadataBuilder> {
         if let Some(title) = opt.title {
             builder = builder.title(title);
@@ -280,11 +483,14 @@ impl Metadata {
         if let Some(publisher) = opt.publisher {
             builder = builder.publisher(publisher);
         }
+        if let Some(cover) = opt.cover {
+            builder = builder.cover(Some(cover).filter(|cover| !cover.trim().is_empty()));
+        }
         if let Some(version) = opt.version {
             builder = builder.version(version);
         }
-        if let Some(deploy_info) = opt.deploy_info {
-            builder = builder.deploy_info(deploy_info);
+        if let Some(save_paths) = opt.save_paths {
+            builder = builder.save_paths(save_paths);
         }
         if let Some(time_created) = opt.time_created {
             builder = builder.time_created(time_created);
@@ -297,6 +503,8 @@ impl Metadata {
             opt.archive_info,
             opt.platform,
             opt.flag_create_archive,
+            opt.archive_format,
+            progress,
         )?;
         Ok(builder)
     }
@@ -306,10 +514,12 @@ impl Metadata {
         archive_info: Option<ArchiveInfo>,
         platform_info: Option<DistributionPlatform>,
         flag: bool,
+        format: Option<ArchiveFormat>,
+        progress: &ProgressHandle,
     ) -> MetadataResult<MetadataBuilder> {
         if flag {
             info!("Creating new archive info for metadata with compression flag enabled.");
-            if let Some(ArchiveInfo::ArchiveFile { path, password }) = archive_info {
+            if let Some(ArchiveInfo::ArchiveFile { path, password, .. }) = archive_info {
                 let path = Path::new(&path);
                 if !path.exists() {
                     warn!(
@@ -319,13 +529,15 @@ impl Metadata {
                     return Err(MetadataError::MapConversionError);
                 }
 
+                let format = format.unwrap_or_else(|| config_get().get_archive_format());
+                let ext = format.extension();
                 let (dir, filename) = match platform_info {
-                    Some(DistributionPlatform::Steam { id }) => ("Steam", format!("{id}.7z")),
-                    Some(DistributionPlatform::DLSite { id }) => ("DLSite", format!("{id}.7z")),
+                    Some(DistributionPlatform::Steam { id }) => ("Steam", format!("{id}.{ext}")),
+                    Some(DistributionPlatform::DLSite { id }) => ("DLSite", format!("{id}.{ext}")),
                     Some(DistributionPlatform::Other { name, id }) => (
                         "Other",
                         format!(
-                            "{name}-{}.7z",
+                            "{name}-{}.{ext}",
                             id.unwrap_or_else(|| Utc::now()
                                 .format("%Y-%m-%d-%H-%M-%S")
                                 .to_string())
@@ -333,30 +545,47 @@ impl Metadata {
                     ),
                     _ => (
                         "Unknown",
-                        format!("Unknown-{}.7z", Utc::now().format("%Y-%m-%d-%H-%M-%S")),
+                        format!("Unknown-{}.{ext}", Utc::now().format("%Y-%m-%d-%H-%M-%S")),
                     ),
                 };
 
-                let archive_seg_parent = Path::new(DIR_ARCHIVE).join(dir);
-                if !archive_seg_parent.exists() {
-                    fs::create_dir_all(&archive_seg_parent)?;
-                }
-
-                let archive_seg = archive_seg_parent.join(&filename);
+                let archive_seg = Path::new(DIR_ARCHIVE).join(dir).join(&filename);
                 let archive_path = config_get().resolve_to_root(&archive_seg);
+                if let Some(parent) = archive_path.parent()
+                    && !parent.exists()
+                {
+                    fs::create_dir_all(parent)?;
+                }
 
                 info!("Archive going to be created at: {}", archive_path.display());
 
-                match compress(path, &archive_path, password.as_deref(), None) {
+                match compress(
+                    path,
+                    &archive_path,
+                    format,
+                    password.as_deref(),
+                    None,
+                    progress,
+                ) {
                     Ok(_) => {
                         info!("Successfully created archive for metadata as: {}", filename);
                         builder =
                             builder.archive_size(archive_path.calculate_size().unwrap_or_default());
+                        builder = builder.archive_hash(
+                            hash_file(&archive_path)
+                                .inspect_err(|err| warn!("Failed to hash archive: {err}"))
+                                .ok(),
+                        );
                         builder = builder.archive_info(ArchiveInfo::ArchiveFile {
                             path: archive_seg.to_string_lossy().to_string(),
                             password,
+                            format,
                         });
                     }
+                    Err(CompressError::Cancelled) => {
+                        warn!("Creating archive for {filename} was cancelled");
+                        return Err(MetadataError::Cancelled);
+                    }
                     Err(err) => {
                         warn!("Failed to create archive for {filename}: {err}");
                         builder = builder.archive_info(ArchiveInfo::Unset)
@@ -366,16 +595,37 @@ impl Metadata {
                 warn!("The flag_create_archive is set, but no valid archive info provided.");
                 builder = builder.archive_info(ArchiveInfo::Unset);
                 builder = builder.archive_size(0u64);
+                builder = builder.archive_hash(None);
             }
             Ok(builder)
         } else {
             info!("Using provided archive info without compression.");
+            // Trust the file extension over the recorded format of existing archives
+            let archive_info = archive_info.map(|info| match info {
+                ArchiveInfo::ArchiveFile {
+                    path,
+                    password,
+                    format,
+                } => ArchiveInfo::ArchiveFile {
+                    format: ArchiveFormat::from_path(Path::new(&path)).unwrap_or(format),
+                    path,
+                    password,
+                },
+                info => info,
+            });
             builder = builder.archive_size(
                 archive_info
                     .as_ref()
                     .map_or(0, |info| info.calculate_size()),
             );
-            builder = builder.archive_info(archive_info.unwrap_or_default());
+            // Hashing is slow, keep the stored hash while the source stays the same
+            let archive_info = archive_info.unwrap_or_default();
+            let unchanged = builder.archive_info.as_ref() == Some(&archive_info)
+                && matches!(builder.archive_hash, Some(Some(_)));
+            if !unchanged {
+                builder = builder.archive_hash(archive_info.calculate_hash());
+            }
+            builder = builder.archive_info(archive_info);
             Ok(builder)
         }
     }
@@ -384,18 +634,22 @@ impl Metadata {
         self.time_updated = Utc::now();
     }
 
-    pub fn init(opt: MetadataOptional) -> MetadataResult<Self> {
+    pub fn init(opt: MetadataOptional, progress: &ProgressHandle) -> MetadataResult<Self> {
         let mut builder = MetadataBuilder::default();
-        builder = Self::map_builder(builder, opt)?;
+        builder = Self::map_builder(builder, opt, progress)?;
         builder.build().map_err(|err| {
             error!("Failed to build Metadata from map: {}", err);
             MetadataError::MapConversionError
         })
     }
 
-    pub fn patch(self, opt: MetadataOptional) -> MetadataResult<Metadata> {
+    pub fn patch(
+        self,
+        opt: MetadataOptional,
+        progress: &ProgressHandle,
+    ) -> MetadataResult<Metadata> {
         let mut builder: MetadataBuilder = self.into();
-        builder = Self::map_builder(builder, opt)?;
+        builder = Self::map_builder(builder, opt, progress)?;
         builder = builder.time_updated(Utc::now());
         builder.build().map_err(|err| {
             error!("Failed to build Metadata from map: {}", err);
@@ -403,7 +657,106 @@ impl Metadata {
         })
     }
 
-    pub fn deploy(&mut self, target_path: &str) -> MetadataResult<bool> {
+    /// Take over what [`Metadata::patch`] with `opt` changed on an earlier copy of this entry,
+    /// fields `opt` leaves unset keep their current value
+    pub fn take_patched(&mut self, patched: Metadata, opt: &MetadataOptional) {
+        if opt.title.is_some() {
+            self.title = patched.title;
+        }
+        if opt.alias.is_some() {
+            self.alias = patched.alias;
+        }
+        if opt.tags.is_some() {
+            self.tags = patched.tags;
+        }
+        if opt.content_type.is_some() {
+            self.content_type = patched.content_type;
+        }
+        if opt.platform.is_some() {
+            self.platform = patched.platform;
+        }
+        if opt.description.is_some() {
+            self.description = patched.description;
+        }
+        if opt.developer.is_some() {
+            self.developer = patched.developer;
+        }
+        if opt.publisher.is_some() {
+            self.publisher = patched.publisher;
+        }
+        if opt.cover.is_some() {
+            self.cover = patched.cover;
+        }
+        if opt.version.is_some() {
+            self.version = patched.version;
+        }
+        if opt.save_paths.is_some() {
+            self.save_paths = patched.save_paths;
+        }
+        if opt.time_created.is_some() {
+            self.time_created = patched.time_created;
+        }
+        // The archive is always mapped, see `map_archive_info`
+        self.archive_info = patched.archive_info;
+        self.archive_size = patched.archive_size;
+        self.archive_hash = patched.archive_hash;
+        self.time_updated = patched.time_updated;
+    }
+
+    /// Combine `other` into this entry, which keeps its id and title.
+    ///
+    /// Lists are merged, fields missing here are taken from `other`, and the archive is taken
+    /// from `other` if `keep_other_archive` is set and it has one. The archive not kept
+    /// stays on disk.
+    pub fn merge(mut self, other: Metadata, keep_other_archive: bool) -> Metadata {
+        fn extend_unique(target: &mut Vec<String>, items: impl IntoIterator<Item = String>) {
+            for item in items {
+                if !target.contains(&item) {
+                    target.push(item);
+                }
+            }
+        }
+
+        let other_title = (other.title != self.title).then_some(other.title);
+        extend_unique(&mut self.alias, other_title.into_iter().chain(other.alias));
+        self.alias.retain(|alias| *alias != self.title);
+        extend_unique(&mut self.tags, other.tags);
+        extend_unique(&mut self.save_paths, other.save_paths);
+
+        if self.content_type == ContentType::Other {
+            self.content_type = other.content_type;
+        }
+        if self.platform == DistributionPlatform::Unknown {
+            self.platform = other.platform;
+        }
+        self.description = self.description.or(other.description);
+        self.developer = self.developer.or(other.developer);
+        self.publisher = self.publisher.or(other.publisher);
+        self.cover = self.cover.or(other.cover);
+
+        let other_has_archive = other.archive_info != ArchiveInfo::Unset;
+        if other_has_archive && (keep_other_archive || self.archive_info == ArchiveInfo::Unset) {
+            self.archive_info = other.archive_info;
+            self.archive_size = other.archive_size;
+            self.archive_hash = other.archive_hash;
+        }
+        // Deployments of the other entry are kept so they could still be removed
+        self.deployments.extend(other.deployments);
+        self.time_created = self.time_created.min(other.time_created);
+        self.mark_update();
+        self
+    }
+
+    /// Deploy the archive info to `target_path` as a new deployment, the partial output is
+    /// removed if the operation fails or is cancelled through `progress`.
+    /// Archives are always extracted, `mode` only applies to files and directories.
+    /// Returns the id of the deployment, `None` if the archive info is no longer valid.
+    pub fn deploy(
+        &mut self,
+        target_path: &str,
+        mode: DeployMode,
+        progress: &ProgressHandle,
+    ) -> MetadataResult<Option<String>> {
         let target_path = Path::new(target_path);
         if !target_path.exists() || !target_path.is_dir() {
             warn!("Target path not valid: {}", target_path.display());
@@ -411,28 +764,56 @@ impl Metadata {
                 target_path.display().to_string(),
             ));
         }
-        match self.archive_info.try_resolve() {
+        let (info, manifest) = match self.archive_info.try_resolve() {
             This(source_path) => match &self.archive_info {
                 ArchiveInfo::CommonFile { .. } => {
                     let target_path =
                         target_path.join(source_path.file_name().unwrap_or(OsStr::new("Why")));
+                    if target_path.symlink_metadata().is_ok() {
+                        error!(
+                            "Target file {} already exists, cannot deploy file.",
+                            target_path.display()
+                        );
+                        return Err(MetadataError::TargetPathError(
+                            target_path.display().to_string(),
+                        ));
+                    }
                     info!(
-                        "Deploying file by copying from {} to {}",
+                        "Deploying file with mode {:?} from {} to {}",
+                        mode,
                         source_path.display(),
                         target_path.display()
                     );
 
-                    fs::copy(source_path, &target_path)?;
-
-                    self.deploy_info = DeployInfo::File {
-                        path: target_path.to_string_lossy().to_string(),
+                    let copied = source_path.metadata().and_then(|meta| {
+                        progress.set_total(meta.len(), 1);
+                        progress.start_file(target_path.to_string_lossy());
+                        mode.place_file(&source_path, &target_path, progress)
+                    });
+                    let manifest = copied.and_then(|_| {
+                        let root = target_path.parent().unwrap_or(Path::new("."));
+                        DeployManifest::collect_files(root, &[&target_path])
+                    });
+                    let manifest = match manifest {
+                        Ok(manifest) => manifest,
+                        Err(err) => {
+                            let _ = fs::remove_file(&target_path);
+                            return Err(cancelled_or(err.into(), progress));
+                        }
                     };
-                    self.mark_update();
+                    progress.finish_file();
+                    progress.flush();
 
                     info!("File deployed successfully to {}", target_path.display());
-                    Ok(true)
+                    let info = DeployInfo::File {
+                        path: target_path.to_string_lossy().to_string(),
+                        mode,
+                    };
+                    (info, manifest)
                 }
-                ArchiveInfo::ArchiveFile { password, .. } => {
+                ArchiveInfo::ArchiveFile {
+                    password, format, ..
+                } => {
                     if !target_path.is_dir_empty() {
                         error!(
                             "Target directory {} is not empty, cannot deploy archive.",
@@ -443,22 +824,33 @@ impl Metadata {
                         ));
                     }
 
+                    if mode != DeployMode::Copy {
+                        warn!("Deploy mode {mode:?} is not supported for archives, extracting");
+                    }
                     info!(
                         "Deploying archive by extracting from {} to {}",
                         source_path.display(),
                         target_path.display()
                     );
 
-                    decompress(source_path, target_path, password.as_deref())?;
+                    decompress(
+                        source_path,
+                        target_path,
+                        *format,
+                        password.as_deref(),
+                        progress,
+                    )
+                    .map_err(|err| cancelled_or(err.into(), progress))?;
+                    let manifest = DeployManifest::collect(target_path).inspect_err(|_| {
+                        let _ = target_path.clear_dir();
+                    })?;
 
-                    self.deploy_info = DeployInfo::Directory {
+                    info!("Archive deployed successfully to {}", target_path.display());
+                    let info = DeployInfo::Directory {
                         path: target_path.to_string_lossy().to_string(),
+                        mode: DeployMode::Copy,
                     };
-                    self.mark_update();
-
-                    info!("Archive deployed successfully to {}", target_path.display());
-
-                    Ok(true)
+                    (info, manifest)
                 }
                 ArchiveInfo::Directory { .. } => {
                     if !target_path.is_dir_empty() {
@@ -472,72 +864,405 @@ impl Metadata {
                     }
 
                     info!(
-                        "Deploying directory by copying from {} to {}",
+                        "Deploying directory with mode {:?} from {} to {}",
+                        mode,
                         source_path.display(),
                         target_path.display()
                     );
 
-                    dir::copy(
-                        source_path,
-                        target_path,
-                        &CopyOptions::new().copy_inside(true).overwrite(true),
-                    )?;
-
-                    self.deploy_info = DeployInfo::Directory {
-                        path: target_path.to_string_lossy().to_string(),
+                    // Same layout as before, the directory itself is placed inside the target
+                    let copy_path =
+                        target_path.join(source_path.file_name().unwrap_or(OsStr::new("Why")));
+                    let manifest = source_path
+                        .mirror_dir_to(&copy_path, progress, |source, target| {
+                            mode.place_file(source, target, progress)
+                        })
+                        .and_then(|_| DeployManifest::collect(target_path));
+                    let manifest = match manifest {
+                        Ok(manifest) => manifest,
+                        Err(err) => {
+                            let _ = target_path.clear_dir();
+                            return Err(cancelled_or(err.into(), progress));
+                        }
                     };
-                    self.mark_update();
 
                     info!(
                         "Directory deployed successfully to {}",
                         target_path.display()
                     );
-
-                    Ok(true)
+                    let info = DeployInfo::Directory {
+                        path: target_path.to_string_lossy().to_string(),
+                        mode,
+                    };
+                    (info, manifest)
                 }
                 ArchiveInfo::Unset => {
                     warn!("Archive info is unset, cannot deploy.");
-                    Err(MetadataError::InvalidDeployOperation)
+                    return Err(MetadataError::InvalidDeployOperation);
                 }
             },
             That(update) => {
                 error!("Archive info is invalid or missing");
                 self.archive_info = update;
-                Ok(false)
+                return Ok(None);
             }
-        }
+        };
+
+        let deployment = Deployment {
+            id: uuid::Uuid::new_v4().to_string(),
+            info,
+            time: Utc::now(),
+            manifest: Some(manifest),
+        };
+        self.restore_saves(&deployment);
+        let id = deployment.id.clone();
+        self.deployments.push(deployment);
+        self.mark_update();
+        Ok(Some(id))
     }
 
-    pub fn deploy_off(&mut self) -> MetadataResult<bool> {
-        let path = match self.deploy_info.try_resolve() {
+    /// Remove a deployment, files modified or added since are kept and reported. Deployments
+    /// without a manifest are compared against the source instead, which is staged through
+    /// `progress`. Returns `None` if the deployment no longer exists on disk, it is dropped
+    /// anyway.
+    pub fn deploy_off(
+        &mut self,
+        deployment_id: &str,
+        progress: &ProgressHandle,
+    ) -> MetadataResult<Option<DeployOffReport>> {
+        let Some(index) = self
+            .deployments
+            .iter()
+            .position(|deployment| deployment.id == deployment_id)
+        else {
+            warn!("Deployment {deployment_id} not found in {}", self.id);
+            return Err(MetadataError::DeploymentNotFound(deployment_id.to_string()));
+        };
+        let deployment = &self.deployments[index];
+
+        let path = match deployment.info.try_resolve() {
             This(path) => path,
-            That(update) => {
-                error!("Deploy info is invalid or missing");
-                self.deploy_info = update;
-                return Ok(false);
+            That(_) => {
+                error!("Deployment {deployment_id} is invalid or missing, dropping it");
+                self.deployments.remove(index);
+                self.mark_update();
+                return Ok(None);
             }
         };
-        match self.deploy_info {
-            DeployInfo::Unset => {
+        let Some(root) = deployment.info.root() else {
+            warn!("Deploy info not exists, cannot deploy off.");
+            return Err(MetadataError::InvalidDeployOperation);
+        };
+        let saved = self.snapshot_saves(&root)?;
+        let report = match (&deployment.info, &deployment.manifest) {
+            (DeployInfo::Unset, _) => {
                 warn!("Deploy info not exists, cannot deploy off.");
-                Err(MetadataError::InvalidDeployOperation)
+                return Err(MetadataError::InvalidDeployOperation);
+            }
+            (DeployInfo::File { .. }, Some(manifest)) => {
+                info!("Removing deployed file at: {}", path.display());
+                manifest.remove_from(&root, &saved, deployment.time)?
             }
-            DeployInfo::File { .. } => {
+            (DeployInfo::File { .. }, None) => {
                 info!("Removing deployed file at: {}", path.display());
                 fs::remove_file(&path)?;
-                self.deploy_info = DeployInfo::Unset;
-                self.mark_update();
-                info!("File deployed off successfully.");
-                Ok(true)
+                DeployOffReport {
+                    removed: 1,
+                    ..Default::default()
+                }
             }
-            DeployInfo::Directory { .. } => {
-                info!("Removing deployed directory at: {}", path.display());
-                path.clear_dir()?;
-                self.deploy_info = DeployInfo::Unset;
-                self.mark_update();
-                info!("Directory deployed off successfully.");
-                Ok(true)
+            (DeployInfo::Directory { .. }, Some(manifest)) => {
+                info!("Removing deployed entries from: {}", path.display());
+                manifest.remove_from(&root, &saved, deployment.time)?
             }
+            (DeployInfo::Directory { mode, .. }, None) => {
+                // The target may hold more than the deployment, only what the source has goes
+                warn!(
+                    "No deploy manifest recorded, removing the entries of the source from: {}",
+                    path.display()
+                );
+                let manifest = self
+                    .stage_source(progress)
+                    .and_then(|source| Ok(source.deployed_manifest(*mode)?))
+                    .inspect_err(|err| {
+                        error!("Cannot tell what was deployed to {}: {err}", path.display())
+                    })?;
+                manifest.remove_from(&root, &saved, deployment.time)?
+            }
+        };
+        self.deployments.remove(index);
+        self.mark_update();
+        info!("Deployed off successfully.");
+        Ok(Some(report))
+    }
+
+    pub fn is_deployed(&self) -> bool {
+        !self.deployments.is_empty()
+    }
+
+    pub fn get_deployment(&self, deployment_id: &str) -> Option<&Deployment> {
+        self.deployments
+            .iter()
+            .find(|deployment| deployment.id == deployment_id)
+    }
+
+    /// Compare a deployment against its recorded manifest, deployments made before manifests
+    /// were recorded are compared against the source instead
+    pub fn verify_deployment(
+        &self,
+        deployment_id: &str,
+        progress: &ProgressHandle,
+    ) -> MetadataResult<DriftReport> {
+        let deployment = self
+            .get_deployment(deployment_id)
+            .ok_or_else(|| MetadataError::DeploymentNotFound(deployment_id.to_string()))?;
+        let root = deployment
+            .info
+            .root()
+            .ok_or(MetadataError::InvalidDeployOperation)?;
+        let mode = deployment.info.mode().unwrap_or_default();
+        let report = match &deployment.manifest {
+            Some(manifest) => manifest.verify(&root)?,
+            None => self
+                .stage_source(progress)?
+                .deployed_manifest(mode)?
+                .verify(&root)?,
+        };
+        Ok(report)
+    }
+
+    /// Place missing and modified entries of a deployment again from the source,
+    /// extra files are left alone. Returns the drift found before repairing.
+    pub fn repair_deployment(
+        &mut self,
+        deployment_id: &str,
+        progress: &ProgressHandle,
+    ) -> MetadataResult<DriftReport> {
+        let Some(index) = self
+            .deployments
+            .iter()
+            .position(|deployment| deployment.id == deployment_id)
+        else {
+            return Err(MetadataError::DeploymentNotFound(deployment_id.to_string()));
+        };
+        let deployment = &self.deployments[index];
+        let root = deployment
+            .info
+            .root()
+            .ok_or(MetadataError::InvalidDeployOperation)?;
+        let mode = deployment.info.mode().unwrap_or_default();
+
+        let source = self.stage_source(progress)?;
+        let expected = match &deployment.manifest {
+            Some(manifest) => manifest.clone(),
+            None => source.deployed_manifest(mode)?,
+        };
+        let report = expected.verify(&root)?;
+
+        let damaged = expected
+            .entries
+            .iter()
+            .filter(|entry| {
+                let path = entry.path().to_string();
+                report.missing.contains(&path) || report.modified.contains(&path)
+            })
+            .collect::<Vec<_>>();
+        let damaged_files = damaged
+            .iter()
+            .filter(|entry| matches!(entry, ManifestEntry::File { .. }))
+            .count();
+        progress.set_total(0, damaged_files as u64);
+        info!(
+            "Repairing {} entries of deployment {} at {}",
+            damaged.len(),
+            deployment_id,
+            root.display()
+        );
+
+        // Entries are recorded parents first, so directories exist before their files
+        let repaired = damaged.into_iter().try_for_each(|entry| {
+            progress.check()?;
+            let target = root.join(entry.path());
+            if let Ok(meta) = target.symlink_metadata() {
+                if meta.is_dir() {
+                    if matches!(entry, ManifestEntry::Directory { .. }) {
+                        return Ok(());
+                    }
+                    fs::remove_dir_all(&target)?;
+                } else {
+                    fs::remove_file(&target)?;
+                }
+            }
+            match entry {
+                ManifestEntry::Directory { .. } => fs::create_dir_all(&target),
+                ManifestEntry::File { path, .. } => {
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    progress.start_file(path.as_str());
+                    mode.place_file(&source.root.join(path), &target, progress)?;
+                    progress.finish_file();
+                    Ok(())
+                }
+            }
+        });
+        repaired.map_err(|err| cancelled_or(err.into(), progress))?;
+        progress.flush();
+
+        if self.deployments[index].manifest.is_none() {
+            self.deployments[index].manifest = Some(expected);
+            self.mark_update();
+        }
+        info!("Deployment {deployment_id} repaired.");
+        Ok(report)
+    }
+
+    /// Rehash the source and compare it with the stored hash, archives are also read through
+    /// with the stored password. A hash is recorded if none was stored before.
+    pub fn verify_source(&mut self, progress: &ProgressHandle) -> IntegrityStatus {
+        if self.archive_info == ArchiveInfo::Unset {
+            return IntegrityStatus::Unset;
+        }
+        let This(path) = self.archive_info.try_resolve() else {
+            return IntegrityStatus::Missing;
+        };
+        let actual = match hash_path(&path) {
+            Ok(hash) => hash,
+            Err(err) => {
+                return IntegrityStatus::Unreadable {
+                    error: err.to_string(),
+                };
+            }
+        };
+        if let Some(expected) = &self.archive_hash
+            && *expected != actual
+        {
+            warn!("Content hash mismatch for {}", path.display());
+            return IntegrityStatus::Mismatch {
+                expected: expected.clone(),
+                actual,
+            };
+        }
+        if let ArchiveInfo::ArchiveFile {
+            password, format, ..
+        } = &self.archive_info
+            && let Err(err) = test(&path, *format, password.as_deref(), progress)
+        {
+            warn!("Archive {} is not readable: {}", path.display(), err);
+            return IntegrityStatus::Unreadable {
+                error: err.to_string(),
+            };
+        }
+        if self.archive_hash.is_none() {
+            self.archive_hash = Some(actual);
+            return IntegrityStatus::Recorded;
+        }
+        IntegrityStatus::Intact
+    }
+
+    /// Lay out the source of the entry the same way a deployment does,
+    /// archives are extracted into a staging directory
+    fn stage_source(&self, progress: &ProgressHandle) -> MetadataResult<StagedSource> {
+        let This(source_path) = self.archive_info.try_resolve() else {
+            error!("Archive info is invalid or missing");
+            return Err(MetadataError::InvalidDeployOperation);
+        };
+        let parent = source_path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        match &self.archive_info {
+            ArchiveInfo::CommonFile { .. } => Ok(StagedSource {
+                manifest: DeployManifest::collect_files(&parent, &[&source_path])?,
+                root: parent,
+                staging: None,
+            }),
+            ArchiveInfo::Directory { .. } => Ok(StagedSource {
+                manifest: DeployManifest::collect_dir(&parent, &source_path)?,
+                root: parent,
+                staging: None,
+            }),
+            ArchiveInfo::ArchiveFile {
+                password, format, ..
+            } => {
+                let staging = config_get()
+                    .get_root()
+                    .join(DIR_STAGING)
+                    .join(uuid::Uuid::new_v4().to_string());
+                fs::create_dir_all(&staging)?;
+                let mut source = StagedSource {
+                    root: staging.clone(),
+                    manifest: DeployManifest::default(),
+                    staging: Some(staging),
+                };
+                info!(
+                    "Extracting {} into staging directory {}",
+                    source_path.display(),
+                    source.root.display()
+                );
+                decompress(
+                    &source_path,
+                    &source.root,
+                    *format,
+                    password.as_deref(),
+                    progress,
+                )
+                .map_err(|err| cancelled_or(err.into(), progress))?;
+                source.manifest = DeployManifest::collect(&source.root)?;
+                Ok(source)
+            }
+            ArchiveInfo::Unset => Err(MetadataError::InvalidDeployOperation),
+        }
+    }
+
+    /// Take the manifests attached to the deployments, paired with their deployment ids
+    pub fn take_manifests(&mut self) -> Vec<(String, DeployManifest)> {
+        self.deployments
+            .iter_mut()
+            .filter_map(|deployment| Some((deployment.id.clone(), deployment.manifest.take()?)))
+            .collect()
+    }
+
+    /// Convert the single deployment recorded by older versions into [`Metadata::deployments`],
+    /// returns true if anything changed
+    pub fn upgrade_legacy_deploy(&mut self) -> bool {
+        if self.deploy_info == DeployInfo::Unset {
+            return false;
+        }
+        self.deployments.push(Deployment {
+            id: uuid::Uuid::new_v4().to_string(),
+            info: std::mem::take(&mut self.deploy_info),
+            time: self.time_updated,
+            manifest: self.deploy_manifest.take(),
+        });
+        true
+    }
+
+    /// Copy save files out of the deploy root, returns the paths saved
+    fn snapshot_saves(&self, root: &Path) -> MetadataResult<BTreeSet<String>> {
+        if self.save_paths.is_empty() {
+            return Ok(BTreeSet::new());
+        }
+        let snapshot = save_snapshot(&self.id, root, &self.save_paths)?;
+        Ok(snapshot
+            .map(|snapshot| snapshot.files.into_iter().collect())
+            .unwrap_or_default())
+    }
+
+    /// Restore the latest save snapshot into a fresh deployment, failures are only logged
+    /// since the snapshot is still kept in the save store
+    fn restore_saves(&self, deployment: &Deployment) {
+        if self.save_paths.is_empty() {
+            return;
+        }
+        let Some(root) = deployment.info.root() else {
+            return;
+        };
+        match save_latest(&self.id) {
+            Ok(Some(snapshot)) => {
+                if let Err(err) = save_restore(&self.id, &snapshot, &root) {
+                    error!("Failed to restore saves of {}: {}", self.id, err);
+                }
+            }
+            Ok(None) => {}
+            Err(err) => error!("Failed to load saves of {}: {}", self.id, err),
         }
     }
 }
@@ -554,10 +1279,15 @@ impl From<Metadata> for MetadataBuilder {
             .description(metadata.description)
             .developer(metadata.developer)
             .publisher(metadata.publisher)
+            .cover(metadata.cover)
             .version(metadata.version)
             .archive_info(metadata.archive_info)
             .archive_size(metadata.archive_size)
+            .archive_hash(metadata.archive_hash)
+            .deployments(metadata.deployments)
             .deploy_info(metadata.deploy_info)
+            .deploy_manifest(metadata.deploy_manifest)
+            .save_paths(metadata.save_paths)
             .time_created(metadata.time_created)
             .time_updated(metadata.time_updated)
     }
@@ -573,12 +1303,161 @@ pub enum MetadataError {
     #[error("Failed with file system operation: {0}")]
     FileSystemError(#[from] std::io::Error),
 
-    #[error("Failed with fs_extra operation: {0}")]
-    FileSystemErrorEx(#[from] fs_extra::error::Error),
+    #[error("Failed with archive operation: {0}")]
+    CompressError(#[from] CompressError),
+
+    #[error("Failed with save snapshot: {0}")]
+    SaveError(#[from] SaveError),
 
     #[error("Target path is not a valid directory or does not exist")]
     TargetPathError(String),
 
     #[error("Deploy related info is missing or invalid")]
     InvalidDeployOperation,
+
+    #[error("Deployment {0} not found")]
+    DeploymentNotFound(String),
+
+    #[error("Operation cancelled")]
+    Cancelled,
+}
+
+/// Source of an entry as it would be deployed, see [`Metadata::stage_source`]
+struct StagedSource {
+    root: PathBuf,
+    manifest: DeployManifest,
+
+    /// Extracted archive content, removed on drop
+    staging: Option<PathBuf>,
+}
+
+impl StagedSource {
+    /// What a deployment of the source with `mode` records, links are recorded by their
+    /// target instead of the content, see [`DeployMode::place_file`]
+    fn deployed_manifest(&self, mode: DeployMode) -> io::Result<DeployManifest> {
+        if mode != DeployMode::Symlink {
+            return Ok(self.manifest.clone());
+        }
+        let entries = self
+            .manifest
+            .entries
+            .iter()
+            .map(|entry| match entry {
+                ManifestEntry::File { path, .. } => {
+                    let target = std::path::absolute(self.root.join(path))?;
+                    let target = target.to_string_lossy();
+                    Ok(ManifestEntry::File {
+                        path: path.clone(),
+                        size: target.len() as u64,
+                        hash: hash_reader(target.as_bytes())?,
+                    })
+                }
+                entry => Ok(entry.clone()),
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(DeployManifest { entries })
+    }
+}
+
+impl Drop for StagedSource {
+    fn drop(&mut self) {
+        if let Some(staging) = &self.staging
+            && let Err(err) = fs::remove_dir_all(staging)
+        {
+            warn!(
+                "Failed to remove staging directory {}: {}",
+                staging.display(),
+                err
+            );
+        }
+    }
+}
+
+/// Errors caused by a cancelled progress are reported as [`MetadataError::Cancelled`]
+fn cancelled_or(err: MetadataError, progress: &ProgressHandle) -> MetadataError {
+    if progress.is_cancelled() {
+        MetadataError::Cancelled
+    } else {
+        err
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::config::config_init_test;
+    use tempfile::tempdir;
+
+    /// An entry of the directory `source` holding `a.txt` and `sub/b.txt`
+    fn directory_entry(source: &Path) -> Metadata {
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("a.txt"), "a").unwrap();
+        fs::write(source.join("sub/b.txt"), "b").unwrap();
+        let opt = MetadataOptional {
+            title: Some(String::from("Entry")),
+            archive_info: Some(ArchiveInfo::Directory {
+                path: source.to_string_lossy().to_string(),
+            }),
+            ..Default::default()
+        };
+        Metadata::init(opt, &ProgressHandle::silent()).unwrap()
+    }
+
+    fn deploy_off_without_manifest(mode: DeployMode) {
+        config_init_test();
+        let dir = tempdir().unwrap();
+        let mut metadata = directory_entry(&dir.path().join("source"));
+        let target = dir.path().join("target");
+        fs::create_dir_all(&target).unwrap();
+        let progress = ProgressHandle::silent();
+        let id = metadata
+            .deploy(&target.to_string_lossy(), mode, &progress)
+            .unwrap()
+            .unwrap();
+        // As recorded by versions before manifests
+        metadata.deployments[0].manifest = None;
+        fs::write(target.join("unrelated.txt"), "mine").unwrap();
+        fs::write(target.join("source/sub/added.txt"), "added").unwrap();
+
+        let report = metadata.deploy_off(&id, &progress).unwrap().unwrap();
+        assert_eq!(report.added, ["source/sub/added.txt"]);
+        assert!(report.modified.is_empty());
+        assert!(target.join("unrelated.txt").exists());
+        assert!(target.join("source/sub/added.txt").exists());
+        assert!(!target.join("source/a.txt").exists());
+        assert!(!target.join("source/sub/b.txt").exists());
+        assert!(metadata.deployments.is_empty());
+    }
+
+    #[test]
+    fn deploy_off_without_manifest_removes_what_the_source_has() {
+        deploy_off_without_manifest(DeployMode::Copy);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn deploy_off_without_manifest_removes_links() {
+        deploy_off_without_manifest(DeployMode::Symlink);
+    }
+
+    #[test]
+    fn deploy_off_without_manifest_fails_if_the_source_is_gone() {
+        config_init_test();
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("source");
+        let mut metadata = directory_entry(&source);
+        let target = dir.path().join("target");
+        fs::create_dir_all(&target).unwrap();
+        let progress = ProgressHandle::silent();
+        let id = metadata
+            .deploy(&target.to_string_lossy(), DeployMode::Copy, &progress)
+            .unwrap()
+            .unwrap();
+        metadata.deployments[0].manifest = None;
+        fs::remove_dir_all(&source).unwrap();
+
+        assert!(metadata.deploy_off(&id, &progress).is_err());
+        assert!(target.join("source/a.txt").exists());
+        assert_eq!(metadata.deployments.len(), 1);
+    }
 }
@@ -1,2 +1,20 @@
+pub mod backup;
+pub mod bundle;
+pub mod catalog;
+pub mod check;
+pub mod duplicate;
+pub mod external;
+pub mod history;
+pub mod import;
+pub mod index;
+pub mod job;
 pub mod library;
+pub mod manifest;
 pub mod metadata;
+pub mod orphan;
+pub mod query;
+pub mod save;
+pub mod schema;
+pub mod transfer;
+pub mod trash;
+pub mod undo;
@@ -0,0 +1,128 @@
+use crate::DIR_ARCHIVE;
+use crate::data::metadata::{
+    ArchiveInfo, ContentType, DistributionPlatform, Metadata, MetadataOptional,
+};
+use crate::util::compress::ArchiveFormat;
+use crate::util::config::config_get;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+/// Files modified more recently are left out of a scan, they may be archives
+/// still being written by a running job
+const ORPHAN_GRACE: Duration = Duration::from_secs(600);
+
+/// A file under the archive directory which no entry refers to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrphanArchive {
+    /// Path relative to the data root, as stored in [`ArchiveInfo::ArchiveFile`]
+    pub path: String,
+    pub size: u64,
+    pub time_modified: DateTime<Utc>,
+
+    /// Platform guessed from where the archive was placed, see [`Metadata::init`]
+    pub platform: DistributionPlatform,
+}
+
+impl OrphanArchive {
+    /// Options creating a stub entry referring to the archive, titled after the file
+    pub(crate) fn stub(&self) -> MetadataOptional {
+        let path = Path::new(&self.path);
+        let format = ArchiveFormat::from_path(path).unwrap_or_default();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let title = name
+            .strip_suffix(&format!(".{}", format.extension()))
+            .unwrap_or(&name)
+            .to_string();
+        MetadataOptional {
+            id: None,
+            title: Some(title),
+            alias: None,
+            tags: None,
+            content_type: Some(ContentType::default()),
+            platform: Some(self.platform.clone()),
+            description: None,
+            developer: None,
+            publisher: None,
+            cover: None,
+            version: None,
+            archive_info: Some(ArchiveInfo::ArchiveFile {
+                path: self.path.clone(),
+                password: None,
+                format,
+            }),
+            archive_size: None,
+            save_paths: None,
+            time_created: None,
+            time_updated: None,
+            flag_create_archive: false,
+            archive_format: None,
+        }
+    }
+}
+
+/// Guess the platform from the directory and file name the archive was created with
+fn guess_platform(relative: &Path) -> DistributionPlatform {
+    let mut components = relative.iter().skip(1);
+    let (Some(dir), Some(name)) = (components.next(), components.next()) else {
+        return DistributionPlatform::Unknown;
+    };
+    let Some(id) = Path::new(name)
+        .to_string_lossy()
+        .split('.')
+        .next()
+        .map(str::to_string)
+        .filter(|id| !id.is_empty())
+    else {
+        return DistributionPlatform::Unknown;
+    };
+    match dir.to_string_lossy().as_ref() {
+        "Steam" => DistributionPlatform::Steam { id },
+        "DLSite" => DistributionPlatform::DLSite { id },
+        _ => DistributionPlatform::Unknown,
+    }
+}
+
+/// Files under the archive directory not referred to by any of `entries`, the largest first
+pub(crate) fn orphan_scan<'a>(
+    entries: impl IntoIterator<Item = &'a Metadata>,
+) -> io::Result<Vec<OrphanArchive>> {
+    let root = config_get().get_root().canonicalize()?;
+    let archive_dir = root.join(DIR_ARCHIVE);
+    if !archive_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let referenced = entries
+        .into_iter()
+        .filter_map(|metadata| metadata.archive_info.managed_path())
+        .collect::<HashSet<PathBuf>>();
+    let now = SystemTime::now();
+
+    let mut orphans = Vec::new();
+    for entry in WalkDir::new(&archive_dir) {
+        let entry = entry.map_err(io::Error::from)?;
+        if !entry.file_type().is_file() || referenced.contains(entry.path()) {
+            continue;
+        }
+        let file_metadata = entry.metadata().map_err(io::Error::from)?;
+        let modified = file_metadata.modified()?;
+        if now.duration_since(modified).unwrap_or_default() < ORPHAN_GRACE {
+            continue;
+        }
+        let Ok(relative) = entry.path().strip_prefix(&root) else {
+            continue;
+        };
+        orphans.push(OrphanArchive {
+            path: relative.to_string_lossy().to_string(),
+            size: file_metadata.len(),
+            time_modified: modified.into(),
+            platform: guess_platform(relative),
+        });
+    }
+    orphans.sort_by_key(|orphan| std::cmp::Reverse(orphan.size));
+    Ok(orphans)
+}
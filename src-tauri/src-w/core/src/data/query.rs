@@ -0,0 +1,246 @@
+use crate::data::metadata::{ContentType, DistributionPlatform, Metadata};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub enum PlatformKind {
+    Unknown,
+    Steam,
+    DLSite,
+    Other,
+}
+
+impl PlatformKind {
+    pub fn of(platform: &DistributionPlatform) -> Self {
+        match platform {
+            DistributionPlatform::Unknown => PlatformKind::Unknown,
+            DistributionPlatform::Steam { .. } => PlatformKind::Steam,
+            DistributionPlatform::DLSite { .. } => PlatformKind::DLSite,
+            DistributionPlatform::Other { .. } => PlatformKind::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Default)]
+pub enum SortField {
+    #[default]
+    Title,
+    Size,
+    Created,
+    Updated,
+}
+
+/// Filters, sorting and pagination over the library, every field is optional
+/// and unset filters match everything
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LibraryQuery {
+    /// Whitespace separated terms, each has to be found case-insensitively in the title,
+    /// alias, description, developer, publisher, tags or platform id
+    pub text: Option<String>,
+
+    /// Any of these content types
+    pub content_type: Option<Vec<ContentType>>,
+
+    /// Any of these platforms
+    pub platform: Option<Vec<PlatformKind>>,
+
+    /// Whether the entry has at least one deployment
+    pub deployed: Option<bool>,
+
+    pub size_min: Option<u64>,
+    pub size_max: Option<u64>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub updated_after: Option<DateTime<Utc>>,
+    pub updated_before: Option<DateTime<Utc>>,
+
+    pub sort: SortField,
+    pub descending: bool,
+
+    /// Entries skipped before the page starts
+    pub offset: usize,
+
+    /// Size of the page, everything after `offset` if not set
+    pub limit: Option<usize>,
+}
+
+/// A page of query results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryPage {
+    /// Count of all matching entries, regardless of pagination
+    pub total: usize,
+    pub items: Vec<Metadata>,
+}
+
+impl LibraryQuery {
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        self.matches_text(metadata)
+            && self
+                .content_type
+                .as_ref()
+                .is_none_or(|types| types.contains(&metadata.content_type))
+            && self
+                .platform
+                .as_ref()
+                .is_none_or(|kinds| kinds.contains(&PlatformKind::of(&metadata.platform)))
+            && self
+                .deployed
+                .is_none_or(|deployed| deployed == metadata.is_deployed())
+            && self.size_min.is_none_or(|min| metadata.archive_size >= min)
+            && self.size_max.is_none_or(|max| metadata.archive_size <= max)
+            && self
+                .created_after
+                .is_none_or(|t| metadata.time_created >= t)
+            && self
+                .created_before
+                .is_none_or(|t| metadata.time_created <= t)
+            && self
+                .updated_after
+                .is_none_or(|t| metadata.time_updated >= t)
+            && self
+                .updated_before
+                .is_none_or(|t| metadata.time_updated <= t)
+    }
+
+    fn matches_text(&self, metadata: &Metadata) -> bool {
+        let Some(text) = self.text.as_deref().filter(|text| !text.trim().is_empty()) else {
+            return true;
+        };
+        let platform = match &metadata.platform {
+            DistributionPlatform::Unknown => None,
+            DistributionPlatform::Steam { id } | DistributionPlatform::DLSite { id } => {
+                Some(id.clone())
+            }
+            DistributionPlatform::Other { name, id } => {
+                Some(format!("{name} {}", id.as_deref().unwrap_or_default()))
+            }
+        };
+        let haystack = std::iter::once(&metadata.title)
+            .chain(&metadata.alias)
+            .chain(&metadata.tags)
+            .chain(metadata.description.as_ref())
+            .chain(metadata.developer.as_ref())
+            .chain(metadata.publisher.as_ref())
+            .chain(platform.as_ref())
+            .map(|field| field.to_lowercase())
+            .collect::<Vec<_>>();
+        text.split_whitespace().all(|term| {
+            let term = term.to_lowercase();
+            haystack.iter().any(|field| field.contains(&term))
+        })
+    }
+
+    fn compare(&self, a: &Metadata, b: &Metadata) -> Ordering {
+        let ordering = match self.sort {
+            SortField::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            SortField::Size => a.archive_size.cmp(&b.archive_size),
+            SortField::Created => a.time_created.cmp(&b.time_created),
+            SortField::Updated => a.time_updated.cmp(&b.time_updated),
+        };
+        // Keep the order stable between pages for equal keys
+        let ordering = ordering.then_with(|| a.id.cmp(&b.id));
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    /// Filter, sort and paginate `entries`
+    pub fn apply(&self, entries: Vec<Metadata>) -> QueryPage {
+        let mut items = entries
+            .into_iter()
+            .filter(|metadata| self.matches(metadata))
+            .collect::<Vec<_>>();
+        items.sort_by(|a, b| self.compare(a, b));
+        let total = items.len();
+        let items = items
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+        QueryPage { total, items }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::metadata::MetadataBuilder;
+
+    fn entry(id: &str, title: &str, size: u64) -> Metadata {
+        MetadataBuilder::default()
+            .id(id)
+            .title(title)
+            .archive_size(size)
+            .build()
+            .unwrap()
+    }
+
+    /// Ids of every page of `query` in turn, read from entries in the given order
+    fn pages(query: &LibraryQuery, entries: &[Metadata], limit: usize) -> Vec<String> {
+        let mut ids = Vec::new();
+        for offset in (0..entries.len()).step_by(limit) {
+            let query = LibraryQuery {
+                offset,
+                limit: Some(limit),
+                ..query.clone()
+            };
+            let page = query.apply(entries.to_vec());
+            assert_eq!(page.total, entries.len());
+            ids.extend(page.items.into_iter().map(|metadata| metadata.id));
+        }
+        ids
+    }
+
+    #[test]
+    fn paging_is_stable_over_equal_keys() {
+        let mut entries = ["e", "b", "d", "a", "c"]
+            .map(|id| entry(id, "Same Title", 10))
+            .to_vec();
+        let query = LibraryQuery::default();
+        let forward = pages(&query, &entries, 2);
+        assert_eq!(forward, ["a", "b", "c", "d", "e"]);
+
+        // The library hands entries over in no particular order
+        entries.reverse();
+        assert_eq!(pages(&query, &entries, 2), forward);
+
+        let query = LibraryQuery {
+            sort: SortField::Size,
+            descending: true,
+            ..Default::default()
+        };
+        assert_eq!(pages(&query, &entries, 3), ["e", "d", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn filters_count_before_paging() {
+        let entries = vec![
+            entry("a", "Alpha", 5),
+            entry("b", "Beta", 50),
+            entry("c", "alphabet", 500),
+        ];
+        let query = LibraryQuery {
+            text: Some(String::from("ALPHA")),
+            size_max: Some(100),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let page = query.apply(entries.clone());
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].id, "a");
+
+        let query = LibraryQuery {
+            text: Some(String::from("alpha")),
+            offset: 1,
+            ..Default::default()
+        };
+        let page = query.apply(entries);
+        assert_eq!(page.total, 2);
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].id, "c");
+    }
+}
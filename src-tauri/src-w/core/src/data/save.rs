@@ -0,0 +1,356 @@
+use crate::DIR_SAVES;
+use crate::util::config::config_get;
+use crate::util::path_ext::PathExt;
+use chrono::{DateTime, Utc};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use walkdir::WalkDir;
+
+const SNAPSHOT_INFO_FILE: &str = "snapshot.json";
+const SNAPSHOT_FILES_DIR: &str = "files";
+
+/// Save files of an entry copied out of its deploy directory at some point
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveSnapshot {
+    pub id: String,
+    pub time: DateTime<Utc>,
+
+    /// Paths relative to the deploy root, using `/` as separator
+    pub files: Vec<String>,
+    pub size: u64,
+}
+
+/// Snapshots of an entry are stored as `saves/{entry}/{snapshot}/` under the data root
+fn save_dir(entry_id: &str) -> PathBuf {
+    config_get()
+        .get_root()
+        .join(DIR_SAVES)
+        .join(sanitize(entry_id))
+}
+
+/// Keep entry ids from escaping the save store
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim_matches('.')
+        .to_string()
+}
+
+fn build_patterns(patterns: &[String]) -> SaveResult<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Files under `root` matching any of `patterns`, links are never followed
+pub fn save_match(root: &Path, patterns: &[String]) -> SaveResult<BTreeSet<String>> {
+    let set = build_patterns(patterns)?;
+    let mut matched = BTreeSet::new();
+    for entry in WalkDir::new(root).min_depth(1) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let name = entry.path().relative_name(root);
+        if set.is_match(&name) {
+            matched.insert(name);
+        }
+    }
+    Ok(matched)
+}
+
+/// Copy files under `root` matching `patterns` into a new snapshot,
+/// returns `None` if nothing matched.
+pub fn save_snapshot(
+    entry_id: &str,
+    root: &Path,
+    patterns: &[String],
+) -> SaveResult<Option<SaveSnapshot>> {
+    let files = save_match(root, patterns)?;
+    if files.is_empty() {
+        info!("No save files found in {}", root.display());
+        return Ok(None);
+    }
+    let snapshot = create_snapshot(entry_id, root, files)?;
+    info!(
+        "Saved {} files of {} into snapshot {}",
+        snapshot.files.len(),
+        entry_id,
+        snapshot.id
+    );
+    Ok(Some(snapshot))
+}
+
+fn create_snapshot(
+    entry_id: &str,
+    source_root: &Path,
+    files: BTreeSet<String>,
+) -> SaveResult<SaveSnapshot> {
+    let time = Utc::now();
+    let base = time.format("%Y%m%d-%H%M%S%3f").to_string();
+    let mut id = base.clone();
+    let mut suffix = 1;
+    while save_dir(entry_id).join(&id).exists() {
+        id = format!("{base}-{suffix}");
+        suffix += 1;
+    }
+    let snapshot_dir = save_dir(entry_id).join(&id);
+    let files_dir = snapshot_dir.join(SNAPSHOT_FILES_DIR);
+
+    let copied = files.iter().try_fold(0, |size, name| {
+        let target = files_dir.join(name);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok::<_, std::io::Error>(size + fs::copy(source_root.join(name), target)?)
+    });
+    let snapshot = copied.map(|size| SaveSnapshot {
+        id,
+        time,
+        files: files.into_iter().collect(),
+        size,
+    });
+    let written = snapshot.map_err(SaveError::from).and_then(|snapshot| {
+        fs::write(
+            snapshot_dir.join(SNAPSHOT_INFO_FILE),
+            serde_json::to_string_pretty(&snapshot)?,
+        )?;
+        Ok(snapshot)
+    });
+    if written.is_err() && snapshot_dir.exists() {
+        let _ = fs::remove_dir_all(&snapshot_dir);
+    }
+    written
+}
+
+/// All snapshots of an entry, the newest first
+pub fn save_list(entry_id: &str) -> SaveResult<Vec<SaveSnapshot>> {
+    let dir = save_dir(entry_id);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let info = entry?.path().join(SNAPSHOT_INFO_FILE);
+        match fs::read_to_string(&info)
+            .map_err(SaveError::from)
+            .and_then(|raw| Ok(serde_json::from_str::<SaveSnapshot>(&raw)?))
+        {
+            Ok(snapshot) => snapshots.push(snapshot),
+            Err(err) => warn!("Skipping invalid save snapshot {}: {}", info.display(), err),
+        }
+    }
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.time));
+    Ok(snapshots)
+}
+
+pub fn save_latest(entry_id: &str) -> SaveResult<Option<SaveSnapshot>> {
+    Ok(save_list(entry_id)?.into_iter().next())
+}
+
+fn find_snapshot(entry_id: &str, snapshot_id: &str) -> SaveResult<SaveSnapshot> {
+    save_list(entry_id)?
+        .into_iter()
+        .find(|snapshot| snapshot.id == snapshot_id)
+        .ok_or_else(|| SaveError::NotFound(snapshot_id.to_string()))
+}
+
+/// Whether the file is a symbolic link or has other hard links to it. Hard links cannot be
+/// told apart from plain files on other platforms, any file is taken as linked there.
+fn is_linked(meta: &fs::Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        meta.is_symlink() || meta.nlink() > 1
+    }
+    #[cfg(not(unix))]
+    {
+        !meta.is_dir()
+    }
+}
+
+/// Copy the files of a snapshot into `root`, overwriting existing ones and replacing links
+pub fn save_restore(entry_id: &str, snapshot: &SaveSnapshot, root: &Path) -> SaveResult<()> {
+    let files_dir = save_dir(entry_id)
+        .join(&snapshot.id)
+        .join(SNAPSHOT_FILES_DIR);
+    for name in &snapshot.files {
+        let target = root.join(name);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Copying writes through links, replace them so the file they point to stays intact
+        if target.symlink_metadata().is_ok_and(|meta| is_linked(&meta)) {
+            fs::remove_file(&target)?;
+        }
+        fs::copy(files_dir.join(name), target)?;
+    }
+    info!(
+        "Restored {} save files of {} from snapshot {} into {}",
+        snapshot.files.len(),
+        entry_id,
+        snapshot.id,
+        root.display()
+    );
+    Ok(())
+}
+
+/// Roll back to an older snapshot.
+///
+/// If the entry is deployed at `root` the files are restored right away,
+/// otherwise the snapshot is copied as the newest one so the next deploy picks it up.
+pub fn save_rollback(entry_id: &str, snapshot_id: &str, root: Option<&Path>) -> SaveResult<()> {
+    let snapshot = find_snapshot(entry_id, snapshot_id)?;
+    match root {
+        Some(root) => save_restore(entry_id, &snapshot, root),
+        None => {
+            let source = save_dir(entry_id)
+                .join(&snapshot.id)
+                .join(SNAPSHOT_FILES_DIR);
+            let copy = create_snapshot(entry_id, &source, snapshot.files.into_iter().collect())?;
+            info!(
+                "Snapshot {} of {} copied as {} for the next deploy",
+                snapshot_id, entry_id, copy.id
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Move all snapshots of `from_id` to `into_id`, e.g. when two entries are merged.
+/// Snapshot ids already used by `into_id` get a suffix.
+pub fn save_move(from_id: &str, into_id: &str) -> SaveResult<()> {
+    let from = save_dir(from_id);
+    if !from.exists() {
+        return Ok(());
+    }
+    let into = save_dir(into_id);
+    fs::create_dir_all(&into)?;
+    for entry in fs::read_dir(&from)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let mut target = into.join(&name);
+        let mut suffix = 1;
+        while target.exists() {
+            target = into.join(format!("{name}-{suffix}"));
+            suffix += 1;
+        }
+        fs::rename(entry.path(), &target)?;
+
+        // The id is stored in the snapshot itself too
+        let info = target.join(SNAPSHOT_INFO_FILE);
+        if let Ok(raw) = fs::read_to_string(&info) {
+            let mut snapshot = serde_json::from_str::<SaveSnapshot>(&raw)?;
+            snapshot.id = target
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            fs::write(&info, serde_json::to_string_pretty(&snapshot)?)?;
+        }
+    }
+    fs::remove_dir_all(&from)?;
+    info!("Moved save snapshots of {} to {}", from_id, into_id);
+    Ok(())
+}
+
+pub type SaveResult<T> = Result<T, SaveError>;
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("Save snapshot {0} not found")]
+    NotFound(String),
+
+    #[error("Invalid save path pattern: {0}")]
+    PatternError(#[from] globset::Error),
+
+    #[error("Failed with file system operation: {0}")]
+    FileSystemError(#[from] std::io::Error),
+
+    #[error("Failed with walking directory: {0}")]
+    WalkDirError(#[from] walkdir::Error),
+
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::config::config_init_test;
+    use tempfile::tempdir;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn save_match_follows_patterns_relative_to_root() {
+        let dir = tempdir().unwrap();
+        write(&dir.path().join("save/slot1.sav"), "1");
+        write(&dir.path().join("save/auto/slot2.sav"), "2");
+        write(&dir.path().join("save/readme.txt"), "");
+        write(&dir.path().join("config.ini"), "");
+        write(&dir.path().join("data/config.ini"), "");
+        let patterns = ["save/**/*.sav", "config.ini"].map(String::from);
+
+        let matched = save_match(dir.path(), &patterns).unwrap();
+        assert_eq!(
+            matched.into_iter().collect::<Vec<_>>(),
+            ["config.ini", "save/auto/slot2.sav", "save/slot1.sav"]
+        );
+        assert!(save_match(dir.path(), &[String::from("save/[")]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_restore_replaces_links_instead_of_writing_through() {
+        config_init_test();
+        let dir = tempdir().unwrap();
+        let deployed = dir.path().join("deployed");
+        write(&deployed.join("save/slot.sav"), "progress");
+        write(&deployed.join("save/hard.sav"), "progress");
+        let entry = format!("save-test-{}", uuid::Uuid::new_v4());
+        let patterns = [String::from("save/*.sav")];
+        let snapshot = save_snapshot(&entry, &deployed, &patterns)
+            .unwrap()
+            .unwrap();
+
+        // A later deployment links its files to ones outside of it
+        let outside = dir.path().join("outside");
+        write(&outside.join("soft.sav"), "outside");
+        write(&outside.join("hard.sav"), "outside");
+        let target = dir.path().join("target");
+        fs::create_dir_all(target.join("save")).unwrap();
+        std::os::unix::fs::symlink(outside.join("soft.sav"), target.join("save/slot.sav")).unwrap();
+        fs::hard_link(outside.join("hard.sav"), target.join("save/hard.sav")).unwrap();
+
+        save_restore(&entry, &snapshot, &target).unwrap();
+        for name in ["slot.sav", "hard.sav"] {
+            let restored = target.join("save").join(name);
+            assert!(!restored.symlink_metadata().unwrap().is_symlink());
+            assert_eq!(fs::read_to_string(restored).unwrap(), "progress");
+        }
+        assert_eq!(
+            fs::read_to_string(outside.join("soft.sav")).unwrap(),
+            "outside"
+        );
+        assert_eq!(
+            fs::read_to_string(outside.join("hard.sav")).unwrap(),
+            "outside"
+        );
+        fs::remove_dir_all(save_dir(&entry)).unwrap();
+    }
+}
@@ -0,0 +1,487 @@
+use crate::data::backup::backup_copy;
+use crate::data::index::{IndexError, index_rebuild};
+use crate::data::library::LIB_TABLE;
+use crate::data::manifest::{MANIFEST_TABLE, manifest_init};
+use crate::data::metadata::Metadata;
+use bson::{Bson, Document};
+use log::{info, warn};
+use redb::{Database, ReadTransaction, ReadableTable, TableDefinition, WriteTransaction};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+
+const META_TABLE: TableDefinition<&str, u64> = TableDefinition::new("META");
+const QUARANTINE_TABLE: TableDefinition<&str, Vec<u8>> = TableDefinition::new("QUARANTINE");
+
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// Backups taken before a migration live here, out of reach of the backup rotation
+pub(crate) const MIGRATION_BACKUP_DIR: &str = "migration";
+
+struct Migration {
+    description: &'static str,
+    apply: fn(&WriteTransaction) -> SchemaResult<()>,
+}
+
+/// Applied in order, the schema version of a database is the count of migrations applied to it
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "Move the single legacy deployment into the deployment list",
+        apply: migrate_legacy_deploy,
+    },
+    Migration {
+        description: "Build the secondary indexes",
+        apply: migrate_build_index,
+    },
+    Migration {
+        description: "Move deploy manifests out of the entries into their own table",
+        apply: migrate_manifest_table,
+    },
+];
+
+/// Schema version written by this build
+pub const SCHEMA_VERSION: u64 = MIGRATIONS.len() as u64;
+
+/// A stored record which could not be decoded, kept aside instead of failing the library
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantinedRecord {
+    pub id: String,
+
+    /// Why decoding as [`Metadata`] failed
+    pub error: String,
+
+    /// Record content as relaxed extended JSON, if it is a valid BSON document at all
+    pub document: Option<serde_json::Value>,
+}
+
+/// Schema version of the opened database, 0 for libraries from before versioning
+pub(crate) fn schema_version(read: &ReadTransaction) -> SchemaResult<u64> {
+    let table = match read.open_table(META_TABLE) {
+        Ok(table) => table,
+        // Written before the schema was versioned
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(table
+        .get(SCHEMA_VERSION_KEY)?
+        .map(|version| version.value())
+        .unwrap_or_default())
+}
+
+/// Bring the database up to [`SCHEMA_VERSION`], each migration commits on its own.
+///
+/// `backup` is the copy of the database taken on open, it is kept aside before migrating.
+pub(crate) fn schema_migrate(db: &Database, backup: Option<&Path>) -> SchemaResult<()> {
+    let write = db.begin_write()?;
+    write.open_table(META_TABLE)?;
+    write.open_table(QUARANTINE_TABLE)?;
+    write.commit()?;
+
+    let version = schema_version(&db.begin_read()?)?;
+    if version > SCHEMA_VERSION {
+        return Err(SchemaError::UnsupportedVersion(version));
+    }
+    if version == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    if let Some(backup) = backup {
+        keep_backup(backup, version)?;
+    }
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let target = index as u64 + 1;
+        info!(
+            "Migrating library to schema version {}: {}",
+            target, migration.description
+        );
+        let write = db.begin_write()?;
+        (migration.apply)(&write)?;
+        write
+            .open_table(META_TABLE)?
+            .insert(SCHEMA_VERSION_KEY, target)?;
+        write.commit()?;
+    }
+    info!(
+        "Library migrated from schema version {} to {}",
+        version, SCHEMA_VERSION
+    );
+    Ok(())
+}
+
+fn keep_backup(backup: &Path, version: u64) -> SchemaResult<()> {
+    let Some(backup_dir) = backup.parent() else {
+        return Ok(());
+    };
+    let target_dir = backup_dir.join(MIGRATION_BACKUP_DIR);
+    fs::create_dir_all(&target_dir)?;
+    let Some(name) = backup.file_name() else {
+        return Ok(());
+    };
+    let target = target_dir.join(format!("v{version}.{}", name.to_string_lossy()));
+    backup_copy(backup, &target)?;
+    info!("Kept pre-migration backup at {}", target.display());
+    Ok(())
+}
+
+/// Move the record stored at `key` into the quarantine, returns false if there was none
+pub(crate) fn quarantine_move(write: &WriteTransaction, key: &str) -> SchemaResult<bool> {
+    let raw = write
+        .open_table(LIB_TABLE)?
+        .remove(key)?
+        .map(|raw| raw.value());
+    let Some(raw) = raw else {
+        return Ok(false);
+    };
+    write.open_table(QUARANTINE_TABLE)?.insert(key, raw)?;
+    warn!("Moved undecodable metadata {} into quarantine", key);
+    Ok(true)
+}
+
+/// Take a record out of the quarantine, returns its raw content
+pub(crate) fn quarantine_take(
+    write: &WriteTransaction,
+    key: &str,
+) -> SchemaResult<Option<Vec<u8>>> {
+    let mut table = write.open_table(QUARANTINE_TABLE)?;
+    Ok(table.remove(key)?.map(|raw| raw.value()))
+}
+
+/// Take the quarantined records which decode now out of the quarantine
+pub(crate) fn quarantine_restore(write: &WriteTransaction) -> SchemaResult<Vec<Metadata>> {
+    let mut table = write.open_table(QUARANTINE_TABLE)?;
+    let mut restored = Vec::new();
+    for entry in table.iter()? {
+        let (key, raw) = entry?;
+        if let Ok(metadata) = bson::from_slice::<Metadata>(&raw.value()) {
+            restored.push((key.value().to_string(), metadata));
+        }
+    }
+    for (key, _) in &restored {
+        table.remove(key.as_str())?;
+    }
+    Ok(restored.into_iter().map(|(_, metadata)| metadata).collect())
+}
+
+pub(crate) fn quarantine_list(read: &ReadTransaction) -> SchemaResult<Vec<QuarantinedRecord>> {
+    let table = read.open_table(QUARANTINE_TABLE)?;
+    let mut records = Vec::new();
+    for entry in table.iter()? {
+        let (key, raw) = entry?;
+        let raw = raw.value();
+        let error = match bson::from_slice::<Metadata>(&raw) {
+            Ok(_) => String::from("Decodable now, retry to restore it"),
+            Err(e) => e.to_string(),
+        };
+        records.push(QuarantinedRecord {
+            id: key.value().to_string(),
+            error,
+            document: raw_document(&raw),
+        });
+    }
+    Ok(records)
+}
+
+/// A raw record as relaxed extended JSON, `None` if it is not even a BSON document
+pub(crate) fn raw_document(raw: &[u8]) -> Option<serde_json::Value> {
+    bson::from_slice::<Document>(raw)
+        .ok()
+        .map(|document| Bson::Document(document).into_relaxed_extjson())
+}
+
+/// Decode every stored entry, records failing to decode are quarantined
+pub(crate) fn decode_all(write: &WriteTransaction) -> SchemaResult<Vec<Metadata>> {
+    let mut result = Vec::new();
+    let mut failed = Vec::new();
+    {
+        let table = write.open_table(LIB_TABLE)?;
+        for entry in table.iter()? {
+            let (key, raw) = entry?;
+            match bson::from_slice::<Metadata>(&raw.value()) {
+                Ok(metadata) => result.push(metadata),
+                Err(e) => {
+                    warn!("Failed to deserialize Metadata {}: {}", key.value(), e);
+                    failed.push(key.value().to_string());
+                }
+            }
+        }
+    }
+    for key in failed {
+        quarantine_move(write, &key)?;
+    }
+    Ok(result)
+}
+
+fn raw_entries(write: &WriteTransaction) -> SchemaResult<Vec<(String, Vec<u8>)>> {
+    let mut raws = Vec::new();
+    let table = write.open_table(LIB_TABLE)?;
+    for entry in table.iter()? {
+        let (key, raw) = entry?;
+        raws.push((key.value().to_string(), raw.value()));
+    }
+    Ok(raws)
+}
+
+/// Write back an entry, with the manifests attached to it stored apart
+fn store_upgraded(write: &WriteTransaction, key: &str, mut metadata: Metadata) -> SchemaResult<()> {
+    for (deployment, manifest) in metadata.take_manifests() {
+        write
+            .open_table(MANIFEST_TABLE)?
+            .insert(deployment.as_str(), bson::to_vec(&manifest)?)?;
+    }
+    // The legacy fields are not serialized, writing back drops them
+    write
+        .open_table(LIB_TABLE)?
+        .insert(key, bson::to_vec(&metadata)?)?;
+    Ok(())
+}
+
+fn migrate_legacy_deploy(write: &WriteTransaction) -> SchemaResult<()> {
+    let mut upgraded = 0;
+    for (key, raw) in raw_entries(write)? {
+        let Ok(document) = bson::from_slice::<Document>(&raw) else {
+            quarantine_move(write, &key)?;
+            continue;
+        };
+        if !document.contains_key("deploy_info") && !document.contains_key("deploy_manifest") {
+            continue;
+        }
+        let Ok(mut metadata) = bson::from_document::<Metadata>(document) else {
+            quarantine_move(write, &key)?;
+            continue;
+        };
+        metadata.upgrade_legacy_deploy();
+        store_upgraded(write, &key, metadata)?;
+        upgraded += 1;
+    }
+    info!("Upgraded {} entries with a legacy deployment", upgraded);
+    Ok(())
+}
+
+fn migrate_build_index(write: &WriteTransaction) -> SchemaResult<()> {
+    let entries = decode_all(write)?;
+    index_rebuild(write, &entries)?;
+    Ok(())
+}
+
+fn migrate_manifest_table(write: &WriteTransaction) -> SchemaResult<()> {
+    manifest_init(write)?;
+    let mut moved = 0;
+    for (key, raw) in raw_entries(write)? {
+        let Ok(document) = bson::from_slice::<Document>(&raw) else {
+            quarantine_move(write, &key)?;
+            continue;
+        };
+        let embedded = document.get_array("deployments").is_ok_and(|deployments| {
+            deployments.iter().any(|deployment| {
+                deployment
+                    .as_document()
+                    .is_some_and(|deployment| deployment.contains_key("manifest"))
+            })
+        });
+        if !embedded {
+            continue;
+        }
+        let Ok(metadata) = bson::from_document::<Metadata>(document) else {
+            quarantine_move(write, &key)?;
+            continue;
+        };
+        store_upgraded(write, &key, metadata)?;
+        moved += 1;
+    }
+    info!("Moved the deploy manifests of {} entries", moved);
+    Ok(())
+}
+
+pub(crate) type SchemaResult<T> = Result<T, SchemaError>;
+
+#[derive(Debug, Error)]
+pub enum SchemaError {
+    #[error("Library schema version {0} is newer than supported, please update the application")]
+    UnsupportedVersion(u64),
+
+    #[error("Library index error: {0}")]
+    IndexError(#[from] IndexError),
+
+    #[error("Database transaction error: {0}")]
+    TransactionError(Box<redb::TransactionError>),
+
+    #[error("Database table error: {0}")]
+    TableError(#[from] redb::TableError),
+
+    #[error("Database storage error: {0}")]
+    StorageError(#[from] redb::StorageError),
+
+    #[error("Database commit error: {0}")]
+    CommitError(#[from] redb::CommitError),
+
+    #[error("BSON serialization error: {0}")]
+    SerializationError(#[from] bson::ser::Error),
+
+    #[error("File system error: {0}")]
+    FileSystemError(#[from] std::io::Error),
+}
+
+impl From<redb::TransactionError> for SchemaError {
+    fn from(e: redb::TransactionError) -> Self {
+        SchemaError::TransactionError(Box::new(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::index::{LibraryIndex, index_lookup};
+    use crate::data::manifest::{DeployManifest, ManifestEntry, manifest_get};
+    use crate::data::metadata::{DeployInfo, DeployMode, MetadataBuilder};
+    use tempfile::tempdir;
+
+    fn manifest(path: &str) -> DeployManifest {
+        DeployManifest {
+            entries: vec![ManifestEntry::File {
+                path: path.to_string(),
+                size: 1,
+                hash: String::from("hash"),
+            }],
+        }
+    }
+
+    fn entry(id: &str) -> Metadata {
+        MetadataBuilder::default()
+            .id(id)
+            .title(id)
+            .tags(vec![String::from("tag")])
+            .build()
+            .unwrap()
+    }
+
+    fn insert(db: &Database, records: &[(&str, Vec<u8>)]) {
+        let write = db.begin_write().unwrap();
+        {
+            let mut table = write.open_table(LIB_TABLE).unwrap();
+            for (key, raw) in records {
+                table.insert(*key, raw.clone()).unwrap();
+            }
+        }
+        write.commit().unwrap();
+    }
+
+    fn stored(db: &Database, key: &str) -> Document {
+        let read = db.begin_read().unwrap();
+        let raw = read
+            .open_table(LIB_TABLE)
+            .unwrap()
+            .get(key)
+            .unwrap()
+            .unwrap();
+        bson::from_slice(&raw.value()).unwrap()
+    }
+
+    #[test]
+    fn migrate_from_unversioned() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("library.bin");
+        let db = Database::create(&path).unwrap();
+        let mut legacy = bson::to_document(&entry("legacy")).unwrap();
+        legacy.insert(
+            "deploy_info",
+            bson::to_bson(&DeployInfo::Directory {
+                path: String::from("/games"),
+                mode: DeployMode::Copy,
+            })
+            .unwrap(),
+        );
+        legacy.insert(
+            "deploy_manifest",
+            bson::to_bson(&manifest("a.txt")).unwrap(),
+        );
+        insert(
+            &db,
+            &[
+                ("legacy", bson::to_vec(&legacy).unwrap()),
+                ("plain", bson::to_vec(&entry("plain")).unwrap()),
+                ("broken", vec![1, 2, 3]),
+            ],
+        );
+        let backup = dir.path().join("library.backup.bin");
+        fs::copy(&path, &backup).unwrap();
+
+        schema_migrate(&db, Some(&backup)).unwrap();
+        let read = db.begin_read().unwrap();
+        assert_eq!(schema_version(&read).unwrap(), SCHEMA_VERSION);
+        assert!(
+            dir.path()
+                .join(MIGRATION_BACKUP_DIR)
+                .join("v0.library.backup.bin")
+                .exists()
+        );
+
+        let document = stored(&db, "legacy");
+        assert!(!document.contains_key("deploy_info"));
+        assert!(!document.contains_key("deploy_manifest"));
+        let metadata: Metadata = bson::from_document(document).unwrap();
+        assert_eq!(metadata.deployments.len(), 1);
+        assert_eq!(metadata.deployments[0].manifest, None);
+        let deployment = &metadata.deployments[0].id;
+        assert_eq!(
+            manifest_get(&read, deployment).unwrap(),
+            Some(manifest("a.txt"))
+        );
+
+        let quarantined = quarantine_list(&read).unwrap();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].id, "broken");
+        assert!(
+            read.open_table(LIB_TABLE)
+                .unwrap()
+                .get("broken")
+                .unwrap()
+                .is_none()
+        );
+
+        let mut tagged = index_lookup(&read, LibraryIndex::Tag, "tag").unwrap();
+        tagged.sort();
+        assert_eq!(tagged, ["legacy", "plain"]);
+    }
+
+    #[test]
+    fn migrate_embedded_manifests() {
+        let dir = tempdir().unwrap();
+        let db = Database::create(dir.path().join("library.bin")).unwrap();
+        let write = db.begin_write().unwrap();
+        write
+            .open_table(META_TABLE)
+            .unwrap()
+            .insert(SCHEMA_VERSION_KEY, 2)
+            .unwrap();
+        write.commit().unwrap();
+
+        let mut metadata = entry("deployed");
+        metadata.deploy_info = DeployInfo::Directory {
+            path: String::from("/games"),
+            mode: DeployMode::Copy,
+        };
+        metadata.upgrade_legacy_deploy();
+        let id = metadata.deployments[0].id.clone();
+        let mut document = bson::to_document(&metadata).unwrap();
+        let deployments = document.get_array_mut("deployments").unwrap();
+        deployments[0]
+            .as_document_mut()
+            .unwrap()
+            .insert("manifest", bson::to_bson(&manifest("b.txt")).unwrap());
+        insert(&db, &[("deployed", bson::to_vec(&document).unwrap())]);
+
+        schema_migrate(&db, None).unwrap();
+        let read = db.begin_read().unwrap();
+        assert_eq!(schema_version(&read).unwrap(), SCHEMA_VERSION);
+        let document = stored(&db, "deployed");
+        let deployments = document.get_array("deployments").unwrap();
+        assert!(
+            !deployments[0]
+                .as_document()
+                .unwrap()
+                .contains_key("manifest")
+        );
+        assert_eq!(manifest_get(&read, &id).unwrap(), Some(manifest("b.txt")));
+    }
+}
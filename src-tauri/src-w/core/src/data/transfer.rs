@@ -0,0 +1,394 @@
+use crate::data::metadata::Metadata;
+use crate::data::query::LibraryQuery;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::Path;
+use thiserror::Error;
+
+/// Columns holding nested values, written as JSON text in a CSV cell
+const CSV_JSON_COLUMNS: [&str; 6] = [
+    "alias",
+    "tags",
+    "platform",
+    "archive_info",
+    "deployments",
+    "save_paths",
+];
+
+/// Columns holding numbers, any other column is a plain string
+const CSV_NUMBER_COLUMNS: [&str; 1] = ["archive_size"];
+
+/// File format of an export or import
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Default)]
+pub enum TransferFormat {
+    /// An array of entries, as written by earlier versions
+    #[default]
+    Json,
+
+    /// One entry per line
+    JsonLines,
+
+    /// One entry per row, nested fields as JSON text
+    Csv,
+
+    /// Entries as an array of `[[metadata]]` tables
+    Toml,
+}
+
+impl TransferFormat {
+    /// Guess the format from the file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        match ext.as_str() {
+            "json" => Some(TransferFormat::Json),
+            "jsonl" | "ndjson" => Some(TransferFormat::JsonLines),
+            "csv" => Some(TransferFormat::Csv),
+            "toml" => Some(TransferFormat::Toml),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TransferFormat::Json => "json",
+            TransferFormat::JsonLines => "jsonl",
+            TransferFormat::Csv => "csv",
+            TransferFormat::Toml => "toml",
+        }
+    }
+}
+
+/// Subset of the library to export, everything if no field is set
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ExportFilter {
+    /// Only these entries
+    pub ids: Option<Vec<String>>,
+
+    /// Only entries matching the query, in its order
+    pub query: Option<LibraryQuery>,
+}
+
+impl ExportFilter {
+    pub(crate) fn apply(&self, entries: Vec<Metadata>) -> Vec<Metadata> {
+        let mut entries = match &self.query {
+            Some(query) => query.apply(entries).items,
+            None => entries,
+        };
+        if let Some(ids) = &self.ids {
+            entries.retain(|metadata| ids.contains(&metadata.id));
+        }
+        entries
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportReport {
+    pub path: String,
+    pub format: TransferFormat,
+
+    /// Count of entries exported
+    pub entries: usize,
+}
+
+/// Wrapper giving the entries a key, TOML has no top-level arrays
+#[derive(Serialize, Deserialize)]
+struct TomlDocument {
+    #[serde(default)]
+    metadata: Vec<Metadata>,
+}
+
+pub(crate) fn transfer_encode(
+    entries: &[Metadata],
+    format: TransferFormat,
+) -> TransferResult<String> {
+    match format {
+        TransferFormat::Json => Ok(serde_json::to_string_pretty(entries)?),
+        TransferFormat::JsonLines => {
+            let mut out = String::new();
+            for metadata in entries {
+                out.push_str(&serde_json::to_string(metadata)?);
+                out.push('\n');
+            }
+            Ok(out)
+        }
+        TransferFormat::Csv => csv_encode(entries),
+        TransferFormat::Toml => Ok(toml::to_string(&TomlDocument {
+            metadata: entries.to_vec(),
+        })?),
+    }
+}
+
+pub(crate) fn transfer_decode(data: &str, format: TransferFormat) -> TransferResult<Vec<Metadata>> {
+    match format {
+        TransferFormat::Json => Ok(serde_json::from_str(data)?),
+        TransferFormat::JsonLines => data
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|e| TransferError::LineError(index + 1, e))
+            })
+            .collect(),
+        TransferFormat::Csv => csv_decode(data),
+        TransferFormat::Toml => Ok(toml::from_str::<TomlDocument>(data)?.metadata),
+    }
+}
+
+fn csv_escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+fn csv_encode(entries: &[Metadata]) -> TransferResult<String> {
+    let rows = entries
+        .iter()
+        .map(|metadata| match serde_json::to_value(metadata)? {
+            Value::Object(row) => Ok(row),
+            _ => Ok(Map::new()),
+        })
+        .collect::<TransferResult<Vec<_>>>()?;
+    let mut columns: Vec<&String> = Vec::new();
+    for row in &rows {
+        for key in row.keys() {
+            if !columns.contains(&key) {
+                columns.push(key);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    let header = columns.iter().map(|column| csv_escape(column));
+    out.push_str(&header.collect::<Vec<_>>().join(","));
+    out.push_str("\r\n");
+    for row in &rows {
+        let cells = columns.iter().map(|column| {
+            let cell = match row.get(column.as_str()) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(value)) if !CSV_JSON_COLUMNS.contains(&column.as_str()) => {
+                    value.clone()
+                }
+                Some(value) => value.to_string(),
+            };
+            csv_escape(&cell)
+        });
+        out.push_str(&cells.collect::<Vec<_>>().join(","));
+        out.push_str("\r\n");
+    }
+    Ok(out)
+}
+
+/// Split CSV text into records of cells, quoted cells may span lines
+pub(crate) fn csv_records(data: &str) -> TransferResult<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    cell.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if cell.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut cell)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut cell));
+                records.push(std::mem::take(&mut record));
+                line += 1;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                cell.push(c);
+            }
+        }
+    }
+    if quoted {
+        return Err(TransferError::CsvError(
+            line,
+            "unterminated quoted cell".to_string(),
+        ));
+    }
+    if !cell.is_empty() || !record.is_empty() {
+        record.push(cell);
+        records.push(record);
+    }
+    records.retain(|record| !(record.len() == 1 && record[0].is_empty()));
+    Ok(records)
+}
+
+fn csv_decode(data: &str) -> TransferResult<Vec<Metadata>> {
+    let mut records = csv_records(data.trim_start_matches('\u{feff}'))?.into_iter();
+    let Some(header) = records.next() else {
+        return Ok(Vec::new());
+    };
+    let mut entries = Vec::new();
+    for (index, record) in records.enumerate() {
+        // The header is the first row
+        let row_number = index + 2;
+        let mut row = Map::new();
+        for (column, cell) in header.iter().zip(record) {
+            if cell.is_empty() {
+                continue;
+            }
+            let value = if CSV_JSON_COLUMNS.contains(&column.as_str()) {
+                serde_json::from_str(&cell)
+                    .map_err(|e| TransferError::CsvError(row_number, format!("{column}: {e}")))?
+            } else if CSV_NUMBER_COLUMNS.contains(&column.as_str()) {
+                let number = cell
+                    .trim()
+                    .parse::<u64>()
+                    .map_err(|e| TransferError::CsvError(row_number, format!("{column}: {e}")))?;
+                Value::from(number)
+            } else {
+                Value::String(cell)
+            };
+            row.insert(column.clone(), value);
+        }
+        let metadata = serde_json::from_value(Value::Object(row))
+            .map_err(|e| TransferError::CsvError(row_number, e.to_string()))?;
+        entries.push(metadata);
+    }
+    Ok(entries)
+}
+
+pub(crate) type TransferResult<T> = Result<T, TransferError>;
+
+#[derive(Debug, Error)]
+pub enum TransferError {
+    #[error("Unknown file format of {0}, expected json, jsonl, csv or toml")]
+    UnknownFormat(String),
+
+    #[error("Invalid entry on line {0}: {1}")]
+    LineError(usize, serde_json::Error),
+
+    #[error("Invalid CSV on row {0}: {1}")]
+    CsvError(usize, String),
+
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("TOML serialization error: {0}")]
+    TomlSerializationError(#[from] toml::ser::Error),
+
+    #[error("TOML deserialization error: {0}")]
+    TomlDeserializationError(#[from] toml::de::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::metadata::{
+        ArchiveInfo, ContentType, DeployInfo, DeployMode, Deployment, DistributionPlatform,
+        MetadataBuilder,
+    };
+    use crate::util::compress::ArchiveFormat;
+    use chrono::Utc;
+
+    fn entries() -> Vec<Metadata> {
+        let full = MetadataBuilder::default()
+            .title("Title, with \"quotes\"")
+            .alias(vec![String::from("別名")])
+            .tags(vec![String::from("a"), String::from("b")])
+            .content_type(ContentType::Game)
+            .platform(DistributionPlatform::DLSite {
+                id: String::from("RJ01000000"),
+            })
+            .description(String::from("First line\r\nsecond line"))
+            .archive_info(ArchiveInfo::ArchiveFile {
+                path: String::from("archive/game.7z"),
+                password: Some(String::from("secret")),
+                format: ArchiveFormat::default(),
+            })
+            .archive_size(1024u64)
+            .archive_hash(String::from("abc"))
+            .deployments(vec![Deployment {
+                id: String::from("deployment"),
+                info: DeployInfo::Directory {
+                    path: String::from("/games"),
+                    mode: DeployMode::Copy,
+                },
+                time: Utc::now(),
+                manifest: None,
+            }])
+            .save_paths(vec![String::from("saves/*.sav")])
+            .build()
+            .unwrap();
+        let bare = MetadataBuilder::default().title("Bare").build().unwrap();
+        vec![full, bare]
+    }
+
+    fn round_trip(format: TransferFormat) {
+        let entries = entries();
+        let data = transfer_encode(&entries, format).unwrap();
+        assert_eq!(transfer_decode(&data, format).unwrap(), entries);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        round_trip(TransferFormat::Json);
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        round_trip(TransferFormat::JsonLines);
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        round_trip(TransferFormat::Csv);
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        round_trip(TransferFormat::Toml);
+    }
+
+    #[test]
+    fn csv_records_quoting() {
+        let data = "a,\"b,c\",\"d \"\"e\"\"\"\r\n\"multi\nline\",,f\n\n";
+        let records = csv_records(data).unwrap();
+        assert_eq!(
+            records,
+            [vec!["a", "b,c", "d \"e\""], vec!["multi\nline", "", "f"],]
+        );
+        assert_eq!(csv_records("a,b").unwrap(), [vec!["a", "b"]]);
+        assert!(matches!(
+            csv_records("a\n\"open\nstill open"),
+            Err(TransferError::CsvError(3, _))
+        ));
+    }
+
+    #[test]
+    fn csv_decode_reports_rows() {
+        let data = "\u{feff}title,tags,archive_size\r\nKept,,\r\nBroken,[\"a\"],many\r\n";
+        assert!(matches!(
+            csv_decode(data),
+            Err(TransferError::CsvError(3, _))
+        ));
+
+        let data = "title,tags,unknown\r\nOne,\"[\"\"a\"\"]\",ignored\r\n";
+        let entries = csv_decode(data).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].title, "One");
+        assert_eq!(entries[0].tags, ["a"]);
+
+        let data = "title,tags\r\nBad,[a\r\n";
+        assert!(matches!(
+            csv_decode(data),
+            Err(TransferError::CsvError(2, _))
+        ));
+    }
+}
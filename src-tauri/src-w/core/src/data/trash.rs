@@ -0,0 +1,71 @@
+use crate::data::metadata::Metadata;
+use chrono::{DateTime, Utc};
+use redb::{ReadTransaction, ReadableTable, TableDefinition, WriteTransaction};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use thiserror::Error;
+
+const TRASH_TABLE: TableDefinition<&str, Vec<u8>> = TableDefinition::new("TRASH");
+
+/// A removed entry kept until restored or purged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub metadata: Metadata,
+    pub time_deleted: DateTime<Utc>,
+}
+
+/// Create the trash table if missing
+pub(crate) fn trash_init(write: &WriteTransaction) -> Result<(), redb::TableError> {
+    write.open_table(TRASH_TABLE)?;
+    Ok(())
+}
+
+pub(crate) fn trash_insert(write: &WriteTransaction, metadata: &Metadata) -> TrashResult<()> {
+    let entry = TrashEntry {
+        metadata: metadata.clone(),
+        time_deleted: Utc::now(),
+    };
+    let mut table = write.open_table(TRASH_TABLE)?;
+    table.insert(metadata.id.as_str(), bson::to_vec(&entry)?)?;
+    Ok(())
+}
+
+/// Take an entry out of the trash, `None` if it is not there
+pub(crate) fn trash_take(write: &WriteTransaction, key: &str) -> TrashResult<Option<TrashEntry>> {
+    let raw = {
+        let mut table = write.open_table(TRASH_TABLE)?;
+        table.remove(key)?.map(|raw| raw.value())
+    };
+    Ok(raw.map(|raw| bson::from_slice(&raw)).transpose()?)
+}
+
+/// Trashed entries, the latest removed first
+pub(crate) fn trash_list(read: &ReadTransaction) -> TrashResult<Vec<TrashEntry>> {
+    let table = read.open_table(TRASH_TABLE)?;
+    let mut entries = Vec::new();
+    for entry in table.iter()? {
+        entries.push(bson::from_slice::<TrashEntry>(&entry?.1.value())?);
+    }
+    entries.sort_by_key(|entry| Reverse(entry.time_deleted));
+    Ok(entries)
+}
+
+pub(crate) type TrashResult<T> = Result<T, TrashError>;
+
+#[derive(Debug, Error)]
+pub enum TrashError {
+    #[error("Metadata {0} is not in the trash")]
+    NotFound(String),
+
+    #[error("Trash table error: {0}")]
+    TableError(#[from] redb::TableError),
+
+    #[error("Trash storage error: {0}")]
+    StorageError(#[from] redb::StorageError),
+
+    #[error("BSON serialization error: {0}")]
+    SerializationError(#[from] bson::ser::Error),
+
+    #[error("BSON deserialization error: {0}")]
+    DeserializationError(#[from] bson::de::Error),
+}
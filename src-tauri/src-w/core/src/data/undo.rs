@@ -0,0 +1,135 @@
+use crate::data::history::ChangeOperation;
+use crate::data::metadata::Metadata;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+
+/// Steps kept for undo, the oldest are dropped beyond this
+const UNDO_LIMIT: usize = 20;
+
+static STACK: Mutex<UndoStack> = Mutex::new(UndoStack {
+    undo: VecDeque::new(),
+    redo: Vec::new(),
+});
+
+/// One entry changed by a step, unset on a side if the entry did not exist
+#[derive(Debug, Clone)]
+pub(crate) struct EntryChange {
+    pub key: String,
+    pub before: Option<Metadata>,
+    pub after: Option<Metadata>,
+
+    /// Whether the entry went into the trash when removed, so a redo does the same
+    pub trashed: bool,
+}
+
+/// Entries changed together by one library operation
+#[derive(Debug, Clone)]
+pub(crate) struct UndoStep {
+    pub operation: ChangeOperation,
+    pub time: DateTime<Utc>,
+    pub changes: Vec<EntryChange>,
+}
+
+impl UndoStep {
+    pub fn summary(&self) -> UndoSummary {
+        UndoSummary {
+            operation: self.operation,
+            time: self.time,
+            entries: self.changes.len(),
+        }
+    }
+
+    /// Changes doing the opposite, in reverse order
+    pub fn inverse(&self) -> Vec<EntryChange> {
+        self.changes
+            .iter()
+            .rev()
+            .map(|change| EntryChange {
+                key: change.key.clone(),
+                before: change.after.clone(),
+                after: change.before.clone(),
+                trashed: false,
+            })
+            .collect()
+    }
+}
+
+pub(crate) struct UndoStack {
+    undo: VecDeque<UndoStep>,
+    redo: Vec<UndoStep>,
+}
+
+impl UndoStack {
+    /// Record a new step, which invalidates everything undone before
+    pub fn push(&mut self, step: UndoStep) {
+        self.redo.clear();
+        self.undo.push_back(step);
+        while self.undo.len() > UNDO_LIMIT {
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn pop_undo(&mut self) -> Option<UndoStep> {
+        self.undo.pop_back()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<UndoStep> {
+        self.redo.pop()
+    }
+
+    /// Put back a step which was undone, or one which failed to redo on a database error
+    pub fn push_redo(&mut self, step: UndoStep) {
+        self.redo.push(step);
+    }
+
+    /// Put back a step which was redone, or one which failed to undo on a database error
+    pub fn push_undo(&mut self, step: UndoStep) {
+        self.undo.push_back(step);
+    }
+
+    pub fn state(&self) -> UndoState {
+        UndoState {
+            undo: self.undo.back().map(UndoStep::summary),
+            redo: self.redo.last().map(UndoStep::summary),
+            undo_count: self.undo.len(),
+            redo_count: self.redo.len(),
+        }
+    }
+}
+
+/// Whether an operation is recorded for undo, deployments touch the file system and are not
+pub(crate) fn undoable(operation: ChangeOperation) -> bool {
+    matches!(
+        operation,
+        ChangeOperation::Create
+            | ChangeOperation::Patch
+            | ChangeOperation::Remove
+            | ChangeOperation::Clear
+            | ChangeOperation::Import
+            | ChangeOperation::BackupRestore
+    )
+}
+
+pub(crate) fn undo_stack() -> MutexGuard<'static, UndoStack> {
+    STACK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoSummary {
+    pub operation: ChangeOperation,
+    pub time: DateTime<Utc>,
+
+    /// Count of entries changed by the step
+    pub entries: usize,
+}
+
+/// What undo and redo would apply next
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoState {
+    pub undo: Option<UndoSummary>,
+    pub redo: Option<UndoSummary>,
+    pub undo_count: usize,
+    pub redo_count: usize,
+}
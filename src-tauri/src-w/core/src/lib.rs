@@ -7,3 +7,6 @@ pub enum Whether<A, B> {
 }
 
 pub const DIR_ARCHIVE: &str = "archive";
+pub const DIR_COVER: &str = "cover";
+pub const DIR_SAVES: &str = "saves";
+pub const DIR_STAGING: &str = "staging";
@@ -0,0 +1,99 @@
+use crate::util::compress::{CompressError, CompressResult};
+use crate::util::create_hidden_command;
+use std::env::current_dir;
+use std::path::Path;
+use std::process::Command;
+
+/// Find if 7z exists in PATH
+pub(super) fn exist_7z() -> bool {
+    match Command::new("7z").arg("--help").output() {
+        Ok(out) => out.status.success(),
+        Err(_) => false,
+    }
+}
+
+pub(super) fn compress(
+    input_path: &Path,
+    output_path: &Path,
+    password: Option<&str>,
+    compression_level: u32,
+) -> CompressResult<()> {
+    if !exist_7z() {
+        return Err(CompressError::CliNotFound);
+    }
+
+    let mut command = create_hidden_command("7z");
+
+    command.arg("a").arg(format!("-mx={compression_level}"));
+
+    if let Some(pwd) = password {
+        command.arg(format!("-p{pwd}")).arg("-mhe=on");
+    }
+
+    command.arg(current_dir()?.join(output_path));
+    command.current_dir(input_path);
+    command.arg("*");
+
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(CompressError::CliError(format!(
+            "Failed in 7z compression command: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(())
+}
+
+pub(super) fn decompress(
+    input_path: &Path,
+    output_path: &Path,
+    password: Option<&str>,
+) -> CompressResult<()> {
+    if !exist_7z() {
+        return Err(CompressError::CliNotFound);
+    }
+
+    let mut command = create_hidden_command("7z");
+    command
+        .arg("x")
+        .arg(input_path)
+        .arg(format!("-o{}", output_path.display()))
+        .arg("-aoa");
+
+    if let Some(pwd) = password {
+        command.arg(format!("-p{pwd}"));
+    }
+
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(CompressError::CliError(format!(
+            "Failed in 7z decompression command: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(())
+}
+
+pub(super) fn test(input_path: &Path, password: Option<&str>) -> CompressResult<()> {
+    if !exist_7z() {
+        return Err(CompressError::CliNotFound);
+    }
+
+    let mut command = create_hidden_command("7z");
+    command.arg("t").arg(input_path);
+
+    // An empty password makes 7z fail instead of prompting for one
+    command.arg(format!("-p{}", password.unwrap_or_default()));
+
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(CompressError::CliError(format!(
+            "Failed in 7z test command: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(())
+}
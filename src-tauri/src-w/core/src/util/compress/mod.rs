@@ -0,0 +1,433 @@
+mod cli;
+mod seven_z;
+mod tar_zst;
+mod zip;
+
+use crate::util::path_ext::PathExt;
+use crate::util::progress::ProgressHandle;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Default)]
+pub enum ArchiveFormat {
+    #[default]
+    SevenZ,
+    Zip,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::SevenZ => "7z",
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::TarZst => "tar.zst",
+        }
+    }
+
+    /// Guess the format from the file name, `None` if the extension is unknown
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".7z") {
+            Some(ArchiveFormat::SevenZ)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(ArchiveFormat::TarZst)
+        } else {
+            None
+        }
+    }
+
+    pub fn supports_password(&self) -> bool {
+        match self {
+            ArchiveFormat::SevenZ | ArchiveFormat::Zip => true,
+            ArchiveFormat::TarZst => false,
+        }
+    }
+}
+
+pub type CompressResult<T> = Result<T, CompressError>;
+
+#[derive(Debug, Error)]
+pub enum CompressError {
+    #[error("Failed with file system operation: {0}")]
+    FileSystemError(#[from] std::io::Error),
+
+    #[error("Failed with walking directory: {0}")]
+    WalkDirError(#[from] walkdir::Error),
+
+    #[error("Failed with 7z archive operation: {0}")]
+    SevenZError(#[from] sevenz_rust::Error),
+
+    #[error("Failed with zip archive operation: {0}")]
+    ZipError(#[from] ::zip::result::ZipError),
+
+    #[error("Archive format {0:?} does not support password")]
+    PasswordUnsupported(ArchiveFormat),
+
+    #[error("Operation cancelled")]
+    Cancelled,
+
+    #[error("7z not found in path")]
+    CliNotFound,
+
+    #[error("{0}")]
+    CliError(String),
+}
+
+/// Compress all contents of `input_dir` into an archive of `format` at `output_file`.
+///
+/// Archives are created in process, the 7z CLI is only used as a fallback
+/// for the 7z format when the native backend fails and 7z could be found in PATH.
+/// The output file is removed if the operation fails or is cancelled.
+pub fn compress(
+    input_dir: impl AsRef<Path>,
+    output_file: impl AsRef<Path>,
+    format: ArchiveFormat,
+    password: Option<&str>,
+    compression_level: Option<u32>,
+    progress: &ProgressHandle,
+) -> CompressResult<()> {
+    let input_path = input_dir.as_ref();
+    let output_path = output_file.as_ref();
+    let compression_level = compression_level.unwrap_or(9).min(9);
+
+    if password.is_some() && !format.supports_password() {
+        return Err(CompressError::PasswordUnsupported(format));
+    }
+
+    let (bytes, files) = measure_dir(input_path)?;
+    progress.set_total(bytes, files);
+
+    let result = match format {
+        ArchiveFormat::SevenZ => match seven_z::compress(
+            input_path,
+            output_path,
+            password,
+            compression_level,
+            progress,
+        ) {
+            Err(err) if !progress.is_cancelled() && cli::exist_7z() => {
+                warn!("Native 7z compression failed, falling back to 7z CLI: {err}");
+                if output_path.exists() {
+                    fs::remove_file(output_path)?;
+                }
+                cli::compress(input_path, output_path, password, compression_level)
+            }
+            result => result,
+        },
+        ArchiveFormat::Zip => zip::compress(
+            input_path,
+            output_path,
+            password,
+            compression_level,
+            progress,
+        ),
+        ArchiveFormat::TarZst => {
+            tar_zst::compress(input_path, output_path, compression_level, progress)
+        }
+    };
+
+    match result {
+        Ok(_) => {
+            progress.flush();
+            Ok(())
+        }
+        Err(err) => {
+            if output_path.exists() {
+                let _ = fs::remove_file(output_path);
+            }
+            Err(cancelled_or(err, progress))
+        }
+    }
+}
+
+/// Extract an archive of `format` at `input_file` into `output_dir`, overwriting existing files.
+///
+/// 7z archives using methods unsupported by the native backend (e.g. PPMd, BCJ2)
+/// will be handled by the 7z CLI if it could be found in PATH.
+/// If `output_dir` was empty before, it is emptied again when the operation fails or is cancelled.
+pub fn decompress(
+    input_file: impl AsRef<Path>,
+    output_dir: impl AsRef<Path>,
+    format: ArchiveFormat,
+    password: Option<&str>,
+    progress: &ProgressHandle,
+) -> CompressResult<()> {
+    let input_path = input_file.as_ref();
+    let output_path = output_dir.as_ref();
+    let output_existed = output_path.exists();
+    let output_fresh = !output_existed || output_path.is_dir_empty();
+
+    let result = match format {
+        ArchiveFormat::SevenZ => {
+            match seven_z::decompress(input_path, output_path, password, progress) {
+                Err(err) if !progress.is_cancelled() && cli::exist_7z() => {
+                    warn!("Native 7z decompression failed, falling back to 7z CLI: {err}");
+                    cli::decompress(input_path, output_path, password)
+                }
+                result => result,
+            }
+        }
+        ArchiveFormat::Zip => zip::decompress(input_path, output_path, password, progress),
+        ArchiveFormat::TarZst => {
+            if password.is_some() {
+                warn!("Password is ignored for archive format {format:?}");
+            }
+            tar_zst::decompress(input_path, output_path, progress)
+        }
+    };
+
+    match result {
+        Ok(_) => {
+            progress.flush();
+            Ok(())
+        }
+        Err(err) => {
+            if output_fresh && output_path.exists() {
+                warn!("Cleaning up partial output at {}", output_path.display());
+                let _ = if output_existed {
+                    output_path.clear_dir()
+                } else {
+                    fs::remove_dir_all(output_path)
+                };
+            }
+            Err(cancelled_or(err, progress))
+        }
+    }
+}
+
+/// Read every entry of an archive of `format` at `input_file` without writing anything,
+/// fails if the archive is damaged or `password` is wrong.
+///
+/// Like [`decompress`], 7z archives unsupported by the native backend are tested with the 7z CLI.
+pub fn test(
+    input_file: impl AsRef<Path>,
+    format: ArchiveFormat,
+    password: Option<&str>,
+    progress: &ProgressHandle,
+) -> CompressResult<()> {
+    let input_path = input_file.as_ref();
+    let result = match format {
+        ArchiveFormat::SevenZ => match seven_z::test(input_path, password, progress) {
+            Err(err) if !progress.is_cancelled() && cli::exist_7z() => {
+                warn!("Native 7z test failed, falling back to 7z CLI: {err}");
+                cli::test(input_path, password)
+            }
+            result => result,
+        },
+        ArchiveFormat::Zip => zip::test(input_path, password, progress),
+        ArchiveFormat::TarZst => tar_zst::test(input_path, progress),
+    };
+    progress.flush();
+    result.map_err(|err| cancelled_or(err, progress))
+}
+
+/// Errors caused by a cancelled progress are reported as [`CompressError::Cancelled`]
+fn cancelled_or(err: CompressError, progress: &ProgressHandle) -> CompressError {
+    if progress.is_cancelled() {
+        CompressError::Cancelled
+    } else {
+        err
+    }
+}
+
+/// Path of an archive entry relative to the output directory, `None` if the name is absolute
+/// or climbs out of it with `..`. Both separators are accepted as archives made on Windows
+/// may use either.
+fn enclosed_name(name: &str) -> Option<PathBuf> {
+    if name.starts_with(['/', '\\']) || name.contains('\0') {
+        return None;
+    }
+    let mut path = PathBuf::new();
+    for part in name.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => return None,
+            // Drive prefixes such as `C:`
+            part if part.contains(':') => return None,
+            part => path.push(part),
+        }
+    }
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
+/// Total bytes and count of files under `path`
+fn measure_dir(path: &Path) -> CompressResult<(u64, u64)> {
+    let mut bytes = 0;
+    let mut files = 0;
+    for entry in WalkDir::new(path) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            bytes += entry.metadata()?.len();
+            files += 1;
+        }
+    }
+    Ok((bytes, files))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sevenz_rust::{SevenZArchiveEntry, SevenZWriter};
+    use std::io::Cursor;
+    use tempfile::tempdir;
+
+    fn sample_dir(root: &Path) -> std::path::PathBuf {
+        let input = root.join("input");
+        fs::create_dir_all(input.join("sub/empty")).unwrap();
+        fs::write(input.join("a.txt"), "alpha").unwrap();
+        fs::write(input.join("sub/b.bin"), vec![7u8; 64 * 1024]).unwrap();
+        input
+    }
+
+    fn assert_same_tree(expected: &Path, actual: &Path) {
+        for entry in WalkDir::new(expected).min_depth(1) {
+            let entry = entry.unwrap();
+            let target = actual.join(entry.path().strip_prefix(expected).unwrap());
+            if entry.file_type().is_dir() {
+                assert!(target.is_dir(), "missing directory {}", target.display());
+            } else {
+                assert_eq!(fs::read(entry.path()).unwrap(), fs::read(&target).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn enclosed_name_rejects_escaping_paths() {
+        assert_eq!(
+            enclosed_name("a/b.txt"),
+            Some(PathBuf::from("a").join("b.txt"))
+        );
+        assert_eq!(
+            enclosed_name("./a\\b.txt"),
+            Some(PathBuf::from("a").join("b.txt"))
+        );
+        for name in [
+            "../x",
+            "a/../../x",
+            "a\\..\\x",
+            "/etc/x",
+            "\\x",
+            "C:\\x",
+            "C:x",
+            "",
+        ] {
+            assert_eq!(enclosed_name(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn seven_z_round_trip_with_password() {
+        let dir = tempdir().unwrap();
+        let input = sample_dir(dir.path());
+        let archive = dir.path().join("out.7z");
+        let output = dir.path().join("output");
+        let progress = ProgressHandle::silent();
+        compress(
+            &input,
+            &archive,
+            ArchiveFormat::SevenZ,
+            Some("pw"),
+            Some(1),
+            &progress,
+        )
+        .unwrap();
+        test(&archive, ArchiveFormat::SevenZ, Some("pw"), &progress).unwrap();
+        decompress(
+            &archive,
+            &output,
+            ArchiveFormat::SevenZ,
+            Some("pw"),
+            &progress,
+        )
+        .unwrap();
+        assert_same_tree(&input, &output);
+    }
+
+    #[test]
+    fn zip_and_tar_zst_round_trip() {
+        for (format, password) in [
+            (ArchiveFormat::Zip, Some("pw")),
+            (ArchiveFormat::Zip, None),
+            (ArchiveFormat::TarZst, None),
+        ] {
+            let dir = tempdir().unwrap();
+            let input = sample_dir(dir.path());
+            let archive = dir.path().join(format!("out.{}", format.extension()));
+            let output = dir.path().join("output");
+            let progress = ProgressHandle::silent();
+            compress(&input, &archive, format, password, Some(3), &progress).unwrap();
+            test(&archive, format, password, &progress).unwrap();
+            decompress(&archive, &output, format, password, &progress).unwrap();
+            assert_same_tree(&input, &output);
+        }
+    }
+
+    #[test]
+    fn cancelled_operations_leave_nothing_behind() {
+        for format in [
+            ArchiveFormat::SevenZ,
+            ArchiveFormat::Zip,
+            ArchiveFormat::TarZst,
+        ] {
+            let dir = tempdir().unwrap();
+            let input = sample_dir(dir.path());
+            let archive = dir.path().join(format!("out.{}", format.extension()));
+            compress(
+                &input,
+                &archive,
+                format,
+                None,
+                Some(1),
+                &ProgressHandle::silent(),
+            )
+            .unwrap();
+
+            let cancelled = ProgressHandle::silent();
+            cancelled.cancel();
+            let partial = dir.path().join(format!("partial.{}", format.extension()));
+            let result = compress(&input, &partial, format, None, Some(1), &cancelled);
+            assert!(
+                matches!(result, Err(CompressError::Cancelled)),
+                "{format:?}"
+            );
+            assert!(!partial.exists(), "{format:?}");
+
+            let output = dir.path().join("output");
+            let result = decompress(&archive, &output, format, None, &cancelled);
+            assert!(
+                matches!(result, Err(CompressError::Cancelled)),
+                "{format:?}"
+            );
+            assert!(!output.exists(), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn seven_z_skips_entries_outside_output() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("evil.7z");
+        let mut writer = SevenZWriter::create(&archive).unwrap();
+        for name in ["../evil.txt", "ok.txt"] {
+            let mut entry = SevenZArchiveEntry::new();
+            entry.name = name.to_string();
+            entry.has_stream = true;
+            writer
+                .push_archive_entry(entry, Some(Cursor::new(b"payload".to_vec())))
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let output = dir.path().join("output");
+        let progress = ProgressHandle::silent();
+        decompress(&archive, &output, ArchiveFormat::SevenZ, None, &progress).unwrap();
+        assert!(output.join("ok.txt").is_file());
+        assert!(!dir.path().join("evil.txt").exists());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::util::compress::{CompressResult, enclosed_name};
+use crate::util::path_ext::PathExt;
+use crate::util::progress::ProgressHandle;
+use log::warn;
+use sevenz_rust::lzma::LZMA2Options;
+use sevenz_rust::{
+    AesEncoderOptions, Password, SevenZArchiveEntry, SevenZMethodConfiguration, SevenZReader,
+    SevenZWriter,
+};
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write, copy, sink};
+use std::path::Path;
+use walkdir::WalkDir;
+
+pub(super) fn compress(
+    input_path: &Path,
+    output_path: &Path,
+    password: Option<&str>,
+    compression_level: u32,
+    progress: &ProgressHandle,
+) -> CompressResult<()> {
+    let mut writer = SevenZWriter::create(output_path)?;
+
+    let mut methods: Vec<SevenZMethodConfiguration> = Vec::new();
+    if let Some(pwd) = password {
+        // Same as `-mhe=on` of the CLI, the header is encrypted with the content
+        methods.push(AesEncoderOptions::new(Password::from(pwd)).into());
+        writer.set_encrypt_header(true);
+    }
+    methods.push(LZMA2Options::with_preset(compression_level).into());
+    writer.set_content_methods(methods);
+
+    let walker = WalkDir::new(input_path)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter();
+    for entry in walker {
+        let entry = entry?;
+        let name = entry.path().relative_name(input_path);
+        if entry.file_type().is_dir() {
+            writer.push_archive_entry::<File>(
+                SevenZArchiveEntry::from_path(entry.path(), name),
+                None,
+            )?;
+        } else if entry.file_type().is_file() {
+            progress.start_file(&name);
+            writer.push_archive_entry(
+                SevenZArchiveEntry::from_path(entry.path(), name),
+                Some(progress.reader(File::open(entry.path())?)),
+            )?;
+            progress.finish_file();
+        }
+    }
+
+    progress.check()?;
+    writer.finish()?;
+    Ok(())
+}
+
+pub(super) fn decompress(
+    input_path: &Path,
+    output_path: &Path,
+    password: Option<&str>,
+    progress: &ProgressHandle,
+) -> CompressResult<()> {
+    let password = password.map(Password::from).unwrap_or_else(Password::empty);
+    let mut reader = SevenZReader::open(input_path, password)?;
+
+    let files = reader.archive().files.iter().filter(|f| !f.is_directory());
+    let (bytes, count) = files.fold((0, 0), |(bytes, count), f| (bytes + f.size(), count + 1));
+    progress.set_total(bytes, count);
+
+    if !output_path.exists() {
+        fs::create_dir_all(output_path)?;
+    }
+
+    reader.for_each_entries(|entry, data| {
+        // Entries escaping the output directory are skipped, as for zip
+        let Some(name) = enclosed_name(entry.name()) else {
+            warn!(
+                "Skipped 7z entry outside the output directory: {}",
+                entry.name()
+            );
+            copy(data, &mut sink())?;
+            return Ok(true);
+        };
+        let target = output_path.join(name);
+        if entry.is_directory() {
+            fs::create_dir_all(&target)?;
+        } else {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            progress.start_file(entry.name());
+            let mut file = BufWriter::new(File::create(&target)?);
+            copy(&mut progress.reader(data), &mut file)?;
+            file.flush()?;
+            progress.finish_file();
+        }
+        Ok(true)
+    })?;
+
+    Ok(())
+}
+
+pub(super) fn test(
+    input_path: &Path,
+    password: Option<&str>,
+    progress: &ProgressHandle,
+) -> CompressResult<()> {
+    let password = password.map(Password::from).unwrap_or_else(Password::empty);
+    let mut reader = SevenZReader::open(input_path, password)?;
+
+    let files = reader.archive().files.iter().filter(|f| !f.is_directory());
+    let (bytes, count) = files.fold((0, 0), |(bytes, count), f| (bytes + f.size(), count + 1));
+    progress.set_total(bytes, count);
+
+    // Reading an entry to the end checks its CRC
+    reader.for_each_entries(|entry, data| {
+        if !entry.is_directory() {
+            progress.start_file(entry.name());
+            copy(&mut progress.reader(data), &mut sink())?;
+            progress.finish_file();
+        }
+        Ok(true)
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use crate::util::compress::CompressResult;
+use crate::util::path_ext::PathExt;
+use crate::util::progress::ProgressHandle;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write, copy, sink};
+use std::path::Path;
+use tar::{Archive, Builder, Header};
+use walkdir::WalkDir;
+use zstd::{Decoder, Encoder};
+
+pub(super) fn compress(
+    input_path: &Path,
+    output_path: &Path,
+    compression_level: u32,
+    progress: &ProgressHandle,
+) -> CompressResult<()> {
+    // Map the 0-9 scale of 7z to the 1-19 scale of zstd
+    let level = (compression_level * 2).clamp(1, 19) as i32;
+    let mut encoder = Encoder::new(BufWriter::new(File::create(output_path)?), level)?;
+    // Lets damaged archives be detected when reading them back
+    encoder.include_checksum(true)?;
+    let mut builder = Builder::new(encoder);
+
+    let walker = WalkDir::new(input_path)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter();
+    for entry in walker {
+        let entry = entry?;
+        let name = entry.path().relative_name(input_path);
+        if entry.file_type().is_dir() {
+            builder.append_dir(name, entry.path())?;
+        } else if entry.file_type().is_file() {
+            progress.start_file(&name);
+            let file = File::open(entry.path())?;
+            let mut header = Header::new_gnu();
+            header.set_metadata(&file.metadata()?);
+            builder.append_data(&mut header, name, progress.reader(file))?;
+            progress.finish_file();
+        }
+    }
+
+    builder.into_inner()?.finish()?.flush()?;
+    Ok(())
+}
+
+/// The uncompressed size is unknown until the end, so the progress of tar.zst
+/// is measured in bytes of the compressed input instead.
+pub(super) fn decompress(
+    input_path: &Path,
+    output_path: &Path,
+    progress: &ProgressHandle,
+) -> CompressResult<()> {
+    let file = File::open(input_path)?;
+    progress.set_total(file.metadata()?.len(), 0);
+    let decoder = Decoder::new(BufReader::new(progress.reader(file)))?;
+    let mut archive = Archive::new(decoder);
+
+    if !output_path.exists() {
+        fs::create_dir_all(output_path)?;
+    }
+
+    archive.set_overwrite(true);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        progress.start_file(entry.path()?.to_string_lossy());
+        entry.unpack_in(output_path)?;
+        progress.finish_file();
+    }
+    Ok(())
+}
+
+pub(super) fn test(input_path: &Path, progress: &ProgressHandle) -> CompressResult<()> {
+    let file = File::open(input_path)?;
+    progress.set_total(file.metadata()?.len(), 0);
+    let decoder = Decoder::new(BufReader::new(progress.reader(file)))?;
+    let mut archive = Archive::new(decoder);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        progress.start_file(entry.path()?.to_string_lossy());
+        copy(&mut entry, &mut sink())?;
+        progress.finish_file();
+    }
+    // The frame checksum is only verified once the decoder reaches the end
+    copy(&mut archive.into_inner(), &mut sink())?;
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use crate::util::compress::CompressResult;
+use crate::util::path_ext::PathExt;
+use crate::util::progress::ProgressHandle;
+use ::zip::write::SimpleFileOptions;
+use ::zip::{AesMode, CompressionMethod, ZipArchive, ZipWriter};
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write, copy, sink};
+use std::path::Path;
+use walkdir::WalkDir;
+
+pub(super) fn compress(
+    input_path: &Path,
+    output_path: &Path,
+    password: Option<&str>,
+    compression_level: u32,
+    progress: &ProgressHandle,
+) -> CompressResult<()> {
+    let mut writer = ZipWriter::new(BufWriter::new(File::create(output_path)?));
+
+    let mut options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .compression_level(Some(compression_level as i64))
+        .large_file(true);
+    if let Some(pwd) = password {
+        // Zip only encrypts the content, entry names stay readable
+        options = options.with_aes_encryption(AesMode::Aes256, pwd);
+    }
+
+    let walker = WalkDir::new(input_path)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter();
+    for entry in walker {
+        let entry = entry?;
+        let name = entry.path().relative_name(input_path);
+        if entry.file_type().is_dir() {
+            writer.add_directory(name, options)?;
+        } else if entry.file_type().is_file() {
+            progress.start_file(&name);
+            writer.start_file(name, options)?;
+            let file = BufReader::new(File::open(entry.path())?);
+            copy(&mut progress.reader(file), &mut writer)?;
+            progress.finish_file();
+        }
+    }
+
+    writer.finish()?.flush()?;
+    Ok(())
+}
+
+pub(super) fn decompress(
+    input_path: &Path,
+    output_path: &Path,
+    password: Option<&str>,
+    progress: &ProgressHandle,
+) -> CompressResult<()> {
+    let mut archive = ZipArchive::new(BufReader::new(File::open(input_path)?))?;
+    progress.set_total(
+        archive.decompressed_size().unwrap_or_default() as u64,
+        archive.len() as u64,
+    );
+
+    if !output_path.exists() {
+        fs::create_dir_all(output_path)?;
+    }
+
+    for index in 0..archive.len() {
+        let mut file = match password {
+            Some(pwd) => archive.by_index_decrypt(index, pwd.as_bytes())?,
+            None => archive.by_index(index)?,
+        };
+        // Entries escaping the output directory are skipped
+        let Some(name) = file.enclosed_name() else {
+            continue;
+        };
+        let target = output_path.join(name);
+        if file.is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            progress.start_file(file.name());
+            let mut writer = BufWriter::new(File::create(&target)?);
+            copy(&mut progress.reader(&mut file), &mut writer)?;
+            writer.flush()?;
+        }
+        progress.finish_file();
+    }
+
+    Ok(())
+}
+
+pub(super) fn test(
+    input_path: &Path,
+    password: Option<&str>,
+    progress: &ProgressHandle,
+) -> CompressResult<()> {
+    let mut archive = ZipArchive::new(BufReader::new(File::open(input_path)?))?;
+    progress.set_total(
+        archive.decompressed_size().unwrap_or_default() as u64,
+        archive.len() as u64,
+    );
+
+    // Reading an entry to the end checks its CRC
+    for index in 0..archive.len() {
+        let mut file = match password {
+            Some(pwd) => archive.by_index_decrypt(index, pwd.as_bytes())?,
+            None => archive.by_index(index)?,
+        };
+        progress.start_file(file.name());
+        copy(&mut progress.reader(&mut file), &mut sink())?;
+        progress.finish_file();
+    }
+
+    Ok(())
+}
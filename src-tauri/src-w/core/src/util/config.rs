@@ -1,3 +1,4 @@
+use crate::util::compress::ArchiveFormat;
 use crate::util::dir_rel;
 use log::{error, info};
 use serde::{Deserialize, Serialize};
@@ -9,12 +10,47 @@ use std::sync::OnceLock;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ConfigRaw {
     pub root: String,
+
+    /// Format used for newly created archives if not specified per entry
+    #[serde(default)]
+    pub archive_format: ArchiveFormat,
+
+    /// Days removed entries stay in the trash, kept until purged by hand if 0
+    #[serde(default = "ConfigRaw::default_trash_retention_days")]
+    pub trash_retention_days: u32,
+
+    /// Whether entries expiring from the trash take their archive in the data root along
+    #[serde(default)]
+    pub trash_delete_archive: bool,
+
+    /// Automatic backups kept in the backup directory, unlimited if 0
+    #[serde(default = "ConfigRaw::default_backup_keep_count")]
+    pub backup_keep_count: usize,
+
+    /// Days automatic backups are kept, not expired by age if 0
+    #[serde(default)]
+    pub backup_keep_days: u32,
+}
+
+impl ConfigRaw {
+    fn default_trash_retention_days() -> u32 {
+        30
+    }
+
+    fn default_backup_keep_count() -> usize {
+        5
+    }
 }
 
 impl Default for ConfigRaw {
     fn default() -> Self {
         Self {
             root: String::from("data"),
+            archive_format: ArchiveFormat::default(),
+            trash_retention_days: ConfigRaw::default_trash_retention_days(),
+            trash_delete_archive: false,
+            backup_keep_count: ConfigRaw::default_backup_keep_count(),
+            backup_keep_days: 0,
         }
     }
 }
@@ -47,6 +83,11 @@ impl From<ConfigRaw> for Config {
             } else {
                 dir_rel().join(path)
             },
+            archive_format: raw.archive_format,
+            trash_retention_days: raw.trash_retention_days,
+            trash_delete_archive: raw.trash_delete_archive,
+            backup_keep_count: raw.backup_keep_count,
+            backup_keep_days: raw.backup_keep_days,
         }
     }
 }
@@ -54,6 +95,11 @@ impl From<ConfigRaw> for Config {
 #[derive(Debug)]
 pub struct Config {
     root_path: PathBuf,
+    archive_format: ArchiveFormat,
+    trash_retention_days: u32,
+    trash_delete_archive: bool,
+    backup_keep_count: usize,
+    backup_keep_days: u32,
 }
 
 impl Config {
@@ -67,6 +113,26 @@ impl Config {
             .expect("Failed to get absolute path")
     }
 
+    pub fn get_archive_format(&self) -> ArchiveFormat {
+        self.archive_format
+    }
+
+    pub fn get_trash_retention_days(&self) -> u32 {
+        self.trash_retention_days
+    }
+
+    pub fn get_trash_delete_archive(&self) -> bool {
+        self.trash_delete_archive
+    }
+
+    pub fn get_backup_keep_count(&self) -> usize {
+        self.backup_keep_count
+    }
+
+    pub fn get_backup_keep_days(&self) -> u32 {
+        self.backup_keep_days
+    }
+
     pub fn resolve_to_root(&self, path: &Path) -> PathBuf {
         if path.is_absolute() {
             path.to_path_buf()
@@ -129,3 +195,16 @@ pub fn config_init() {
 pub fn config_get() -> &'static Config {
     CONFIG.get().expect("Config is not initialized")
 }
+
+/// Configure a data root in the temporary directory, shared by all tests of the process
+#[cfg(test)]
+pub(crate) fn config_init_test() -> &'static Config {
+    CONFIG.get_or_init(|| {
+        let root = std::env::temp_dir().join(format!("m-core-test-{}", std::process::id()));
+        ConfigRaw {
+            root: root.to_string_lossy().to_string(),
+            ..Default::default()
+        }
+        .into()
+    })
+}
@@ -0,0 +1,49 @@
+use crate::util::path_ext::PathExt;
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Hex encoded blake3 hash of everything read from `reader`
+pub fn hash_reader(reader: impl Read) -> std::io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(reader)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+pub fn hash_file(path: &Path) -> std::io::Result<String> {
+    hash_reader(File::open(path)?)
+}
+
+/// Hash of a directory tree, covering the relative names and contents of its entries.
+///
+/// Entries are visited in name order so the result does not depend on the file system,
+/// links are hashed by their target.
+pub fn hash_dir(path: &Path) -> std::io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    for entry in WalkDir::new(path).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        hasher.update(entry.path().relative_name(path).as_bytes());
+        if entry.file_type().is_dir() {
+            hasher.update(b"\0d\0");
+        } else if entry.file_type().is_symlink() {
+            hasher.update(b"\0l\0");
+            hasher.update(fs::read_link(entry.path())?.to_string_lossy().as_bytes());
+        } else {
+            hasher.update(b"\0f\0");
+            hasher.update(hash_file(entry.path())?.as_bytes());
+        }
+        hasher.update(b"\0");
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// [`hash_dir`] for directories, [`hash_file`] otherwise
+pub fn hash_path(path: &Path) -> std::io::Result<String> {
+    if path.is_dir() {
+        hash_dir(path)
+    } else {
+        hash_file(path)
+    }
+}
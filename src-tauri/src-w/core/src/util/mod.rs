@@ -1,6 +1,8 @@
 pub mod compress;
 pub mod config;
+pub mod hash;
 pub mod path_ext;
+pub mod progress;
 
 use std::path::PathBuf;
 use std::process::Command;
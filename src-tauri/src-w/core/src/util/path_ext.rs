@@ -1,4 +1,7 @@
+use crate::util::progress::ProgressHandle;
 use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write, copy};
 use std::path::Path;
 use walkdir::WalkDir;
 
@@ -6,6 +9,15 @@ pub trait PathExt {
     fn is_dir_empty(&self) -> bool;
     fn clear_dir(&self) -> std::io::Result<()>;
     fn calculate_size(&self) -> std::io::Result<u64>;
+    fn copy_file_to(&self, target: &Path, progress: &ProgressHandle) -> std::io::Result<()>;
+    /// Recreate the directory tree at `target`, files are placed by `place_file(source, target)`
+    fn mirror_dir_to(
+        &self,
+        target: &Path,
+        progress: &ProgressHandle,
+        place_file: impl FnMut(&Path, &Path) -> std::io::Result<()>,
+    ) -> std::io::Result<()>;
+    fn relative_name(&self, root: &Path) -> String;
 }
 
 impl PathExt for Path {
@@ -23,10 +35,11 @@ impl PathExt for Path {
         let read_dir = fs::read_dir(self)?;
         for entry in read_dir {
             let entry = entry?;
-            if entry.file_type()?.is_file() {
-                fs::remove_file(entry.path())?;
-            } else if entry.file_type()?.is_dir() {
+            // Links are removed themselves, never what they point to
+            if entry.file_type()?.is_dir() {
                 fs::remove_dir_all(entry.path())?;
+            } else {
+                fs::remove_file(entry.path())?;
             }
         }
         Ok(())
@@ -55,4 +68,61 @@ impl PathExt for Path {
             ))
         }
     }
+
+    fn copy_file_to(&self, target: &Path, progress: &ProgressHandle) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(target)?);
+        copy(&mut progress.reader(File::open(self)?), &mut writer)?;
+        // Dropping the writer would swallow a failed final write, e.g. on a full disk
+        writer.flush()?;
+        fs::set_permissions(target, self.metadata()?.permissions())?;
+        Ok(())
+    }
+
+    fn mirror_dir_to(
+        &self,
+        target: &Path,
+        progress: &ProgressHandle,
+        mut place_file: impl FnMut(&Path, &Path) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        let mut entries = Vec::new();
+        let mut total_size = 0;
+        for entry in WalkDir::new(self).min_depth(1) {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                total_size += entry.metadata()?.len();
+            }
+            entries.push(entry);
+        }
+        let total_files = entries.iter().filter(|e| e.file_type().is_file()).count();
+        progress.set_total(total_size, total_files as u64);
+
+        for entry in entries {
+            progress.check()?;
+            let relative = entry.path().strip_prefix(self).unwrap_or(entry.path());
+            let target_path = target.join(relative);
+            if entry.file_type().is_dir() {
+                fs::create_dir_all(&target_path)?;
+            } else if entry.file_type().is_file() {
+                if let Some(parent) = target_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                progress.start_file(relative.to_string_lossy());
+                place_file(entry.path(), &target_path)?;
+                progress.finish_file();
+            }
+        }
+        progress.flush();
+        Ok(())
+    }
+
+    /// Path relative to `root` using `/` as separator regardless of the platform,
+    /// as used by archive entries and deploy manifests
+    fn relative_name(&self, root: &Path) -> String {
+        self.strip_prefix(root)
+            .unwrap_or(self)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
 }
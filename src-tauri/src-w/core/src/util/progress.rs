@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Minimal interval between two reports, except for the final one
+const REPORT_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Progress {
+    pub id: String,
+    pub label: String,
+    pub bytes_processed: u64,
+    pub bytes_total: u64,
+    pub files_processed: u64,
+    pub files_total: u64,
+    pub current_file: String,
+    pub eta_secs: Option<u64>,
+}
+
+type ProgressCallback = Box<dyn Fn(&Progress) + Send + Sync>;
+
+struct ProgressInner {
+    cancelled: AtomicBool,
+    state: Mutex<ProgressState>,
+    callback: Option<ProgressCallback>,
+}
+
+struct ProgressState {
+    progress: Progress,
+    started: Instant,
+    reported: Option<Instant>,
+}
+
+/// Handle shared by a long-running operation and its observers.
+///
+/// The operation reports what it has processed, observers receive throttled
+/// [`Progress`] snapshots through the callback and may cancel it through the handle.
+#[derive(Clone)]
+pub struct ProgressHandle(Arc<ProgressInner>);
+
+/// Start tracking a new operation, snapshots are reported to `callback`
+pub fn progress_start(
+    label: impl Into<String>,
+    callback: impl Fn(&Progress) + Send + Sync + 'static,
+) -> ProgressHandle {
+    ProgressHandle::create(label.into(), Some(Box::new(callback)))
+}
+
+impl ProgressHandle {
+    fn create(label: String, callback: Option<ProgressCallback>) -> Self {
+        Self(Arc::new(ProgressInner {
+            cancelled: AtomicBool::new(false),
+            state: Mutex::new(ProgressState {
+                progress: Progress {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    label,
+                    ..Default::default()
+                },
+                started: Instant::now(),
+                reported: None,
+            }),
+            callback,
+        }))
+    }
+
+    /// A handle which reports to nobody and is never cancelled from outside
+    pub fn silent() -> Self {
+        Self::create(String::new(), None)
+    }
+
+    pub fn id(&self) -> String {
+        self.lock().progress.id.clone()
+    }
+
+    /// Latest state of the operation, regardless of throttling
+    pub fn snapshot(&self) -> Progress {
+        self.lock().progress.clone()
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns an error to abort io loops once cancelled
+    pub fn check(&self) -> std::io::Result<()> {
+        if self.is_cancelled() {
+            Err(std::io::Error::other("Operation cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn set_total(&self, bytes: u64, files: u64) {
+        let mut state = self.lock();
+        state.progress.bytes_total = bytes;
+        state.progress.files_total = files;
+        state.started = Instant::now();
+        drop(state);
+        self.report(true);
+    }
+
+    pub fn start_file(&self, name: impl Into<String>) {
+        self.lock().progress.current_file = name.into();
+        self.report(false);
+    }
+
+    pub fn finish_file(&self) {
+        self.lock().progress.files_processed += 1;
+        self.report(false);
+    }
+
+    pub fn add_bytes(&self, bytes: u64) {
+        self.lock().progress.bytes_processed += bytes;
+        self.report(false);
+    }
+
+    /// Force a final report, e.g. when the operation is done
+    pub fn flush(&self) {
+        self.report(true);
+    }
+
+    /// Wrap a reader so every byte read is counted, reading fails once cancelled
+    pub fn reader<R: Read>(&self, inner: R) -> ProgressReader<'_, R> {
+        ProgressReader {
+            inner,
+            progress: self,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ProgressState> {
+        self.0.state.lock().expect("Progress state poisoned")
+    }
+
+    fn report(&self, force: bool) {
+        let Some(callback) = &self.0.callback else {
+            return;
+        };
+        let mut state = self.lock();
+        let now = Instant::now();
+        if !force
+            && state
+                .reported
+                .is_some_and(|last| now.duration_since(last) < REPORT_INTERVAL)
+        {
+            return;
+        }
+        state.reported = Some(now);
+
+        let processed = state.progress.bytes_processed;
+        let total = state.progress.bytes_total;
+        state.progress.eta_secs = if processed > 0 && total >= processed {
+            let elapsed = now.duration_since(state.started).as_secs_f64();
+            Some((elapsed / processed as f64 * (total - processed) as f64) as u64)
+        } else {
+            None
+        };
+
+        let snapshot = state.progress.clone();
+        drop(state);
+        callback(&snapshot);
+    }
+}
+
+pub struct ProgressReader<'a, R: Read> {
+    inner: R,
+    progress: &'a ProgressHandle,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.progress.check()?;
+        let read = self.inner.read(buf)?;
+        self.progress.add_bytes(read as u64);
+        Ok(read)
+    }
+}
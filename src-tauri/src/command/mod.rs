@@ -1,14 +1,47 @@
-use log::info;
+use log::{info, warn};
 use m_api::http::dl_site::{DLSiteInfo, Language, fetch_dl_site_maniax};
 use m_common::ToStringErr;
+use m_core::data::backup::BackupInfo;
+use m_core::data::catalog::CatalogLabels;
+use m_core::data::duplicate::DuplicateMatch;
+use m_core::data::external::ExternalSource;
+use m_core::data::history::ChangeRecord;
+use m_core::data::import::ImportMode;
+use m_core::data::index::LibraryIndex;
+use m_core::data::job;
+use m_core::data::job::{Job, JobEvent, JobKind, job_init, job_submit};
 use m_core::data::library::*;
-use m_core::data::metadata::{Metadata, MetadataOptional};
+use m_core::data::metadata::{DeployMode, Metadata, MetadataOptional};
+use m_core::data::orphan::OrphanArchive;
+use m_core::data::query::{LibraryQuery, QueryPage};
+use m_core::data::save::SaveSnapshot;
+use m_core::data::schema::QuarantinedRecord;
+use m_core::data::transfer::{ExportFilter, TransferFormat};
+use m_core::data::trash::TrashEntry;
+use m_core::data::undo::{UndoState, UndoSummary};
 use m_core::util::config::config_get;
 use std::path::PathBuf;
-use tauri::command;
+use tauri::{AppHandle, Emitter, command};
 
 type CommandResult<T> = Result<T, String>;
 
+const EVENT_JOB_UPDATED: &str = "job-updated";
+const EVENT_JOB_PROGRESS: &str = "job-progress";
+
+/// Start the job worker, forwarding every job event to the frontend
+pub fn job_emitter_init(app: &AppHandle) {
+    let app = app.clone();
+    job_init(move |event| {
+        let result = match event {
+            JobEvent::Updated(job) => app.emit(EVENT_JOB_UPDATED, job),
+            JobEvent::Progress(progress) => app.emit(EVENT_JOB_PROGRESS, progress),
+        };
+        if let Err(err) = result {
+            warn!("Failed to emit job event: {err}");
+        }
+    });
+}
+
 #[command]
 pub fn metadata_get_all() -> CommandResult<Vec<Metadata>> {
     lib_get_all().string_err()
@@ -19,9 +52,23 @@ pub fn metadata_get(key: &str) -> CommandResult<Metadata> {
     lib_get(key).string_err()
 }
 
+#[command]
+pub fn library_query(query: LibraryQuery) -> CommandResult<QueryPage> {
+    lib_query(&query).string_err()
+}
+
 #[command]
 pub fn metadata_update(opt: MetadataOptional) -> CommandResult<String> {
-    lib_update(opt).string_err()
+    job_submit(JobKind::Update {
+        opt: Box::new(opt),
+        redacted: false,
+    })
+    .string_err()
+}
+
+#[command]
+pub fn metadata_duplicates(opt: MetadataOptional) -> CommandResult<Vec<DuplicateMatch>> {
+    lib_duplicates_of(&opt).string_err()
 }
 
 #[command]
@@ -30,13 +77,103 @@ pub fn metadata_remove(key: &str) -> CommandResult<bool> {
 }
 
 #[command]
-pub fn metadata_deploy(key: &str, target: &str) -> CommandResult<()> {
-    lib_deploy(key, target).string_err()
+pub fn metadata_deploy(key: String, target: String, mode: DeployMode) -> CommandResult<String> {
+    job_submit(JobKind::Deploy { key, target, mode }).string_err()
+}
+
+#[command]
+pub fn metadata_deploy_off(key: String, deployment: String) -> CommandResult<String> {
+    job_submit(JobKind::DeployOff { key, deployment }).string_err()
+}
+
+#[command]
+pub fn metadata_verify_deployment(key: String, deployment: String) -> CommandResult<String> {
+    job_submit(JobKind::VerifyDeployment { key, deployment }).string_err()
+}
+
+#[command]
+pub fn metadata_repair_deployment(key: String, deployment: String) -> CommandResult<String> {
+    job_submit(JobKind::RepairDeployment { key, deployment }).string_err()
+}
+
+#[command]
+pub fn metadata_history(key: &str) -> CommandResult<Vec<ChangeRecord>> {
+    lib_history(key).string_err()
+}
+
+#[command]
+pub fn metadata_history_restore(key: &str, seq: u64) -> CommandResult<Metadata> {
+    lib_history_restore(key, seq).string_err()
+}
+
+#[command]
+pub fn library_history_recent(limit: usize) -> CommandResult<Vec<ChangeRecord>> {
+    lib_history_recent(limit).string_err()
+}
+
+#[command]
+pub fn library_undo() -> CommandResult<Option<UndoSummary>> {
+    lib_undo().string_err()
+}
+
+#[command]
+pub fn library_redo() -> CommandResult<Option<UndoSummary>> {
+    lib_redo().string_err()
 }
 
 #[command]
-pub fn metadata_deploy_off(key: &str) -> CommandResult<()> {
-    lib_deploy_off(key).string_err()
+pub fn library_undo_state() -> UndoState {
+    lib_undo_state()
+}
+
+#[command]
+pub fn trash_list() -> CommandResult<Vec<TrashEntry>> {
+    lib_trash_list().string_err()
+}
+
+#[command]
+pub fn trash_restore(key: &str) -> CommandResult<Metadata> {
+    lib_trash_restore(key).string_err()
+}
+
+#[command]
+pub fn trash_purge(key: &str, delete_archive: bool) -> CommandResult<bool> {
+    lib_trash_purge(key, delete_archive).string_err()
+}
+
+#[command]
+pub fn trash_empty(delete_archive: bool) -> CommandResult<String> {
+    job_submit(JobKind::TrashEmpty { delete_archive }).string_err()
+}
+
+#[command]
+pub fn backup_list() -> CommandResult<Vec<BackupInfo>> {
+    lib_backup_list().string_err()
+}
+
+#[command]
+pub fn backup_snapshot(name: &str) -> CommandResult<String> {
+    lib_backup_snapshot(name).string_err()
+}
+
+#[command]
+pub fn backup_restore(id: &str) -> CommandResult<String> {
+    job_submit(JobKind::BackupRestore { id: id.to_string() }).string_err()
+}
+
+#[command]
+pub fn backup_delete(id: &str) -> CommandResult<()> {
+    lib_backup_delete(id).string_err()
+}
+
+#[command]
+pub fn save_list(key: &str) -> CommandResult<Vec<SaveSnapshot>> {
+    lib_save_list(key).string_err()
+}
+
+#[command]
+pub fn save_rollback(key: &str, snapshot: &str, deployment: Option<&str>) -> CommandResult<()> {
+    lib_save_rollback(key, snapshot, deployment).string_err()
 }
 
 #[command]
@@ -45,13 +182,190 @@ pub fn library_clear() -> CommandResult<()> {
 }
 
 #[command]
-pub fn library_export() -> CommandResult<()> {
-    lib_export().string_err()
+pub fn library_export(
+    path: Option<String>,
+    format: Option<TransferFormat>,
+    filter: ExportFilter,
+) -> CommandResult<String> {
+    job_submit(JobKind::Export {
+        path,
+        format,
+        filter,
+    })
+    .string_err()
+}
+
+#[command]
+pub fn library_find(index: LibraryIndex, key: &str) -> CommandResult<Vec<Metadata>> {
+    lib_find(index, key).string_err()
+}
+
+#[command]
+pub fn library_rebuild_index() -> CommandResult<usize> {
+    lib_rebuild_index().string_err()
+}
+
+#[command]
+pub fn library_quarantine_list() -> CommandResult<Vec<QuarantinedRecord>> {
+    lib_quarantine_list().string_err()
+}
+
+#[command]
+pub fn library_quarantine_retry() -> CommandResult<usize> {
+    lib_quarantine_retry().string_err()
+}
+
+#[command]
+pub fn library_quarantine_remove(key: &str) -> CommandResult<bool> {
+    lib_quarantine_remove(key).string_err()
+}
+
+#[command]
+pub fn library_duplicates() -> CommandResult<String> {
+    job_submit(JobKind::Duplicates).string_err()
+}
+
+#[command]
+pub fn metadata_merge(
+    primary: &str,
+    secondary: &str,
+    keep_secondary_archive: bool,
+) -> CommandResult<Metadata> {
+    lib_merge(primary, secondary, keep_secondary_archive).string_err()
+}
+
+#[command]
+pub fn library_verify() -> CommandResult<String> {
+    job_submit(JobKind::Verify).string_err()
+}
+
+#[command]
+pub fn library_check() -> CommandResult<String> {
+    job_submit(JobKind::Check).string_err()
+}
+
+#[command]
+pub fn library_repair() -> CommandResult<String> {
+    job_submit(JobKind::Repair).string_err()
+}
+
+#[command]
+pub fn library_orphan_scan() -> CommandResult<Vec<OrphanArchive>> {
+    lib_orphan_scan().string_err()
+}
+
+#[command]
+pub fn library_orphan_delete(paths: Vec<String>) -> CommandResult<usize> {
+    lib_orphan_delete(&paths).string_err()
+}
+
+#[command]
+pub fn library_orphan_adopt(paths: Vec<String>) -> CommandResult<String> {
+    job_submit(JobKind::OrphanAdopt { paths }).string_err()
+}
+
+#[command]
+pub fn library_import(
+    path: Option<String>,
+    format: Option<TransferFormat>,
+    mode: ImportMode,
+    dry_run: bool,
+) -> CommandResult<String> {
+    job_submit(JobKind::Import {
+        path,
+        format,
+        mode,
+        dry_run,
+    })
+    .string_err()
+}
+
+#[command]
+pub fn library_bundle_export(path: Option<String>, filter: ExportFilter) -> CommandResult<String> {
+    job_submit(JobKind::BundleExport { path, filter }).string_err()
+}
+
+#[command]
+pub fn library_bundle_import(
+    path: String,
+    mode: ImportMode,
+    dry_run: bool,
+) -> CommandResult<String> {
+    job_submit(JobKind::BundleImport {
+        path,
+        mode,
+        dry_run,
+    })
+    .string_err()
+}
+
+#[command]
+pub fn library_catalog_export(
+    path: Option<String>,
+    filter: ExportFilter,
+    labels: CatalogLabels,
+) -> CommandResult<String> {
+    job_submit(JobKind::CatalogExport {
+        path,
+        filter,
+        labels: Box::new(labels),
+    })
+    .string_err()
+}
+
+#[command]
+pub fn library_external_columns(path: &str) -> CommandResult<Vec<String>> {
+    lib_external_columns(path).string_err()
+}
+
+#[command]
+pub fn library_external_preview(path: &str, source: ExternalSource) -> CommandResult<String> {
+    job_submit(JobKind::ExternalPreview {
+        path: path.to_string(),
+        source,
+    })
+    .string_err()
+}
+
+#[command]
+pub fn library_external_import(
+    path: String,
+    source: ExternalSource,
+    selected: Vec<usize>,
+    confirmed: Vec<usize>,
+) -> CommandResult<String> {
+    job_submit(JobKind::ExternalImport {
+        path,
+        source,
+        selected,
+        confirmed,
+    })
+    .string_err()
+}
+
+#[command]
+pub fn job_list() -> CommandResult<Vec<Job>> {
+    job::job_list().string_err()
+}
+
+#[command]
+pub fn job_get(id: &str) -> CommandResult<Job> {
+    job::job_get(id).string_err()
+}
+
+#[command]
+pub fn job_cancel(id: &str) -> CommandResult<bool> {
+    job::job_cancel(id).string_err()
+}
+
+#[command]
+pub fn job_retry(id: &str) -> CommandResult<()> {
+    job::job_retry(id).string_err()
 }
 
 #[command]
-pub fn library_import() -> CommandResult<()> {
-    lib_import().string_err()
+pub fn job_clear() -> CommandResult<usize> {
+    job::job_clear().string_err()
 }
 
 #[command]
@@ -24,19 +24,64 @@ pub fn run() {
 
             config_init();
             lib_init();
+            job_emitter_init(app.handle());
 
             Ok(())
         })
         .invoke_handler(generate_handler![
             metadata_get_all,
             metadata_get,
+            library_query,
             metadata_update,
+            metadata_duplicates,
             metadata_remove,
             metadata_deploy,
             metadata_deploy_off,
+            metadata_verify_deployment,
+            metadata_repair_deployment,
+            metadata_history,
+            metadata_history_restore,
+            library_history_recent,
+            library_undo,
+            library_redo,
+            library_undo_state,
+            trash_list,
+            trash_restore,
+            trash_purge,
+            trash_empty,
+            backup_list,
+            backup_snapshot,
+            backup_restore,
+            backup_delete,
+            save_list,
+            save_rollback,
             library_clear,
             library_export,
             library_import,
+            library_bundle_export,
+            library_bundle_import,
+            library_catalog_export,
+            library_external_columns,
+            library_external_preview,
+            library_external_import,
+            library_verify,
+            library_check,
+            library_repair,
+            library_orphan_scan,
+            library_orphan_delete,
+            library_orphan_adopt,
+            library_find,
+            library_rebuild_index,
+            library_quarantine_list,
+            library_quarantine_retry,
+            library_quarantine_remove,
+            library_duplicates,
+            metadata_merge,
+            job_list,
+            job_get,
+            job_cancel,
+            job_retry,
+            job_clear,
             util_resolve_root,
             api_fetch_dl_site_maniax
         ])
@@ -1,4 +1,27 @@
-import type { DLSiteInfo, Metadata, MetadataOptional } from '@/api/types.ts';
+import type {
+  BackupInfo,
+  CatalogLabels,
+  ChangeRecord,
+  DeployMode,
+  DLSiteInfo,
+  DuplicateMatch,
+  ExportFilter,
+  ExternalSource,
+  ImportMode,
+  Job,
+  LibraryIndex,
+  LibraryQuery,
+  Metadata,
+  MetadataOptional,
+  OrphanArchive,
+  QuarantinedRecord,
+  QueryPage,
+  SaveSnapshot,
+  TransferFormat,
+  TrashEntry,
+  UndoState,
+  UndoSummary,
+} from '@/api/types.ts';
 
 import { invoke } from '@tauri-apps/api/core';
 
@@ -10,32 +33,234 @@ export function command_metadata_get(key: string): Promise<Metadata> {
   return invoke('metadata_get', { key });
 }
 
+export function command_library_query(query: LibraryQuery): Promise<QueryPage> {
+  return invoke('library_query', { query });
+}
+
 export function command_metadata_update(opt: MetadataOptional): Promise<string> {
   return invoke('metadata_update', { opt });
 }
 
+export function command_metadata_duplicates(opt: MetadataOptional): Promise<DuplicateMatch[]> {
+  return invoke('metadata_duplicates', { opt });
+}
+
 export function command_metadata_remove(key: string): Promise<void> {
   return invoke('metadata_remove', { key });
 }
 
-export function command_metadata_deploy(key: string, target: string): Promise<void> {
-  return invoke('metadata_deploy', { key, target });
+export function command_metadata_deploy(
+  key: string,
+  target: string,
+  mode: DeployMode,
+): Promise<string> {
+  return invoke('metadata_deploy', { key, target, mode });
+}
+
+export function command_metadata_deploy_off(key: string, deployment: string): Promise<string> {
+  return invoke('metadata_deploy_off', { key, deployment });
+}
+
+export function command_metadata_verify_deployment(
+  key: string,
+  deployment: string,
+): Promise<string> {
+  return invoke('metadata_verify_deployment', { key, deployment });
+}
+
+export function command_metadata_repair_deployment(
+  key: string,
+  deployment: string,
+): Promise<string> {
+  return invoke('metadata_repair_deployment', { key, deployment });
+}
+
+export function command_metadata_history(key: string): Promise<ChangeRecord[]> {
+  return invoke('metadata_history', { key });
+}
+
+export function command_metadata_history_restore(key: string, seq: number): Promise<Metadata> {
+  return invoke('metadata_history_restore', { key, seq });
+}
+
+export function command_library_history_recent(limit: number): Promise<ChangeRecord[]> {
+  return invoke('library_history_recent', { limit });
+}
+
+export function command_library_undo(): Promise<UndoSummary | null> {
+  return invoke('library_undo');
+}
+
+export function command_library_redo(): Promise<UndoSummary | null> {
+  return invoke('library_redo');
+}
+
+export function command_library_undo_state(): Promise<UndoState> {
+  return invoke('library_undo_state');
 }
 
-export function command_metadata_deploy_off(key: string): Promise<void> {
-  return invoke('metadata_deploy_off', { key });
+export function command_trash_list(): Promise<TrashEntry[]> {
+  return invoke('trash_list');
+}
+
+export function command_trash_restore(key: string): Promise<Metadata> {
+  return invoke('trash_restore', { key });
+}
+
+export function command_trash_purge(key: string, deleteArchive: boolean): Promise<boolean> {
+  return invoke('trash_purge', { key, deleteArchive });
+}
+
+export function command_trash_empty(deleteArchive: boolean): Promise<string> {
+  return invoke('trash_empty', { deleteArchive });
+}
+
+export function command_backup_list(): Promise<BackupInfo[]> {
+  return invoke('backup_list');
+}
+
+export function command_backup_snapshot(name: string): Promise<string> {
+  return invoke('backup_snapshot', { name });
+}
+
+export function command_backup_restore(id: string): Promise<string> {
+  return invoke('backup_restore', { id });
+}
+
+export function command_backup_delete(id: string): Promise<void> {
+  return invoke('backup_delete', { id });
+}
+
+export function command_save_list(key: string): Promise<SaveSnapshot[]> {
+  return invoke('save_list', { key });
+}
+
+export function command_save_rollback(
+  key: string,
+  snapshot: string,
+  deployment?: string,
+): Promise<void> {
+  return invoke('save_rollback', { key, snapshot, deployment });
 }
 
 export function command_library_clear(): Promise<void> {
   return invoke('library_clear');
 }
 
-export function command_library_export(): Promise<void> {
-  return invoke('library_export');
+export function command_library_verify(): Promise<string> {
+  return invoke('library_verify');
+}
+
+export function command_library_check(): Promise<string> {
+  return invoke('library_check');
+}
+
+export function command_library_repair(): Promise<string> {
+  return invoke('library_repair');
 }
 
-export function command_library_import(): Promise<void> {
-  return invoke('library_import');
+export function command_library_orphan_scan(): Promise<OrphanArchive[]> {
+  return invoke('library_orphan_scan');
+}
+
+export function command_library_orphan_delete(paths: string[]): Promise<number> {
+  return invoke('library_orphan_delete', { paths });
+}
+
+export function command_library_orphan_adopt(paths: string[]): Promise<string> {
+  return invoke('library_orphan_adopt', { paths });
+}
+
+export function command_library_find(index: LibraryIndex, key: string): Promise<Metadata[]> {
+  return invoke('library_find', { index, key });
+}
+
+export function command_library_rebuild_index(): Promise<number> {
+  return invoke('library_rebuild_index');
+}
+
+export function command_library_quarantine_list(): Promise<QuarantinedRecord[]> {
+  return invoke('library_quarantine_list');
+}
+
+export function command_library_quarantine_retry(): Promise<number> {
+  return invoke('library_quarantine_retry');
+}
+
+export function command_library_quarantine_remove(key: string): Promise<boolean> {
+  return invoke('library_quarantine_remove', { key });
+}
+
+export function command_library_duplicates(): Promise<string> {
+  return invoke('library_duplicates');
+}
+
+export function command_metadata_merge(
+  primary: string,
+  secondary: string,
+  keepSecondaryArchive: boolean,
+): Promise<Metadata> {
+  return invoke('metadata_merge', { primary, secondary, keepSecondaryArchive });
+}
+
+export function command_library_export(
+  path?: string,
+  format?: TransferFormat,
+  filter: ExportFilter = {},
+): Promise<string> {
+  return invoke('library_export', { path, format, filter });
+}
+
+export function command_library_import(
+  path: string | undefined,
+  format: TransferFormat | undefined,
+  mode: ImportMode,
+  dryRun: boolean,
+): Promise<string> {
+  return invoke('library_import', { path, format, mode, dryRun });
+}
+
+export function command_library_bundle_export(
+  path?: string,
+  filter: ExportFilter = {},
+): Promise<string> {
+  return invoke('library_bundle_export', { path, filter });
+}
+
+export function command_library_bundle_import(
+  path: string,
+  mode: ImportMode,
+  dryRun: boolean,
+): Promise<string> {
+  return invoke('library_bundle_import', { path, mode, dryRun });
+}
+
+export function command_library_catalog_export(
+  path: string | undefined,
+  filter: ExportFilter,
+  labels: CatalogLabels,
+): Promise<string> {
+  return invoke('library_catalog_export', { path, filter, labels });
+}
+
+export function command_library_external_columns(path: string): Promise<string[]> {
+  return invoke('library_external_columns', { path });
+}
+
+export function command_library_external_preview(
+  path: string,
+  source: ExternalSource,
+): Promise<string> {
+  return invoke('library_external_preview', { path, source });
+}
+
+export function command_library_external_import(
+  path: string,
+  source: ExternalSource,
+  selected: number[],
+  confirmed: number[],
+): Promise<string> {
+  return invoke('library_external_import', { path, source, selected, confirmed });
 }
 
 export function command_util_resolve_root(path: string, abs: boolean = true): Promise<string> {
@@ -45,3 +270,23 @@ export function command_util_resolve_root(path: string, abs: boolean = true): Pr
 export function command_api_fetch_dl_site_maniax(id: string): Promise<DLSiteInfo> {
   return invoke('api_fetch_dl_site_maniax', { id });
 }
+
+export function command_job_list(): Promise<Job[]> {
+  return invoke('job_list');
+}
+
+export function command_job_get(id: string): Promise<Job> {
+  return invoke('job_get', { id });
+}
+
+export function command_job_cancel(id: string): Promise<boolean> {
+  return invoke('job_cancel', { id });
+}
+
+export function command_job_retry(id: string): Promise<void> {
+  return invoke('job_retry', { id });
+}
+
+export function command_job_clear(): Promise<number> {
+  return invoke('job_clear');
+}
@@ -0,0 +1,53 @@
+import type { Job, JobProgress, Progress } from '@/api/types.ts';
+
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+
+import { command_job_get } from '@/api/command.ts';
+
+export const EVENT_JOB_UPDATED = 'job-updated';
+export const EVENT_JOB_PROGRESS = 'job-progress';
+
+export function listenJobUpdated(handler: (job: Job) => void): Promise<UnlistenFn> {
+  return listen<Job>(EVENT_JOB_UPDATED, (event) => handler(event.payload));
+}
+
+export function listenJobProgress(handler: (progress: JobProgress) => void): Promise<UnlistenFn> {
+  return listen<JobProgress>(EVENT_JOB_PROGRESS, (event) => handler(event.payload));
+}
+
+/**
+ * Wait until the job has ended, resolves with the job if finished,
+ * rejects with the error message if failed or cancelled.
+ */
+export async function waitJob(
+  id: string,
+  onProgress?: (progress: Progress) => void,
+): Promise<Job> {
+  let settle: (job: Job) => void = () => {};
+  const ended = new Promise<Job>((resolve, reject) => {
+    settle = (job: Job) => {
+      if (job.state === 'Finished') {
+        resolve(job);
+      } else if (job.state === 'Cancelled') {
+        reject('操作已取消');
+      } else if (typeof job.state === 'object') {
+        reject(job.state.Failed.error);
+      }
+    };
+  });
+
+  const unlistenUpdated = await listenJobUpdated((job) => {
+    if (job.id === id) settle(job);
+  });
+  const unlistenProgress = await listenJobProgress((progress) => {
+    if (progress.job === id) onProgress?.(progress.progress);
+  });
+  try {
+    // The job may have ended before listening
+    settle(await command_job_get(id));
+    return await ended;
+  } finally {
+    unlistenUpdated();
+    unlistenProgress();
+  }
+}
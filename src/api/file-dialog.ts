@@ -1,7 +1,7 @@
 import { Notify } from 'quasar';
 
 import { command_util_resolve_root } from '@/api/command.ts';
-import { open } from '@tauri-apps/plugin-dialog';
+import { open, save } from '@tauri-apps/plugin-dialog';
 import { openPath } from '@tauri-apps/plugin-opener';
 
 export async function openSelectFile(): Promise<string | null> {
@@ -27,6 +27,86 @@ export async function openSelectFile(): Promise<string | null> {
   }
 }
 
+const transferFilters = [
+  {
+    name: '数据库文件',
+    extensions: ['json', 'jsonl', 'ndjson', 'csv', 'toml'],
+  },
+];
+
+const bundleFilters = [
+  {
+    name: '便携包',
+    extensions: ['tar'],
+  },
+];
+
+export async function openSelectTransferFile(): Promise<string | null> {
+  try {
+    return await open({
+      multiple: false,
+      directory: false,
+      filters: [
+        {
+          name: '数据库文件或便携包',
+          extensions: [...transferFilters[0]!.extensions, ...bundleFilters[0]!.extensions],
+        },
+      ],
+    });
+  } catch (e) {
+    console.error(e);
+    Notify.create({
+      type: 'negative',
+      message: '选择文件失败',
+      caption: e as string,
+    });
+    return Promise.reject(e);
+  }
+}
+
+export async function openSelectExternalFile(extensions: string[]): Promise<string | null> {
+  try {
+    return await open({
+      multiple: false,
+      directory: false,
+      filters: [
+        {
+          name: '其他软件的库文件',
+          extensions,
+        },
+      ],
+    });
+  } catch (e) {
+    console.error(e);
+    Notify.create({
+      type: 'negative',
+      message: '选择文件失败',
+      caption: e as string,
+    });
+    return Promise.reject(e);
+  }
+}
+
+export async function openSaveTransferFile(
+  defaultPath: string,
+  bundle = false,
+): Promise<string | null> {
+  try {
+    return await save({
+      defaultPath,
+      filters: bundle ? bundleFilters : transferFilters,
+    });
+  } catch (e) {
+    console.error(e);
+    Notify.create({
+      type: 'negative',
+      message: '选择保存位置失败',
+      caption: e as string,
+    });
+    return Promise.reject(e);
+  }
+}
+
 export async function openSelectFolder(): Promise<string | null> {
   try {
     return await open({
@@ -69,12 +69,25 @@ export const ArchiveTypeOptions: QSelectOption[] = [
   { label: '目录', value: ArchiveType.Directory },
 ];
 
+export const enum ArchiveFormat {
+  SevenZ = 'SevenZ',
+  Zip = 'Zip',
+  TarZst = 'TarZst',
+}
+
+export const ArchiveFormatOptions: QSelectOption[] = [
+  { label: '7z', value: ArchiveFormat.SevenZ },
+  { label: 'zip', value: ArchiveFormat.Zip },
+  { label: 'tar.zst', value: ArchiveFormat.TarZst },
+];
+
 export type ArchiveInfo =
   | ArchiveType.Unset
   | {
       ArchiveFile: {
         path: string;
         password?: string;
+        format?: ArchiveFormat;
       };
     }
   | {
@@ -94,19 +107,420 @@ export const enum DeployType {
   Directory = 'Directory',
 }
 
+export const enum DeployMode {
+  Copy = 'Copy',
+  Symlink = 'Symlink',
+  HardLink = 'HardLink',
+  Reflink = 'Reflink',
+}
+
+export const DeployModeOptions: QSelectOption[] = [
+  { label: '复制', value: DeployMode.Copy },
+  { label: '符号链接', value: DeployMode.Symlink },
+  { label: '硬链接', value: DeployMode.HardLink },
+  { label: '写时复制', value: DeployMode.Reflink },
+];
+
 export type DeployInfo =
   | DeployType.Unset
   | {
       File: {
         path: string;
+        mode?: DeployMode;
       };
     }
   | {
       Directory: {
         path: string;
+        mode?: DeployMode;
       };
     };
 
+export type Deployment = {
+  id: string;
+  info: DeployInfo;
+  time: string;
+};
+
+export type DeployOffReport = {
+  removed: number;
+  modified: string[];
+  added: string[];
+  missing: string[];
+};
+
+export type DriftReport = {
+  missing: string[];
+  modified: string[];
+  extra: string[];
+};
+
+export type IntegrityStatus =
+  | 'Intact'
+  | 'Recorded'
+  | 'Unset'
+  | 'Missing'
+  | { Mismatch: { expected: string; actual: string } }
+  | { Unreadable: { error: string } };
+
+export type IntegrityReport = {
+  id: string;
+  title: string;
+  status: IntegrityStatus;
+};
+
+export type CheckIssue =
+  | { Undecodable: { error: string } }
+  | 'ArchiveMissing'
+  | { DeploymentStale: { deployment: string } }
+  | 'LegacyDeploy'
+  | { SizeMismatch: { stored: number; actual: number } };
+
+export type CheckFinding = {
+  id: string;
+  title?: string;
+  issues: CheckIssue[];
+};
+
+export type CheckReport = {
+  checked: number;
+  findings: CheckFinding[];
+};
+
+export type RepairReport = {
+  repaired: number;
+  quarantined: number;
+  unresolved: CheckFinding[];
+  export?: string;
+};
+
+export enum ImportMode {
+  Overwrite = 'Overwrite',
+  Skip = 'Skip',
+  KeepNewer = 'KeepNewer',
+  Merge = 'Merge',
+}
+
+export const ImportModeLabels: Record<ImportMode, string> = {
+  [ImportMode.Overwrite]: '覆盖本地条目',
+  [ImportMode.Skip]: '跳过已有条目',
+  [ImportMode.KeepNewer]: '保留较新的条目',
+  [ImportMode.Merge]: '逐字段合并',
+};
+
+export type ImportItem = {
+  id: string;
+  title: string;
+  fields: string[];
+};
+
+export type ImportReport = {
+  mode: ImportMode;
+  dry_run: boolean;
+  added: ImportItem[];
+  updated: ImportItem[];
+  unchanged: ImportItem[];
+  conflicts: ImportItem[];
+};
+
+export enum TransferFormat {
+  Json = 'Json',
+  JsonLines = 'JsonLines',
+  Csv = 'Csv',
+  Toml = 'Toml',
+}
+
+export const TransferFormatLabels: Record<TransferFormat, string> = {
+  [TransferFormat.Json]: 'JSON',
+  [TransferFormat.JsonLines]: 'JSON Lines',
+  [TransferFormat.Csv]: 'CSV',
+  [TransferFormat.Toml]: 'TOML',
+};
+
+export const TransferFormatExtensions: Record<TransferFormat, string> = {
+  [TransferFormat.Json]: 'json',
+  [TransferFormat.JsonLines]: 'jsonl',
+  [TransferFormat.Csv]: 'csv',
+  [TransferFormat.Toml]: 'toml',
+};
+
+export type ExportFilter = {
+  ids?: string[];
+  query?: LibraryQuery;
+};
+
+export type ExportReport = {
+  path: string;
+  format: TransferFormat;
+  entries: number;
+};
+
+export type BundleExportReport = {
+  path: string;
+  entries: number;
+  archives: number;
+  covers: number;
+  bytes: number;
+  without_archive: string[];
+};
+
+export type CatalogReport = {
+  path: string;
+  entries: number;
+};
+
+export type CatalogLabels = {
+  lang: string;
+  title: string;
+  summary: string;
+  search: string;
+  empty: string;
+  back: string;
+  content_type: string;
+  platform: string;
+  developer: string;
+  publisher: string;
+  version: string;
+  size: string;
+  tags: string;
+  time_created: string;
+  time_updated: string;
+  content_types: Partial<Record<ContentType, string>>;
+};
+
+// {count}, {total} and {time} in the summary are filled in by the export
+export const CatalogPageLabels: CatalogLabels = {
+  lang: 'zh-CN',
+  title: '收藏目录',
+  summary: '共 {count} / {total} 个条目，生成于 {time}',
+  search: '搜索标题、别名、标签、开发商或平台',
+  empty: '没有匹配的条目',
+  back: '← 返回目录',
+  content_type: '类型',
+  platform: '平台',
+  developer: '开发商',
+  publisher: '发行商',
+  version: '版本',
+  size: '大小',
+  tags: '标签',
+  time_created: '添加时间',
+  time_updated: '更新时间',
+  content_types: Object.fromEntries(
+    ContentTypeOptions.map((option) => [option.value, option.label]),
+  ),
+};
+
+export type BundleImportReport = ImportReport & {
+  extracted: string[];
+  reused: string[];
+  damaged: ImportItem[];
+};
+
+export type OrphanArchive = {
+  path: string;
+  size: number;
+  time_modified: string;
+  platform: DistributionPlatform;
+};
+
+export type DuplicateReason =
+  | { Platform: { platform: DistributionPlatform } }
+  | { Hash: { hash: string } }
+  | 'Title';
+
+export type DuplicateGroup = {
+  reason: DuplicateReason;
+  ids: string[];
+};
+
+// Unset id for a record created along, which is not in the library yet
+export type DuplicateMatch = {
+  id?: string;
+  title: string;
+  reason: DuplicateReason;
+};
+
+export type CsvMapping = {
+  title: string;
+  alias?: string;
+  tags?: string;
+  content_type?: string;
+  platform?: string;
+  platform_id?: string;
+  description?: string;
+  developer?: string;
+  publisher?: string;
+  version?: string;
+  separator?: string;
+};
+
+export type ExternalSource = 'Playnite' | 'LaunchBox' | { Csv: { mapping: CsvMapping } };
+
+export type ExternalCandidate = {
+  index: number;
+  opt: Partial<Metadata>;
+  duplicates: DuplicateMatch[];
+};
+
+export type ExternalPreview = {
+  candidates: ExternalCandidate[];
+  skipped: number;
+};
+
+export type ExternalImportReport = {
+  created: string[];
+  duplicates: ExternalCandidate[];
+};
+
+export const enum SortField {
+  Title = 'Title',
+  Size = 'Size',
+  Created = 'Created',
+  Updated = 'Updated',
+}
+
+export type LibraryQuery = {
+  text?: string;
+  content_type?: ContentType[];
+  platform?: PlatformType[];
+  deployed?: boolean;
+  size_min?: number;
+  size_max?: number;
+  created_after?: string;
+  created_before?: string;
+  updated_after?: string;
+  updated_before?: string;
+  sort?: SortField;
+  descending?: boolean;
+  offset?: number;
+  limit?: number;
+};
+
+export type QueryPage = {
+  total: number;
+  items: Metadata[];
+};
+
+export const enum LibraryIndex {
+  Tag = 'Tag',
+  // Keyed by `Steam:<id>`, `DLSite:<ID>` or `Other:<name>:<id>`
+  Platform = 'Platform',
+  Developer = 'Developer',
+  ContentType = 'ContentType',
+}
+
+export type QuarantinedRecord = {
+  id: string;
+  error: string;
+  document?: unknown;
+};
+
+export const enum ChangeOperation {
+  Create = 'Create',
+  Patch = 'Patch',
+  Remove = 'Remove',
+  Deploy = 'Deploy',
+  DeployOff = 'DeployOff',
+  Repair = 'Repair',
+  Verify = 'Verify',
+  Merge = 'Merge',
+  Import = 'Import',
+  Clear = 'Clear',
+  TrashRestore = 'TrashRestore',
+  Recover = 'Recover',
+  Restore = 'Restore',
+  Undo = 'Undo',
+  Redo = 'Redo',
+  BackupRestore = 'BackupRestore',
+}
+
+export const ChangeOperationLabels: Record<ChangeOperation, string> = {
+  [ChangeOperation.Create]: '创建',
+  [ChangeOperation.Patch]: '编辑',
+  [ChangeOperation.Remove]: '删除',
+  [ChangeOperation.Deploy]: '部署',
+  [ChangeOperation.DeployOff]: '取消部署',
+  [ChangeOperation.Repair]: '修复部署',
+  [ChangeOperation.Verify]: '校验',
+  [ChangeOperation.Merge]: '合并',
+  [ChangeOperation.Import]: '导入',
+  [ChangeOperation.Clear]: '清空库',
+  [ChangeOperation.TrashRestore]: '从回收站恢复',
+  [ChangeOperation.Recover]: '从隔离区恢复',
+  [ChangeOperation.Restore]: '恢复历史版本',
+  [ChangeOperation.Undo]: '撤销',
+  [ChangeOperation.Redo]: '重做',
+  [ChangeOperation.BackupRestore]: '从备份恢复',
+};
+
+export type FieldChange = {
+  field: string;
+  before?: unknown;
+  after?: unknown;
+};
+
+export type ChangeRecord = {
+  id: string;
+  seq: number;
+  time: string;
+  operation: ChangeOperation;
+  changes: FieldChange[];
+  snapshot?: Metadata;
+};
+
+export type UndoSummary = {
+  operation: ChangeOperation;
+  time: string;
+  entries: number;
+};
+
+export type UndoState = {
+  undo?: UndoSummary;
+  redo?: UndoSummary;
+  undo_count: number;
+  redo_count: number;
+};
+
+export type TrashEntry = {
+  metadata: Metadata;
+  time_deleted: string;
+};
+
+export enum BackupKind {
+  Auto = 'Auto',
+  Snapshot = 'Snapshot',
+  Migration = 'Migration',
+}
+
+export const BackupKindLabels: Record<BackupKind, string> = {
+  [BackupKind.Auto]: '自动备份',
+  [BackupKind.Snapshot]: '快照',
+  [BackupKind.Migration]: '迁移前备份',
+};
+
+export type BackupInfo = {
+  id: string;
+  kind: BackupKind;
+  name?: string;
+  time: string;
+  size: number;
+  entries?: number;
+  error?: string;
+};
+
+export type BackupRestoreReport = {
+  entries: number;
+  undecodable: number;
+  snapshot: string;
+};
+
+export type SaveSnapshot = {
+  id: string;
+  time: string;
+  files: string[];
+  size: number;
+};
+
 export type Metadata = {
   id: string;
   title: string;
@@ -117,10 +531,13 @@ export type Metadata = {
   description?: string;
   developer?: string;
   publisher?: string;
+  cover?: string;
   version?: string;
   archive_info: ArchiveInfo;
   archive_size: number;
-  deploy_info: DeployInfo;
+  archive_hash?: string;
+  deployments: Deployment[];
+  save_paths: string[];
   time_created: string;
   time_updated: string;
 };
@@ -130,7 +547,75 @@ type RequireFields<T, K extends keyof T> = Omit<T, K> & Required<Pick<T, K>>;
 export type MetadataOptional = RequireFields<
   Partial<Metadata>,
   'title' | 'alias' | 'tags' | 'platform' | 'archive_info'
->;
+> & {
+  flag_create_archive?: boolean;
+  archive_format?: ArchiveFormat;
+};
+
+export type Progress = {
+  id: string;
+  label: string;
+  bytes_processed: number;
+  bytes_total: number;
+  files_processed: number;
+  files_total: number;
+  current_file: string;
+  eta_secs?: number;
+};
+
+export type JobKind =
+  | { Update: { opt: MetadataOptional; redacted?: boolean } }
+  | { Deploy: { key: string; target: string; mode?: DeployMode } }
+  | { DeployOff: { key: string; deployment: string } }
+  | { VerifyDeployment: { key: string; deployment: string } }
+  | { RepairDeployment: { key: string; deployment: string } }
+  | 'Verify'
+  | 'Check'
+  | 'Repair'
+  | 'Duplicates'
+  | { TrashEmpty: { delete_archive?: boolean } }
+  | { BackupRestore: { id: string } }
+  | { OrphanAdopt: { paths: string[] } }
+  | {
+      Import: {
+        path?: string;
+        format?: TransferFormat;
+        mode?: ImportMode;
+        dry_run?: boolean;
+      };
+    }
+  | { Export: { path?: string; format?: TransferFormat; filter?: ExportFilter } }
+  | { BundleImport: { path: string; mode?: ImportMode; dry_run?: boolean } }
+  | { BundleExport: { path?: string; filter?: ExportFilter } }
+  | { CatalogExport: { path?: string; filter?: ExportFilter; labels?: CatalogLabels } }
+  | { ExternalPreview: { path: string; source: ExternalSource } }
+  | {
+      ExternalImport: {
+        path: string;
+        source: ExternalSource;
+        selected: number[];
+        confirmed?: number[];
+      };
+    };
+
+export type JobState = 'Queued' | 'Running' | 'Finished' | { Failed: { error: string } } | 'Cancelled';
+
+export type Job = {
+  id: string;
+  kind: JobKind;
+  state: JobState;
+  result?: unknown;
+  progress?: Progress;
+  attempts: number;
+  time_created: string;
+  time_started?: string;
+  time_finished?: string;
+};
+
+export type JobProgress = {
+  job: string;
+  progress: Progress;
+};
 
 // API
 
@@ -3,7 +3,7 @@ import '@quasar/extras/material-icons/material-icons.css';
 import '@quasar/extras/fontawesome-v6/fontawesome-v6.css';
 import 'quasar/src/css/index.sass';
 
-import { Loading, Notify, Quasar } from 'quasar';
+import { Dialog, Loading, Notify, Quasar } from 'quasar';
 import quasarLang from 'quasar/lang/zh-CN';
 import { createApp } from 'vue';
 
@@ -17,6 +17,7 @@ app.use(pinia);
 app.use(router);
 app.use(Quasar, {
   plugins: {
+    Dialog,
     Notify,
     Loading,
   },
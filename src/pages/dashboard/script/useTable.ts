@@ -1,9 +1,11 @@
-import type { Metadata } from '@/api/types.ts';
+import type { ContentType, LibraryQuery, Metadata } from '@/api/types.ts';
 
 import { storeToRefs } from 'pinia';
 import { computed, ref } from 'vue';
+import { watchDebounced } from '@vueuse/core';
 
 import { PlatformType } from '@/api/types.ts';
+import { command_library_query } from '@/api/command.ts';
 import { useLibraryStore } from '@/pages/dashboard/store.ts';
 
 export const useTable = () => {
@@ -11,36 +13,62 @@ export const useTable = () => {
 
   const searchTag = ref('');
   const searchByRegex = ref(false);
+  const filterContentType = ref<ContentType[] | null>(null);
+  const filterDeployed = ref<boolean | null>(null);
 
-  const searchFunc = computed(() =>
-    searchByRegex.value
-      ? (a: string, b: string) => a.match(b)
-      : (a: string, b: string) => a.includes(b),
-  );
-  const filterFunc = computed(
-    () => (rows: Metadata[]) =>
-      rows.filter(
-        (row) =>
-          searchFunc.value(row.title, searchTag.value) ||
-          row.alias.some((alias) => searchFunc.value(alias, searchTag.value)) ||
-          row.tags.some((tag) => searchFunc.value(tag, searchTag.value)) ||
-          (row.developer && searchFunc.value(row.developer, searchTag.value)) ||
-          (row.publisher && searchFunc.value(row.publisher, searchTag.value)) ||
-          (row.platform != PlatformType.Unknown &&
-            PlatformType.Steam in row.platform &&
-            searchFunc.value(row.platform.Steam.id, searchTag.value)) ||
-          (row.platform != PlatformType.Unknown &&
-            PlatformType.DLSite in row.platform &&
-            searchFunc.value(row.platform.DLSite.id, searchTag.value)) ||
-          (row.platform != PlatformType.Unknown &&
-            PlatformType.Other in row.platform &&
-            (searchFunc.value(row.platform.Other.name, searchTag.value) ||
-              (row.platform.Other.id && searchFunc.value(row.platform.Other.id, searchTag.value)))),
-      ),
-  );
-  const filteredRows = computed(() =>
-    searchTag.value ? filterFunc.value(data.value) : data.value,
+  // Regex search is not supported by the backend query, it stays in JS
+  const regexFilter = (rows: Metadata[]) => {
+    const match = (a: string) => a.match(searchTag.value);
+    return rows.filter(
+      (row) =>
+        match(row.title) ||
+        row.alias.some(match) ||
+        row.tags.some(match) ||
+        (row.developer && match(row.developer)) ||
+        (row.publisher && match(row.publisher)) ||
+        (row.platform != PlatformType.Unknown &&
+          PlatformType.Steam in row.platform &&
+          match(row.platform.Steam.id)) ||
+        (row.platform != PlatformType.Unknown &&
+          PlatformType.DLSite in row.platform &&
+          match(row.platform.DLSite.id)) ||
+        (row.platform != PlatformType.Unknown &&
+          PlatformType.Other in row.platform &&
+          (match(row.platform.Other.name) ||
+            (row.platform.Other.id && match(row.platform.Other.id)))),
+    );
+  };
+
+  const query = computed<LibraryQuery | undefined>(() => {
+    const text = searchByRegex.value ? undefined : searchTag.value.trim() || undefined;
+    const content_type = filterContentType.value?.length ? filterContentType.value : undefined;
+    const deployed = filterDeployed.value ?? undefined;
+    if (text == undefined && content_type == undefined && deployed == undefined) {
+      return undefined;
+    }
+    return { text, content_type, deployed };
+  });
+  const queriedRows = ref<Metadata[]>([]);
+  watchDebounced(
+    [query, data],
+    async () => {
+      if (!query.value) {
+        return;
+      }
+      try {
+        queriedRows.value = (await command_library_query(query.value)).items;
+      } catch (e) {
+        console.error(e);
+        queriedRows.value = [];
+      }
+    },
+    { debounce: 200, immediate: true },
   );
 
-  return { searchTag, searchByRegex, filteredRows };
+  const filteredRows = computed(() => {
+    const rows = query.value ? queriedRows.value : data.value;
+    return searchByRegex.value && searchTag.value ? regexFilter(rows) : rows;
+  });
+
+  return { searchTag, searchByRegex, filterContentType, filterDeployed, filteredRows };
 };
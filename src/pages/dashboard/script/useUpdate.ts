@@ -1,19 +1,24 @@
 import type { QForm } from 'quasar';
 import type { Ref } from 'vue';
-import type { ArchiveInfo, DistributionPlatform, MetadataOptional } from '@/api/types.ts';
+import type {
+  ArchiveInfo,
+  DistributionPlatform,
+  DuplicateMatch,
+  MetadataOptional,
+} from '@/api/types.ts';
 
 import { cloneDeep } from 'lodash-es';
 import { useQuasar } from 'quasar';
 import { computed, ref, watch } from 'vue';
 
-import { command_api_fetch_dl_site_maniax } from '@/api/command.ts';
-import { ArchiveType, ContentType, PlatformType } from '@/api/types.ts';
+import { command_api_fetch_dl_site_maniax, command_metadata_duplicates } from '@/api/command.ts';
+import { ArchiveFormat, ArchiveType, ContentType, PlatformType } from '@/api/types.ts';
 import { removeEmptyStrings } from '@/api/util.ts';
 import { useLibraryStore } from '@/pages/dashboard/store.ts';
 import { get, set } from '@vueuse/core';
 
 export const useUpdate = (id: Ref<string>, formRef: Ref<QForm>) => {
-  const { notify, loading } = useQuasar();
+  const { notify, loading, dialog } = useQuasar();
   const library = useLibraryStore();
   const current = computed<MetadataOptional | undefined>(() =>
     library.data.find((val) => val.id === id.value),
@@ -41,6 +46,7 @@ export const useUpdate = (id: Ref<string>, formRef: Ref<QForm>) => {
     });
     set(cAlias, '');
     set(cTag, '');
+    set(cSavePath, '');
     set(cPlatformType, PlatformType.Unknown);
     set(cPlatformID, '');
     set(cPlatformName, '');
@@ -48,6 +54,7 @@ export const useUpdate = (id: Ref<string>, formRef: Ref<QForm>) => {
     set(cArchiveType, ArchiveType.Unset);
     set(cArchivePath, '');
     set(cArchivePassword, '');
+    set(cArchiveFormat, ArchiveFormat.SevenZ);
   };
 
   const cAlias = ref('');
@@ -131,6 +138,37 @@ export const useUpdate = (id: Ref<string>, formRef: Ref<QForm>) => {
     edit.value.tags?.splice(idx, 1);
   };
 
+  const cSavePath = ref('');
+  const addSavePath = () => {
+    if (!edit.value.save_paths) {
+      edit.value.save_paths = [];
+    }
+
+    const trimInput = cSavePath.value.trim();
+    if (!trimInput) {
+      notify({
+        message: '存档路径不能为空',
+        color: 'warning',
+        position: 'top',
+        icon: 'warning',
+      });
+      return;
+    } else if (edit.value.save_paths.includes(trimInput)) {
+      notify({
+        message: `存档路径 '${trimInput}' 已存在`,
+        color: 'warning',
+        position: 'top',
+        icon: 'warning',
+      });
+      return;
+    }
+    edit.value.save_paths.push(trimInput);
+    cSavePath.value = '';
+  };
+  const delSavePath = (idx: number) => {
+    edit.value.save_paths?.splice(idx, 1);
+  };
+
   const cPlatformType = ref<PlatformType>(PlatformType.Unknown);
   const cPlatformID = ref('');
   const cPlatformName = ref('');
@@ -155,6 +193,7 @@ export const useUpdate = (id: Ref<string>, formRef: Ref<QForm>) => {
   const cArchiveType = ref<ArchiveType>(ArchiveType.Unset);
   const cArchivePath = ref('');
   const cArchivePassword = ref('');
+  const cArchiveFormat = ref<ArchiveFormat>(ArchiveFormat.SevenZ);
   const mapArchiveInfo = (): ArchiveInfo => {
     switch (cArchiveType.value) {
       case ArchiveType.Unset:
@@ -165,11 +204,13 @@ export const useUpdate = (id: Ref<string>, formRef: Ref<QForm>) => {
               ArchiveFile: {
                 path: cArchivePath.value,
                 password: cArchivePassword.value,
+                format: cArchiveFormat.value,
               },
             }
           : {
               ArchiveFile: {
                 path: cArchivePath.value,
+                format: cArchiveFormat.value,
               },
             };
       case ArchiveType.CommonFile:
@@ -223,6 +264,19 @@ export const useUpdate = (id: Ref<string>, formRef: Ref<QForm>) => {
     }
   };
 
+  // Ask before creating an entry which is likely already in the library
+  const confirmDuplicates = (duplicates: DuplicateMatch[]) =>
+    new Promise<boolean>((resolve) => {
+      dialog({
+        title: '可能重复',
+        message: `已有条目${duplicates.map((d) => `「${d.title}」`).join('、')}可能是同一作品，仍然创建？`,
+        cancel: true,
+        persistent: true,
+      })
+        .onOk(() => resolve(true))
+        .onCancel(() => resolve(false));
+    });
+
   const doUpdate = async (): Promise<boolean> => {
     if (await formRef.value.validate()) {
       edit.value.platform = mapPlatform();
@@ -233,6 +287,14 @@ export const useUpdate = (id: Ref<string>, formRef: Ref<QForm>) => {
       if (get(fCreateArchive) && get(cArchiveType) != ArchiveType.Unset) {
         // If we are creating a new item, we need to set the creation flag
         editCopy['flag_create_archive'] = true;
+        editCopy['archive_format'] = get(cArchiveFormat);
+      }
+
+      if (!get(mode)) {
+        const duplicates = await command_metadata_duplicates(editCopy);
+        if (duplicates.length > 0 && !(await confirmDuplicates(duplicates))) {
+          return false;
+        }
       }
 
       await library.update(editCopy);
@@ -291,6 +353,7 @@ export const useUpdate = (id: Ref<string>, formRef: Ref<QForm>) => {
           set(cArchiveType, ArchiveType.ArchiveFile);
           set(cArchivePath, clone.archive_info.ArchiveFile.path);
           set(cArchivePassword, clone.archive_info.ArchiveFile.password ?? '');
+          set(cArchiveFormat, clone.archive_info.ArchiveFile.format ?? ArchiveFormat.SevenZ);
         } else if (ArchiveType.CommonFile in clone.archive_info) {
           console.log(2);
           set(cArchiveType, ArchiveType.CommonFile);
@@ -329,6 +392,10 @@ export const useUpdate = (id: Ref<string>, formRef: Ref<QForm>) => {
     cTag,
     addTag,
     delTag,
+    // Helper SAVE PATH methods
+    cSavePath,
+    addSavePath,
+    delSavePath,
     // Helper PLATFORM methods
     cPlatformType,
     cPlatformID,
@@ -338,6 +405,7 @@ export const useUpdate = (id: Ref<string>, formRef: Ref<QForm>) => {
     cArchiveType,
     cArchivePath,
     cArchivePassword,
+    cArchiveFormat,
     apiFetchDLInfo,
   };
 };
@@ -1,28 +1,70 @@
 import type { QTableProps } from 'quasar';
-import type { Metadata, MetadataOptional } from '@/api/types.ts';
+import type {
+  BundleExportReport,
+  BundleImportReport,
+  CatalogReport,
+  DeployMode,
+  DeployOffReport,
+  DriftReport,
+  ExportFilter,
+  ExportReport,
+  ExternalImportReport,
+  ExternalSource,
+  ImportMode,
+  ImportReport,
+  IntegrityReport,
+  Metadata,
+  MetadataOptional,
+  Progress,
+  TransferFormat,
+  UndoState,
+  UndoSummary,
+} from '@/api/types.ts';
 
 import { defineStore } from 'pinia';
 import { Loading, Notify } from 'quasar';
 
 import {
+  command_library_bundle_export,
+  command_library_bundle_import,
+  command_library_catalog_export,
   command_library_clear,
   command_library_export,
+  command_library_external_import,
   command_library_import,
+  command_library_rebuild_index,
+  command_library_redo,
+  command_library_undo,
+  command_library_undo_state,
+  command_library_verify,
   command_metadata_deploy,
   command_metadata_deploy_off,
   command_metadata_get_all,
   command_metadata_remove,
+  command_metadata_repair_deployment,
   command_metadata_update,
+  command_metadata_verify_deployment,
 } from '@/api/command.ts';
+import { waitJob } from '@/api/event.ts';
 import { formatBytes } from '@/api/util.ts';
+import { CatalogPageLabels, ChangeOperationLabels } from '@/api/types.ts';
+
+const progressMessage = (prefix: string, progress: Progress): string => {
+  const percent =
+    progress.bytes_total > 0 ? (progress.bytes_processed / progress.bytes_total) * 100 : 0;
+  const eta = progress.eta_secs != undefined ? `，剩余约 ${progress.eta_secs} 秒` : '';
+  return `${prefix} ${percent.toFixed(1)}% (${progress.files_processed}/${progress.files_total})${eta}`;
+};
 
 interface LibraryState {
   data: Metadata[];
+  undoState: UndoState | null;
 }
 
 export const useLibraryStore = defineStore('library', {
   state: (): LibraryState => ({
     data: [],
+    undoState: null,
   }),
   getters: {
     totalSize: (state): string =>
@@ -55,17 +97,15 @@ export const useLibraryStore = defineStore('library', {
       }
     },
     async update(opt: MetadataOptional) {
+      const prefix = !!opt.id ? `更新条目 ${opt.id}` : '创建新条目';
       try {
-        if (!!opt.id) {
-          Loading.show({
-            message: `更新条目 ${opt.id}...`,
-          });
-        } else {
-          Loading.show({
-            message: '创建新条目...',
-          });
-        }
-        await command_metadata_update(opt);
+        Loading.show({
+          message: `${prefix}...`,
+        });
+        const job = await command_metadata_update(opt);
+        await waitJob(job, (progress) =>
+          Loading.show({ message: progressMessage(prefix, progress) }),
+        );
         try {
           this.data = await command_metadata_get_all();
           Notify.create({
@@ -117,7 +157,7 @@ export const useLibraryStore = defineStore('library', {
         Loading.hide();
       }
     },
-    async deploy(key: string, target: string) {
+    async deploy(key: string, target: string, mode: DeployMode) {
       if (!target) {
         Notify.create({
           message: '部署目标不能为空',
@@ -132,7 +172,10 @@ export const useLibraryStore = defineStore('library', {
         Loading.show({
           message: `正在部署 ${key} 到 ${target}...`,
         });
-        await command_metadata_deploy(key, target);
+        const job = await command_metadata_deploy(key, target, mode);
+        await waitJob(job, (progress) =>
+          Loading.show({ message: progressMessage(`正在部署 ${key}`, progress) }),
+        );
         this.data = await command_metadata_get_all();
         Notify.create({
           message: `部署成功`,
@@ -152,15 +195,85 @@ export const useLibraryStore = defineStore('library', {
         Loading.hide();
       }
     },
-    async deployOff(key: string) {
+    async deployOff(key: string, deployment: string) {
       try {
         Loading.show({
           message: `正在取消部署 ${key}...`,
         });
-        await command_metadata_deploy_off(key);
+        const job = await waitJob(await command_metadata_deploy_off(key, deployment));
+        const report = job.result as DeployOffReport;
         this.data = await command_metadata_get_all();
+        const kept = report.modified.length + report.added.length;
         Notify.create({
-          message: `取消部署成功`,
+          message: kept > 0 ? `取消部署完成，保留了 ${kept} 个已修改或新增的文件` : `取消部署成功`,
+          caption: kept > 0 ? [...report.modified, ...report.added].join(', ') : undefined,
+          color: kept > 0 ? 'warning' : 'positive',
+          position: 'top',
+          icon: kept > 0 ? 'warning' : 'cloud_done',
+        });
+      } catch (e) {
+        console.error(e);
+        Notify.create({
+          message: `取消部署失败: ${key}`,
+          caption: e as string,
+          color: 'negative',
+          position: 'top',
+          icon: 'error',
+        });
+      } finally {
+        Loading.hide();
+      }
+    },
+    async verifyDeployment(key: string, deployment: string) {
+      try {
+        Loading.show({
+          message: `正在校验部署 ${key}...`,
+        });
+        const job = await command_metadata_verify_deployment(key, deployment);
+        const result = await waitJob(job, (progress) =>
+          Loading.show({ message: progressMessage(`正在校验部署 ${key}`, progress) }),
+        );
+        const report = result.result as DriftReport;
+        const damaged = report.missing.length + report.modified.length;
+        Notify.create({
+          message:
+            damaged > 0
+              ? `部署已损坏：缺失 ${report.missing.length} 个，修改 ${report.modified.length} 个，多出 ${report.extra.length} 个文件`
+              : report.extra.length > 0
+                ? `部署完好，多出 ${report.extra.length} 个文件`
+                : `部署完好`,
+          caption: damaged > 0 ? [...report.missing, ...report.modified].join(', ') : undefined,
+          color: damaged > 0 ? 'warning' : 'positive',
+          position: 'top',
+          icon: damaged > 0 ? 'warning' : 'cloud_done',
+        });
+      } catch (e) {
+        console.error(e);
+        Notify.create({
+          message: `校验部署失败: ${key}`,
+          caption: e as string,
+          color: 'negative',
+          position: 'top',
+          icon: 'error',
+        });
+      } finally {
+        Loading.hide();
+      }
+    },
+    async repairDeployment(key: string, deployment: string) {
+      try {
+        Loading.show({
+          message: `正在修复部署 ${key}...`,
+        });
+        const job = await command_metadata_repair_deployment(key, deployment);
+        const result = await waitJob(job, (progress) =>
+          Loading.show({ message: progressMessage(`正在修复部署 ${key}`, progress) }),
+        );
+        const report = result.result as DriftReport;
+        this.data = await command_metadata_get_all();
+        const damaged = report.missing.length + report.modified.length;
+        Notify.create({
+          message: damaged > 0 ? `已修复 ${damaged} 个损坏的文件` : `部署完好，无需修复`,
           color: 'positive',
           position: 'top',
           icon: 'cloud_done',
@@ -168,7 +281,7 @@ export const useLibraryStore = defineStore('library', {
       } catch (e) {
         console.error(e);
         Notify.create({
-          message: `取消部署失败: ${key}`,
+          message: `修复部署失败: ${key}`,
           caption: e as string,
           color: 'negative',
           position: 'top',
@@ -183,7 +296,7 @@ export const useLibraryStore = defineStore('library', {
         Loading.show({
           message: '正在自动导出库...',
         });
-        await command_library_export();
+        await waitJob(await command_library_export());
         Loading.hide();
         Loading.show({
           message: '正在清空库...',
@@ -209,16 +322,25 @@ export const useLibraryStore = defineStore('library', {
         Loading.hide();
       }
     },
-    async export() {
+    async export(
+      path?: string,
+      format?: TransferFormat,
+      filter: ExportFilter = {},
+    ): Promise<ExportReport | null> {
       try {
-        Loading.show();
-        await command_library_export();
+        Loading.show({
+          message: '正在导出库...',
+        });
+        const job = await waitJob(await command_library_export(path, format, filter));
+        const report = job.result as ExportReport;
         Notify.create({
           message: '导出库成功',
+          caption: `已导出 ${report.entries} 个条目到 ${report.path}`,
           color: 'positive',
           position: 'top',
           icon: 'cloud_done',
         });
+        return report;
       } catch (e) {
         console.error(e);
         Notify.create({
@@ -228,23 +350,134 @@ export const useLibraryStore = defineStore('library', {
           position: 'top',
           icon: 'error',
         });
+        return null;
       } finally {
         Loading.hide();
       }
     },
-    async import() {
+    async verify() {
       try {
         Loading.show({
-          message: '正在导入库...',
+          message: '正在校验库...',
         });
-        await command_library_import();
-        this.data = await command_metadata_get_all();
+        const job = await command_library_verify();
+        const result = await waitJob(job, (progress) =>
+          Loading.show({ message: progressMessage('正在校验库', progress) }),
+        );
+        const reports = result.result as IntegrityReport[];
+        const issues = reports.filter(
+          (report) => !['Intact', 'Recorded', 'Unset'].includes(report.status as string),
+        );
         Notify.create({
-          message: '导入库成功',
+          message:
+            issues.length > 0
+              ? `校验完成，${issues.length} 个条目存在问题`
+              : `校验完成，${reports.length} 个条目均完好`,
+          caption: issues.length > 0 ? issues.map((report) => report.title).join(', ') : undefined,
+          color: issues.length > 0 ? 'warning' : 'positive',
+          position: 'top',
+          icon: issues.length > 0 ? 'warning' : 'cloud_done',
+        });
+      } catch (e) {
+        console.error(e);
+        Notify.create({
+          message: '校验库失败',
+          caption: e as string,
+          color: 'negative',
+          position: 'top',
+          icon: 'error',
+        });
+      } finally {
+        Loading.hide();
+      }
+    },
+    async refreshUndo() {
+      try {
+        this.undoState = await command_library_undo_state();
+      } catch (e) {
+        console.error(e);
+        this.undoState = null;
+      }
+    },
+    async undo() {
+      await this.applyUndo(command_library_undo, '撤销');
+    },
+    async redo() {
+      await this.applyUndo(command_library_redo, '重做');
+    },
+    async applyUndo(command: () => Promise<UndoSummary | null>, label: string) {
+      try {
+        const summary = await command();
+        if (summary) {
+          this.data = await command_metadata_get_all();
+          Notify.create({
+            message: `已${label}${ChangeOperationLabels[summary.operation]}，共 ${summary.entries} 个条目`,
+            color: 'positive',
+            position: 'top',
+            icon: 'cloud_done',
+          });
+        }
+      } catch (e) {
+        console.error(e);
+        Notify.create({
+          message: `${label}失败`,
+          caption: e as string,
+          color: 'negative',
+          position: 'top',
+          icon: 'error',
+        });
+      } finally {
+        await this.refreshUndo();
+      }
+    },
+    async rebuildIndex() {
+      try {
+        Loading.show({
+          message: '正在重建索引...',
+        });
+        const count = await command_library_rebuild_index();
+        Notify.create({
+          message: `重建索引成功，共 ${count} 个条目`,
           color: 'positive',
           position: 'top',
           icon: 'cloud_done',
         });
+      } catch (e) {
+        console.error(e);
+        Notify.create({
+          message: '重建索引失败',
+          caption: e as string,
+          color: 'negative',
+          position: 'top',
+          icon: 'error',
+        });
+      } finally {
+        Loading.hide();
+      }
+    },
+    async import(
+      path: string | undefined,
+      format: TransferFormat | undefined,
+      mode: ImportMode,
+      dryRun = false,
+    ): Promise<ImportReport | null> {
+      try {
+        Loading.show({
+          message: dryRun ? '正在预览导入...' : '正在导入库...',
+        });
+        const job = await waitJob(await command_library_import(path, format, mode, dryRun));
+        const report = job.result as ImportReport;
+        if (!dryRun) {
+          this.data = await command_metadata_get_all();
+          Notify.create({
+            message: '导入库成功',
+            caption: `新增 ${report.added.length}，更新 ${report.updated.length}，冲突 ${report.conflicts.length}`,
+            color: 'positive',
+            position: 'top',
+            icon: 'cloud_done',
+          });
+        }
+        return report;
       } catch (e) {
         console.error(e);
         Notify.create({
@@ -254,10 +487,159 @@ export const useLibraryStore = defineStore('library', {
           position: 'top',
           icon: 'error',
         });
+        return null;
+      } finally {
+        Loading.hide();
+      }
+    },
+    async bundleExport(
+      path?: string,
+      filter: ExportFilter = {},
+    ): Promise<BundleExportReport | null> {
+      try {
+        Loading.show({
+          message: '正在导出便携包...',
+        });
+        const job = await command_library_bundle_export(path, filter);
+        const result = await waitJob(job, (progress) =>
+          Loading.show({ message: progressMessage('正在导出便携包', progress) }),
+        );
+        const report = result.result as BundleExportReport;
+        Notify.create({
+          message: '导出便携包成功',
+          caption: `已导出 ${report.entries} 个条目、${report.archives} 个压缩包和 ${report.covers} 张封面（${formatBytes(report.bytes)}）到 ${report.path}`,
+          color: report.without_archive.length > 0 ? 'warning' : 'positive',
+          position: 'top',
+          icon: 'cloud_done',
+        });
+        return report;
+      } catch (e) {
+        console.error(e);
+        Notify.create({
+          message: '导出便携包失败',
+          caption: e as string,
+          color: 'negative',
+          position: 'top',
+          icon: 'error',
+        });
+        return null;
+      } finally {
+        Loading.hide();
+      }
+    },
+    async catalogExport(path?: string, filter: ExportFilter = {}): Promise<CatalogReport | null> {
+      try {
+        Loading.show({
+          message: '正在导出静态网页...',
+        });
+        const job = await command_library_catalog_export(path, filter, CatalogPageLabels);
+        const result = await waitJob(job, (progress) =>
+          Loading.show({ message: progressMessage('正在生成条目页面', progress) }),
+        );
+        const report = result.result as CatalogReport;
+        Notify.create({
+          message: '导出静态网页成功',
+          caption: `已导出 ${report.entries} 个条目到 ${report.path}，用浏览器打开其中的 index.html 即可查看`,
+          color: 'positive',
+          position: 'top',
+          icon: 'cloud_done',
+        });
+        return report;
+      } catch (e) {
+        console.error(e);
+        Notify.create({
+          message: '导出静态网页失败',
+          caption: e as string,
+          color: 'negative',
+          position: 'top',
+          icon: 'error',
+        });
+        return null;
       } finally {
         Loading.hide();
       }
     },
+    async bundleImport(
+      path: string,
+      mode: ImportMode,
+      dryRun = false,
+    ): Promise<BundleImportReport | null> {
+      try {
+        Loading.show({
+          message: dryRun ? '正在预览导入...' : '正在导入便携包...',
+        });
+        const job = await command_library_bundle_import(path, mode, dryRun);
+        const result = await waitJob(job, (progress) =>
+          Loading.show({ message: progressMessage('正在解压压缩包', progress) }),
+        );
+        const report = result.result as BundleImportReport;
+        if (!dryRun) {
+          this.data = await command_metadata_get_all();
+          Notify.create({
+            message: '导入便携包成功',
+            caption: `新增 ${report.added.length}，更新 ${report.updated.length}，解压 ${report.extracted.length} 个压缩包，损坏 ${report.damaged.length}`,
+            color: report.damaged.length > 0 ? 'warning' : 'positive',
+            position: 'top',
+            icon: 'cloud_done',
+          });
+        }
+        return report;
+      } catch (e) {
+        console.error(e);
+        Notify.create({
+          message: '导入便携包失败',
+          caption: e as string,
+          color: 'negative',
+          position: 'top',
+          icon: 'error',
+        });
+        return null;
+      } finally {
+        Loading.hide();
+      }
+    },
+    async externalImport(
+      path: string,
+      source: ExternalSource,
+      selected: number[],
+      confirmed: number[],
+    ): Promise<ExternalImportReport | null> {
+      try {
+        Loading.show({
+          message: '正在从其他软件导入...',
+        });
+        const job = await command_library_external_import(path, source, selected, confirmed);
+        const result = await waitJob(job, (progress) =>
+          Loading.show({ message: progressMessage('正在创建条目', progress) }),
+        );
+        const report = result.result as ExternalImportReport;
+        this.data = await command_metadata_get_all();
+        Notify.create({
+          message: '导入成功',
+          caption:
+            report.duplicates.length > 0
+              ? `新增 ${report.created.length} 个条目，${report.duplicates.length} 条记录与已有条目重复，未导入`
+              : `新增 ${report.created.length} 个条目`,
+          color: report.duplicates.length > 0 ? 'warning' : 'positive',
+          position: 'top',
+          icon: 'cloud_done',
+        });
+        return report;
+      } catch (e) {
+        console.error(e);
+        Notify.create({
+          message: '从其他软件导入失败',
+          caption: e as string,
+          color: 'negative',
+          position: 'top',
+          icon: 'error',
+        });
+        return null;
+      } finally {
+        Loading.hide();
+      }
+    },
+  },
   },
 });
 